@@ -109,6 +109,30 @@ impl BitCast for usize {
     }
 }
 
+/// 128-bit values can not be represented by a u64, so they are encoded and decoded via u128.
+pub trait BitCast128: Sized {
+    /// Convert the value to a u128.
+    fn to_u128(&self) -> u128;
+    /// Convert a u128 to the value.
+    fn from_u128(value: u128) -> Self;
+}
+impl BitCast128 for u128 {
+    fn to_u128(&self) -> u128 {
+        *self
+    }
+    fn from_u128(value: u128) -> Self {
+        value
+    }
+}
+impl BitCast128 for i128 {
+    fn to_u128(&self) -> u128 {
+        *self as u128
+    }
+    fn from_u128(value: u128) -> Self {
+        value as i128
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,4 +291,47 @@ mod tests {
         assert_eq!(usize::to_u64(&1), 1);
         assert_eq!(usize::to_u64(&4294967295), 4294967295);
     }
+
+    #[test]
+    fn test_bitcast_u128() {
+        // from
+        assert_eq!(u128::from_u128(0), 0);
+        assert_eq!(u128::from_u128(1), 1);
+        assert_eq!(u128::from_u128(u64::MAX as u128 + 1), 18446744073709551616);
+        assert_eq!(u128::from_u128(u128::MAX), u128::MAX);
+
+        // to
+        assert_eq!(u128::to_u128(&0), 0);
+        assert_eq!(u128::to_u128(&1), 1);
+        assert_eq!(u128::to_u128(&18446744073709551616), u64::MAX as u128 + 1);
+        assert_eq!(u128::to_u128(&u128::MAX), u128::MAX);
+    }
+
+    #[test]
+    fn test_bitcast_i128() {
+        // from
+        assert_eq!(i128::from_u128(0), 0);
+        assert_eq!(i128::from_u128(1), 1);
+        assert_eq!(i128::from_u128(u128::MAX), -1);
+        assert_eq!(i128::from_u128(1 << 127), i128::MIN);
+
+        // to
+        assert_eq!(i128::to_u128(&0), 0);
+        assert_eq!(i128::to_u128(&1), 1);
+        assert_eq!(i128::to_u128(&-1), u128::MAX);
+        assert_eq!(i128::to_u128(&i128::MIN), 1 << 127);
+
+        // round trip
+        for v in [
+            i128::MIN,
+            -18446744073709551616,
+            -1,
+            0,
+            1,
+            1 << 100,
+            i128::MAX,
+        ] {
+            assert_eq!(i128::from_u128(v.to_u128()), v);
+        }
+    }
 }
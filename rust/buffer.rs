@@ -2,12 +2,55 @@
 extern crate alloc;
 
 use crate::bit_cast::BitCast;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::str::Utf8Error;
 
 const MAX_VARINT_LENGTH: usize = u64::BITS as usize * 8 / 7 + 1;
 const BIT_SHIFT: [u64; 10] = [0, 7, 14, 21, 28, 35, 42, 49, 56, 63];
 
+/// Encode a u64 as a varint into `out`, returning the number of bytes written.
+/// A varint takes at most 10 bytes, panics if `out` is too short for the value.
+/// Writes the same bytes as `Buffer::write_varint`, without needing a Buffer.
+pub fn encode_varint(mut val: u64, out: &mut [u8]) -> usize {
+    let mut len = 0;
+    while val >= 0x80 {
+        out[len] = ((val & 0x7f) | 0x80) as u8;
+        val >>= 7;
+        len += 1;
+    }
+    out[len] = val as u8;
+
+    len + 1
+}
+
+/// The number of bytes `encode_varint` writes for a value, from 1 to 10
+pub fn varint_size(mut val: u64) -> usize {
+    let mut size = 1;
+    while val >= 0x80 {
+        val >>= 7;
+        size += 1;
+    }
+
+    size
+}
+
+/// Decode a varint from the start of `data`, returning the value and the number of bytes read.
+/// Reads the same value as `Buffer::read_varint`: at most 10 bytes are read, and if `data`
+/// ends before the varint does, the bytes available are decoded (0 bytes read if empty).
+pub fn decode_varint(data: &[u8]) -> (u64, usize) {
+    let mut val: u64 = 0;
+    for (n, (b, shift)) in data.iter().zip(BIT_SHIFT).enumerate() {
+        val |= (*b as u64 & 0x7f) << shift;
+        if *b < 0x80 {
+            return (val, n + 1);
+        }
+    }
+
+    (val, data.len().min(BIT_SHIFT.len()))
+}
+
 /// The `Buffer` struct is used to read and write Buffer messages.
 ///
 /// # Example
@@ -270,6 +313,27 @@ impl Buffer {
         bytes.copy_from_slice(&value.to_le_bytes());
     }
 
+    /// return the current u128 at position
+    pub fn get_u128_at(&mut self, pos: usize) -> u128 {
+        // Borrow the buffer and slice the next 16 bytes
+        let buf = self.buf.borrow();
+        let bytes = &buf[pos..pos + 16];
+
+        u128::from_le_bytes(bytes.try_into().expect("slice with incorrect length"))
+    }
+
+    /// set the current u128 at position
+    pub fn set_u128_at(&mut self, pos: usize, value: u128) {
+        // Borrow the buffer and slice the next 16 bytes
+        let mut buf = self.buf.borrow_mut();
+        if pos + 16 > buf.len() {
+            buf.resize(pos + 16, 0);
+        }
+        let bytes = &mut buf[pos..pos + 16];
+
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+
     /// Decode a varint from the buffer at the current position.
     pub fn decode_varint(&mut self) -> u64 {
         let buf = self.buf.borrow();
@@ -297,6 +361,30 @@ impl Buffer {
         val
     }
 
+    /// Look at the u8 at the current position without advancing. None if at the end.
+    pub fn peek_u8(&self) -> Option<u8> {
+        self.buf.borrow().get(self.pos).copied()
+    }
+
+    /// Look at the varint at the current position without advancing.
+    /// None if at the end or the varint is cut short.
+    pub fn peek_varint<T>(&self) -> Option<T>
+    where
+        T: BitCast,
+    {
+        let buf = self.buf.borrow();
+        let mut val: u64 = 0;
+        for (pos, shift) in (self.pos..).zip(BIT_SHIFT) {
+            let b = *buf.get(pos)? as u64;
+            val |= (b & 0x7f) << shift;
+            if b < 0x80 {
+                return Some(T::from_u64(val));
+            }
+        }
+
+        Some(T::from_u64(val))
+    }
+
     /// Read in a variable size value from the buffer.
     pub fn read_varint<T>(&mut self) -> T
     where
@@ -321,11 +409,108 @@ impl Buffer {
         buf.push(val as u8);
     }
 
+    /// Read in a zigzag encoded signed variable size value from the buffer.
+    pub fn read_varint_s64(&mut self) -> i64 {
+        let val = self.decode_varint();
+        ((val >> 1) as i64) ^ -((val & 1) as i64)
+    }
+
+    /// Write an i64 to the buffer using zigzag encoding, so small negative values stay small.
+    pub fn write_varint_s64(&mut self, val: i64) {
+        self.write_varint(((val << 1) ^ (val >> 63)) as u64);
+    }
+
+    /// Append raw bytes to the end of the buffer. The position is not changed.
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.buf.borrow_mut().extend_from_slice(data);
+    }
+
+    /// Write raw bytes at the current position and advance by the length of the data.
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        let mut buf = self.buf.borrow_mut();
+        let end = self.pos + data.len();
+        if end > buf.len() {
+            buf.resize(end, 0);
+        }
+        buf[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+    }
+
+    /// Read `len` raw bytes starting at the current position and advance by `len`.
+    pub fn read_bytes(&mut self, len: usize) -> Vec<u8> {
+        let buf = self.buf.borrow();
+        let bytes = buf[self.pos..self.pos + len].to_vec();
+        self.pos += len;
+
+        bytes
+    }
+
+    /// Write a string at the current position as its byte length (a varint) followed by its
+    /// UTF-8 bytes, and advance past it.
+    pub fn write_string(&mut self, s: &str) {
+        let mut len = [0; 10];
+        let n = encode_varint(s.len() as u64, &mut len);
+        self.write_bytes(&len[..n]);
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Write a string like `write_string` at position. The position is not changed.
+    pub fn write_string_at(&mut self, pos: usize, s: &str) {
+        let current = self.pos;
+        self.pos = pos;
+        self.write_string(s);
+        self.pos = current;
+    }
+
+    /// Read a string written by `write_string` at the current position and advance past it,
+    /// even if it is not valid UTF-8. Panics if the buffer ends before the string does.
+    pub fn read_string(&mut self) -> Result<String, Utf8Error> {
+        let len = self.decode_varint() as usize;
+        String::from_utf8(self.read_bytes(len)).map_err(|e| e.utf8_error())
+    }
+
+    /// Read a string written by `write_string` at position. The position is not changed.
+    pub fn read_string_at(&mut self, pos: usize) -> Result<String, Utf8Error> {
+        let current = self.pos;
+        self.pos = pos;
+        let s = self.read_string();
+        self.pos = current;
+
+        s
+    }
+
+    /// Copy the bytes from `start` to `end` (exclusive) into a new Vec. The position is not
+    /// changed. Panics if the range is out of bounds
+    pub fn copy_range(&self, start: usize, end: usize) -> Vec<u8> {
+        let buf = self.buf.borrow();
+        match buf.get(start..end) {
+            Some(bytes) => bytes.to_vec(),
+            None => panic!(
+                "Buffer::copy_range: range {start}..{end} out of bounds for length {}",
+                buf.len()
+            ),
+        }
+    }
+
+    /// Create a new Buffer holding a copy of the bytes from `start` to `end` (exclusive),
+    /// positioned at its start. Panics if the range is out of bounds
+    pub fn sub_buffer(&self, start: usize, end: usize) -> Buffer {
+        Buffer::from_input(RefCell::new(self.copy_range(start, end)))
+    }
+
     /// When done writing to the buffer, call this function to take ownership
     pub fn take(&mut self) -> Vec<u8> {
         self.buf.take()
     }
 }
+/// Formatted strings are appended as UTF-8 (like `extend_from_slice`), so `write!` works
+/// without an intermediate String. The position is not changed.
+impl core::fmt::Write for Buffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -345,6 +530,42 @@ mod tests {
         assert_eq!(vec, buf2.buf.borrow().to_vec());
     }
 
+    #[test]
+    fn test_copy_range() {
+        let mut buf = Buffer::from([0, 1, 2, 3, 4, 5, 6, 7].as_slice());
+        buf.set_pos(3);
+        assert_eq!(buf.copy_range(0, 8), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(buf.copy_range(2, 5), vec![2, 3, 4]);
+        assert_eq!(buf.copy_range(7, 8), vec![7]);
+        assert_eq!(buf.copy_range(8, 8), Vec::<u8>::new());
+        assert_eq!(buf.copy_range(4, 4), Vec::<u8>::new());
+        assert_eq!(buf.pos, 3);
+
+        let mut sub = buf.sub_buffer(4, 8);
+        assert_eq!(sub.len(), 4);
+        assert_eq!(sub.get_u8(), 4);
+        assert_eq!(sub.get_u8_at(3), 7);
+        assert!(buf.sub_buffer(0, 0).is_empty());
+        // the sub buffer is a copy
+        sub.set_u8_at(0, 9);
+        assert_eq!(buf.get_u8_at(4), 4);
+        assert_eq!(buf.pos, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "range 6..9 out of bounds for length 8")]
+    fn test_copy_range_out_of_bounds() {
+        let buf = Buffer::from([0, 1, 2, 3, 4, 5, 6, 7].as_slice());
+        buf.copy_range(6, 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "range 5..2 out of bounds")]
+    fn test_sub_buffer_reversed_range() {
+        let buf = Buffer::from([0, 1, 2, 3, 4, 5, 6, 7].as_slice());
+        buf.sub_buffer(5, 2);
+    }
+
     #[test]
     fn test_set_pos() {
         let mut buf = Buffer::new();
@@ -431,6 +652,22 @@ mod tests {
         assert_eq!(1, buf.get_u64_at(0));
     }
 
+    // get_u128_at & set_u128_at
+    #[test]
+    fn test_get_u128() {
+        let mut buf = Buffer::new();
+        buf.set_u128_at(0, u128::MAX);
+        buf.set_u128_at(16, 340282366920938463463374607431768211);
+        assert_eq!(32, buf.len());
+        assert_eq!(u128::MAX, buf.get_u128_at(0));
+        assert_eq!(340282366920938463463374607431768211, buf.get_u128_at(16));
+        // a write straddling the end grows the buffer
+        let mut buf = Buffer::from(&[0u8; 10][..]);
+        buf.set_u128_at(4, u128::MAX);
+        assert_eq!(buf.len(), 20);
+        assert_eq!(u128::MAX, buf.get_u128_at(4));
+    }
+
     // decode_varint, read_varint, & write_varint
     #[test]
     fn test_decode_varint() {
@@ -445,6 +682,182 @@ mod tests {
         assert_eq!(19393930202, buf.decode_varint());
     }
 
+    // peek_u8 & peek_varint
+    #[test]
+    fn test_peek() {
+        let mut buf = Buffer::new();
+        buf.write_varint(7_u8);
+        buf.write_varint(19393930202_u64);
+        assert_eq!(Some(7), buf.peek_u8());
+        assert_eq!(Some(7), buf.peek_u8());
+        assert_eq!(Some(7_u8), buf.peek_varint());
+        assert_eq!(0, buf.pos);
+        assert_eq!(7, buf.read_varint::<u8>());
+
+        assert_eq!(Some(19393930202_u64), buf.peek_varint());
+        assert_eq!(Some(19393930202_u64), buf.peek_varint());
+        assert_eq!(1, buf.pos);
+        assert_eq!(19393930202, buf.read_varint::<u64>());
+
+        // at the end
+        assert_eq!(None, buf.peek_u8());
+        assert_eq!(None, buf.peek_varint::<u64>());
+        // cut short
+        let buf = Buffer::from([0x80, 0x80].as_slice());
+        assert_eq!(Some(0x80), buf.peek_u8());
+        assert_eq!(None, buf.peek_varint::<u64>());
+    }
+
+    // read_varint_s64 & write_varint_s64
+    #[test]
+    fn test_varint_s64() {
+        let values = [-(1_i64 << 62), -1, 0, 1, 1 << 62, i64::MIN, i64::MAX];
+        let mut buf = Buffer::new();
+        for value in values {
+            buf.write_varint_s64(value);
+        }
+        for value in values {
+            assert_eq!(value, buf.read_varint_s64());
+        }
+
+        // zigzag: 0 -> 0, -1 -> 1, 1 -> 2, -2 -> 3
+        let mut buf = Buffer::new();
+        buf.write_varint_s64(0);
+        buf.write_varint_s64(-1);
+        buf.write_varint_s64(1);
+        buf.write_varint_s64(-2);
+        assert_eq!(vec![0, 1, 2, 3], buf.take());
+    }
+
+    // extend_from_slice, write_bytes & read_bytes
+    #[test]
+    fn test_bytes() {
+        let mut buf = Buffer::new();
+        buf.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(0, buf.pos);
+        assert_eq!(3, buf.len());
+
+        buf.set_pos(2);
+        buf.write_bytes(&[4, 5, 6]);
+        assert_eq!(5, buf.pos);
+        assert_eq!(vec![1, 2, 4, 5, 6], buf.buf.borrow().to_vec());
+
+        buf.set_pos(1);
+        assert_eq!(vec![2, 4], buf.read_bytes(2));
+        assert_eq!(3, buf.pos);
+        assert_eq!(vec![5, 6], buf.read_bytes(2));
+        assert_eq!(Vec::<u8>::new(), buf.read_bytes(0));
+    }
+
+    // write_string, read_string & their _at variants
+    #[test]
+    fn test_string() {
+        let strings = [
+            "",
+            "hello",
+            "Zürich",
+            "東京都",
+            "🗺️ tiles 🚀",
+            &"x".repeat(200),
+        ];
+        let mut buf = Buffer::new();
+        for s in strings {
+            buf.write_string(s);
+        }
+        // 1 byte of length each, 2 for the 200 bytes long one
+        let text_len: usize = strings.iter().map(|s| s.len()).sum();
+        assert_eq!(buf.pos, text_len + strings.len() + 1);
+        assert_eq!(
+            buf.copy_range(0, 7),
+            vec![0, 5, b'h', b'e', b'l', b'l', b'o']
+        );
+
+        buf.set_pos(0);
+        for s in strings {
+            assert_eq!(buf.read_string().as_deref(), Ok(s));
+        }
+        assert_eq!(buf.pos, buf.len());
+
+        // at a position, without moving
+        let mut buf = Buffer::new();
+        buf.write_string_at(4, "東京");
+        assert_eq!(buf.pos, 0);
+        assert_eq!(buf.len(), 4 + 1 + 6);
+        buf.write_string_at(0, "ab");
+        assert_eq!(buf.read_string_at(4).as_deref(), Ok("東京"));
+        assert_eq!(buf.read_string_at(0).as_deref(), Ok("ab"));
+        assert_eq!(buf.pos, 0);
+        assert_eq!(buf.read_string().as_deref(), Ok("ab"));
+
+        // invalid UTF-8 is skipped over all the same
+        let mut buf = Buffer::from([2, 0xC3, 0x28, 1, b'a'].as_slice());
+        let err = buf.read_string().unwrap_err();
+        assert_eq!(err.valid_up_to(), 0);
+        assert_eq!(buf.pos, 3);
+        assert_eq!(buf.read_string().as_deref(), Ok("a"));
+    }
+
+    #[test]
+    fn test_fmt_write() {
+        use core::fmt::Write;
+
+        let mut buf = Buffer::from(b">".as_slice());
+        let name = "Zürich";
+        write!(&mut buf, "{{\"name\": \"{}\", \"zoom\": {}}}", name, 7).unwrap();
+        assert_eq!(0, buf.pos);
+        buf.write_char('✓').unwrap();
+        assert_eq!(buf.copy_range(12, 14), vec![0xC3, 0xBC]);
+        assert_eq!(
+            buf.copy_range(buf.len() - 3, buf.len()),
+            vec![0xE2, 0x9C, 0x93]
+        );
+        assert_eq!(
+            buf.take(),
+            ">{\"name\": \"Zürich\", \"zoom\": 7}✓".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_standalone_varint() {
+        let values = [
+            0,
+            1,
+            127,
+            128,
+            255,
+            300,
+            16_383,
+            16_384,
+            u32::MAX as u64,
+            1 << 56,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        for val in values {
+            let mut buf = Buffer::new();
+            buf.write_varint(val);
+            let expected = buf.take();
+
+            let mut out = [0u8; 10];
+            let len = encode_varint(val, &mut out);
+            assert_eq!(out[..len], expected[..], "{val}");
+            assert_eq!(varint_size(val), len);
+            assert_eq!(decode_varint(&out[..len]), (val, len));
+            // trailing bytes are left alone
+            let mut data = expected.clone();
+            data.extend([0xff, 0x01]);
+            assert_eq!(decode_varint(&data), (val, len));
+            let mut buf = Buffer::from(data.as_slice());
+            assert_eq!(buf.read_varint::<u64>(), val);
+            assert_eq!(buf.pos, len);
+        }
+        assert_eq!(encode_varint(u64::MAX, &mut [0; 10]), 10);
+        assert_eq!(decode_varint(&[]), (0, 0));
+        // cut short
+        assert_eq!(decode_varint(&[0x80 | 5]), (5, 1));
+        assert_eq!(decode_varint(&[0xff; 12]).1, 10);
+    }
+
     // take
     #[test]
     fn test_take() {
@@ -18,6 +18,8 @@ pub mod pmtiles;
 pub mod reader;
 /// The `S2PMTiles` specification tools
 pub mod s2pmtiles;
+/// Tools for merging, splitting, extracting and diffing S2PMTiles and PMTiles archives
+pub mod tools;
 /// The `S2PMTiles` tool for writing S2PMTiles and PMTiles messages
 pub mod writer;
 
@@ -1,14 +1,25 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
-use alloc::string::String;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::cmp::Ordering;
-
-use crate::{bit_cast::BitCast, buffer::Buffer};
-
-/// zoom values for each zoom level. Supports up to 27 zooms
-pub const TZ_VALUES: [u64; 27] = [
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+use crate::{
+    bit_cast::BitCast,
+    buffer::{varint_size, Buffer},
+};
+#[cfg(feature = "std")]
+use s2_tilejson::Face;
+use serde::{Deserialize, Serialize};
+
+/// zoom values for each zoom level. Supports up to 31 zooms (0-30)
+pub const TZ_VALUES: [u64; 31] = [
     0,
     1,
     5,
@@ -36,9 +47,17 @@ pub const TZ_VALUES: [u64; 27] = [
     93824992236885,
     375299968947541,
     1501199875790165,
+    6004799503160661,
+    24019198012642645,
+    96076792050570581,
+    384307168202282325,
 ];
 /// the number of bytes in the header
 pub const HEADER_SIZE_BYTES: usize = 127;
+/// the offset of the center latitude, the last field of the header
+const CENTER_LATITUDE_OFFSET: usize = 123;
+// `Header::to_bytes` must fill exactly `HEADER_SIZE_BYTES`
+const _: () = assert!(CENTER_LATITUDE_OFFSET + core::mem::size_of::<i32>() == HEADER_SIZE_BYTES);
 /// the number of bytes in the root
 pub const ROOT_SIZE: usize = 16_384;
 
@@ -52,7 +71,7 @@ pub struct Point2D {
 }
 
 /// A tile, in the format of ZXY
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Tile {
     /// zoom level
     pub zoom: u8,
@@ -67,19 +86,118 @@ impl Tile {
         Tile { zoom, x, y }
     }
 
+    /// Create a Tile instance from a zoom, x, and y, returning None if it is out of bounds
+    pub fn new_checked(zoom: u8, x: u64, y: u64) -> Option<Tile> {
+        let tile = Tile::new(zoom, x, y);
+        if tile.is_valid() {
+            Some(tile)
+        } else {
+            None
+        }
+    }
+
+    /// The number of tiles at a zoom level (4^zoom). The zoom must be at most 30
+    pub const fn zoom_tile_count(zoom: u8) -> u64 {
+        1u64 << (2 * zoom as u32)
+    }
+
+    /// The range of tile IDs of a zoom level. Panics if the zoom is larger than 30
+    pub fn global_tile_id_range(zoom: u8) -> core::ops::Range<u64> {
+        let start = TZ_VALUES[zoom as usize];
+        start..start + Tile::zoom_tile_count(zoom)
+    }
+
+    /// true if the zoom is at most 30 and x and y are within the bounds of the zoom
+    pub fn is_valid(&self) -> bool {
+        self.zoom <= 30 && self.x < (1 << self.zoom) && self.y < (1 << self.zoom)
+    }
+
+    /// true if this tile contains `other`, i.e. `other` is this tile or one of its descendants
+    pub fn covers(&self, other: &Tile) -> bool {
+        if self.zoom > other.zoom {
+            return false;
+        }
+        let dz = (other.zoom - self.zoom) as u32;
+        other.x.checked_shr(dz).unwrap_or(0) == self.x
+            && other.y.checked_shr(dz).unwrap_or(0) == self.y
+    }
+
+    /// The tile one zoom level up containing this tile, None at zoom 0
+    pub fn parent(&self) -> Option<Tile> {
+        match self.zoom {
+            0 => None,
+            zoom => Some(Tile::new(zoom - 1, self.x >> 1, self.y >> 1)),
+        }
+    }
+
+    /// The highest zoom tile covering both `a` and `b`, walking up the ancestors of the higher
+    /// zoom one. A tile is its own ancestor, and the zoom 0 tile covers everything
+    pub fn common_ancestor(a: &Tile, b: &Tile) -> Tile {
+        let (mut ancestor, other) = if a.zoom >= b.zoom { (*a, b) } else { (*b, a) };
+        while !(ancestor.covers(a) && ancestor.covers(other)) {
+            match ancestor.parent() {
+                Some(parent) => ancestor = parent,
+                None => break,
+            }
+        }
+
+        ancestor
+    }
+
+    /// The URL of the tile on an XYZ tile server, "{base_url}/{zoom}/{x}/{y}.{extension}".
+    /// No "." is added for an empty extension
+    pub fn to_slippy_url(&self, base_url: &str, extension: &str) -> String {
+        tile_url(base_url, self, extension)
+    }
+
+    /// The URL of the tile on a TMS server, whose y axis goes up rather than down:
+    /// "{base_url}/{zoom}/{x}/{2^zoom - 1 - y}.{extension}". The tile must be valid
+    pub fn to_tms_url(&self, base_url: &str, extension: &str) -> String {
+        let flipped = Tile::new(self.zoom, self.x, (1u64 << self.zoom) - 1 - self.y);
+        tile_url(base_url, &flipped, extension)
+    }
+
+    /// Parse the tile of an XYZ URL or path like `https://tile.openstreetmap.org/5/12/30.png`,
+    /// from its last three segments, ignoring the base URL, the extension and any query.
+    /// Segments may be separated by "/" or an encoded "%2F"
+    pub fn from_url_path(path: &str) -> Result<Tile, TileParseError> {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let path = path.replace("%2F", "/").replace("%2f", "/");
+        let mut parts = path.trim_end_matches('/').rsplit('/');
+        let y = parts.next().unwrap_or_default();
+        let y = y.split('.').next().unwrap_or_default();
+        match (parts.next(), parts.next()) {
+            (Some(x), Some(zoom)) => format!("{zoom}/{x}/{y}").parse(),
+            _ => Err(TileParseError::WrongPartCount),
+        }
+    }
+
+    /// Hash the tile by its tile ID rather than by its zoom, x and y
+    pub fn hash_by_id<H: Hasher>(&self, state: &mut H) {
+        self.to_id().hash(state);
+    }
+
     /// Create a Tile instance from an ID
     pub fn from_id(id: u64) -> Tile {
+        match Tile::from_id_checked(id) {
+            Some(tile) => tile,
+            None => unreachable!(),
+        }
+    }
+
+    /// Create a Tile instance from an ID, returning None if the ID is beyond zoom 30
+    pub fn from_id_checked(id: u64) -> Option<Tile> {
         let mut acc = 0;
 
-        for z in 0..27 {
+        for z in 0..31 {
             let num_tiles = (0x1 << z) * (0x1 << z);
             if acc + num_tiles > id {
-                return Tile::from_zoom_pos(z, id - acc);
+                return Some(Tile::from_zoom_pos(z, id - acc));
             }
             acc += num_tiles;
         }
 
-        unreachable!()
+        None
     }
 
     /// Create a Tile instance from a zoom and position
@@ -106,11 +224,16 @@ impl Tile {
 
     /// Convert a Tile instance to an ID
     pub fn to_id(&self) -> u64 {
-        if self.zoom > 26
-            || self.x > 2u64.pow(self.zoom as u32) - 1
-            || self.y > 2u64.pow(self.zoom as u32) - 1
-        {
-            unreachable!()
+        match self.to_id_checked() {
+            Some(id) => id,
+            None => unreachable!(),
+        }
+    }
+
+    /// Convert a Tile instance to an ID, returning None if the tile is out of bounds
+    pub fn to_id_checked(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
         }
 
         let n: u64 = 1 << self.zoom;
@@ -131,12 +254,210 @@ impl Tile {
             s /= 2;
         }
 
-        TZ_VALUES[self.zoom as usize] + (d as u64)
+        Some(TZ_VALUES[self.zoom as usize] + (d as u64))
+    }
+
+    /// Get the Z-order (Morton) position of the tile within its zoom, x in the even bits and
+    /// y in the odd bits. Not used by the PMTiles format, but handy for external sorting.
+    pub fn to_z_order_id(&self) -> u64 {
+        let mut id = 0;
+        for i in 0..self.zoom {
+            id |= ((self.x >> i) & 1) << (2 * i);
+            id |= ((self.y >> i) & 1) << (2 * i + 1);
+        }
+        id
+    }
+
+    /// Create a Tile instance from its Z-order (Morton) position within a zoom
+    pub fn from_z_order_id(zoom: u8, id: u64) -> Tile {
+        let mut tile = Tile::new(zoom, 0, 0);
+        for i in 0..zoom {
+            tile.x |= ((id >> (2 * i)) & 1) << i;
+            tile.y |= ((id >> (2 * i + 1)) & 1) << i;
+        }
+        tile
+    }
+
+    /// Get the row-major position (y * width + x) of the tile within its zoom.
+    /// Not used by the PMTiles format, but handy for scanline processing.
+    pub fn to_row_major_id(&self) -> u64 {
+        (self.y << self.zoom) + self.x
+    }
+
+    /// Create a Tile instance from its row-major position within a zoom
+    pub fn from_row_major_id(zoom: u8, id: u64) -> Tile {
+        let width = 1u64 << zoom;
+        Tile::new(zoom, id % width, id / width)
+    }
+
+    /// Get every S2 face the Web Mercator tile intersects, sampled on a grid over the tile
+    #[cfg(feature = "std")]
+    pub fn to_s2_faces(&self) -> Vec<Face> {
+        let weights = self.s2_face_weights();
+        S2_FACES
+            .into_iter()
+            .zip(weights)
+            .filter(|(_, weight)| *weight > 0.)
+            .map(|(face, _)| face)
+            .collect()
+    }
+
+    /// Get the S2 face covering most of the Web Mercator tile's area
+    #[cfg(feature = "std")]
+    pub fn primary_s2_face(&self) -> Face {
+        let weights = self.s2_face_weights();
+        let mut primary = 0;
+        for (i, weight) in weights.iter().enumerate() {
+            if *weight > weights[primary] {
+                primary = i;
+            }
+        }
+        S2_FACES[primary]
+    }
+
+    /// The area of the tile on each S2 face, sampled on a grid (corners included) and
+    /// weighted by cos²(lat) to undo the mercator stretch
+    #[cfg(feature = "std")]
+    fn s2_face_weights(&self) -> [f64; 6] {
+        const SAMPLES: u32 = 8;
+        let n = (1u64 << self.zoom) as f64;
+        let mut weights = [0.; 6];
+        for i in 0..=SAMPLES {
+            for j in 0..=SAMPLES {
+                let x = self.x as f64 + i as f64 / SAMPLES as f64;
+                let y = self.y as f64 + j as f64 / SAMPLES as f64;
+                let lon = (x / n * 360. - 180.).to_radians();
+                let lat = (core::f64::consts::PI * (1. - 2. * y / n)).sinh().atan();
+                // unit sphere XYZ, the face is the axis with the largest component
+                let xyz = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+                let mut axis = 0;
+                for a in 1..3 {
+                    if xyz[a].abs() > xyz[axis].abs() {
+                        axis = a;
+                    }
+                }
+                let face = if xyz[axis] < 0. { axis + 3 } else { axis };
+                weights[face] += lat.cos().powi(2);
+            }
+        }
+        weights
+    }
+}
+
+/// All six S2 faces in order
+#[cfg(feature = "std")]
+const S2_FACES: [Face; 6] = [
+    Face::Face0,
+    Face::Face1,
+    Face::Face2,
+    Face::Face3,
+    Face::Face4,
+    Face::Face5,
+];
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.zoom, self.x, self.y)
+    }
+}
+/// "{base_url}/{zoom}/{x}/{y}.{extension}", without a doubled "/" or a trailing "."
+fn tile_url(base_url: &str, tile: &Tile, extension: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    if extension.is_empty() {
+        format!("{base_url}/{tile}")
+    } else {
+        format!("{base_url}/{tile}.{}", extension.trim_start_matches('.'))
+    }
+}
+impl From<Tile> for String {
+    fn from(tile: Tile) -> Self {
+        tile.to_string()
+    }
+}
+impl FromStr for Tile {
+    type Err = TileParseError;
+
+    /// Parse a tile in the "zoom/x/y" format
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 3 {
+            return Err(TileParseError::WrongPartCount);
+        }
+        let zoom = parts[0]
+            .parse::<u8>()
+            .map_err(|_| TileParseError::InvalidZoom)?;
+        if zoom > 30 {
+            return Err(TileParseError::ZoomOutOfRange);
+        }
+        let x = parts[1]
+            .parse::<u64>()
+            .ok()
+            .filter(|x| *x < 1 << zoom)
+            .ok_or(TileParseError::InvalidX)?;
+        let y = parts[2]
+            .parse::<u64>()
+            .ok()
+            .filter(|y| *y < 1 << zoom)
+            .ok_or(TileParseError::InvalidY)?;
+
+        Ok(Tile { zoom, x, y })
+    }
+}
+
+/// The errors that can occur when parsing a Tile from a "zoom/x/y" string
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileParseError {
+    /// the string is not made of exactly three "/" separated parts
+    WrongPartCount,
+    /// the zoom is not a valid number
+    InvalidZoom,
+    /// the x coordinate is not a valid number, or too large for the zoom
+    InvalidX,
+    /// the y coordinate is not a valid number, or too large for the zoom
+    InvalidY,
+    /// the zoom is larger than the maximum supported zoom of 30
+    ZoomOutOfRange,
+}
+
+/// All the tile ids of a single zoom level, which are contiguous on the hilbert curve
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileRange {
+    /// the zoom level
+    pub zoom: u8,
+    /// the first tile id of the zoom level
+    pub min_tile_id: u64,
+    /// the last tile id of the zoom level (inclusive)
+    pub max_tile_id: u64,
+}
+impl TileRange {
+    /// The range of tile ids for a zoom level. Panics if the zoom is larger than 30
+    pub fn for_zoom(zoom: u8) -> TileRange {
+        let min_tile_id = TZ_VALUES[zoom as usize];
+        TileRange {
+            zoom,
+            min_tile_id,
+            max_tile_id: min_tile_id + Tile::zoom_tile_count(zoom) - 1,
+        }
+    }
+
+    /// Check if a tile id is in the range
+    pub fn contains(&self, tile_id: u64) -> bool {
+        (self.min_tile_id..=self.max_tile_id).contains(&tile_id)
+    }
+
+    /// The number of tiles in the range
+    pub fn tile_count(&self) -> u64 {
+        self.max_tile_id - self.min_tile_id + 1
+    }
+
+    /// Iterate over every tile id in the range
+    pub fn iter(&self) -> impl Iterator<Item = u64> {
+        self.min_tile_id..=self.max_tile_id
     }
 }
 
 /// PMTiles v3 directory entry.
-#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct Entry {
     /// tile ID
     pub tile_id: u64,
@@ -157,10 +478,65 @@ impl Entry {
             run_length,
         }
     }
+
+    /// Split a run into the entries covering [tile_id, at_tile_id) and
+    /// [at_tile_id, tile_id + run_length), both pointing at the same data.
+    /// None if either part would be empty
+    pub fn split_run(&self, at_tile_id: u64) -> Option<(Entry, Entry)> {
+        if at_tile_id <= self.tile_id || at_tile_id >= self.tile_id + self.run_length as u64 {
+            return None;
+        }
+        let head = (at_tile_id - self.tile_id) as u32;
+        Some((
+            Entry {
+                run_length: head,
+                ..*self
+            },
+            Entry {
+                tile_id: at_tile_id,
+                run_length: self.run_length - head,
+                ..*self
+            },
+        ))
+    }
+
+    /// The number of bytes `Directory::serialize` uses for this entry's tile ID delta, run
+    /// length, length and offset when it follows `prev` (None for the first entry). The
+    /// offset is a single byte when it continues right after `prev`'s data
+    pub fn encoded_size(&self, prev: Option<&Entry>) -> usize {
+        let prev_tile_id = prev.map_or(0, |p| p.tile_id);
+        let offset_size = match prev {
+            Some(p) if p.offset + p.length as u64 == self.offset => 1,
+            _ => varint_size(self.offset + 1),
+        };
+
+        varint_size(self.tile_id.abs_diff(prev_tile_id))
+            + varint_size(self.run_length as u64)
+            + varint_size(self.length as u64)
+            + offset_size
+    }
+
+    /// Merge `next` into this entry's run if it starts right after the run and points at the
+    /// same data. None if it does not, if either is a leaf directory entry or if the combined
+    /// run length would overflow
+    pub fn try_merge(&self, next: &Entry) -> Option<Entry> {
+        if self.run_length == 0
+            || next.run_length == 0
+            || next.tile_id != self.tile_id + self.run_length as u64
+            || next.offset != self.offset
+            || next.length != self.length
+        {
+            return None;
+        }
+        Some(Entry {
+            run_length: self.run_length.checked_add(next.run_length)?,
+            ..*self
+        })
+    }
 }
 
 /// PMTiles v3 directory. A collection of Entry instances for storage
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct Directory {
     /// entries
     pub entries: Vec<Entry>,
@@ -171,6 +547,73 @@ impl Directory {
         Directory { entries }
     }
 
+    /// Create a directory from existing entries, same as `new`
+    pub fn from_entries(entries: Vec<Entry>) -> Directory {
+        Directory::new(entries)
+    }
+
+    /// Create an empty directory with room for `n` entries
+    pub fn with_capacity(n: usize) -> Directory {
+        Directory::new(Vec::with_capacity(n))
+    }
+
+    /// Consume the directory, returning its entries
+    pub fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+
+    /// Create a sorted directory from (tile, offset, length) tuples, each tile a run of 1.
+    /// If a tile shows up more than once, the last occurrence is kept.
+    pub fn from_tile_data(tiles: &[(Tile, u64, u32)]) -> Directory {
+        let ids: Vec<(u64, u64, u32)> = tiles
+            .iter()
+            .map(|(tile, offset, length)| (tile.to_id(), *offset, *length))
+            .collect();
+        Directory::from_tile_ids(&ids)
+    }
+
+    /// Create a sorted directory from (tile_id, offset, length) tuples, each tile a run of 1.
+    /// If a tile id shows up more than once, the last occurrence is kept.
+    pub fn from_tile_ids(ids: &[(u64, u64, u32)]) -> Directory {
+        let unique: BTreeMap<u64, (u64, u32)> = ids
+            .iter()
+            .map(|(tile_id, offset, length)| (*tile_id, (*offset, *length)))
+            .collect();
+        let entries = unique
+            .into_iter()
+            .map(|(tile_id, (offset, length))| Entry::new(tile_id, offset, length, 1))
+            .collect();
+        Directory { entries }
+    }
+
+    /// Export the directory as a JSON array of entries, each with its tile coordinates
+    pub fn to_json(&self) -> String {
+        let entries: Vec<EntryJson> = self
+            .entries
+            .iter()
+            .map(|e| EntryJson {
+                tile_id: e.tile_id,
+                tile_xyz: Some(TileJson::from(Tile::from_id(e.tile_id))),
+                offset: e.offset,
+                length: e.length,
+                run_length: e.run_length,
+            })
+            .collect();
+        serde_json::to_string(&entries).unwrap()
+    }
+
+    /// Create a directory from a JSON array of entries, see `to_json`.
+    /// The tile coordinates are ignored in favor of the tile_id
+    pub fn from_json(s: &str) -> Result<Directory, serde_json::Error> {
+        let entries: Vec<EntryJson> = serde_json::from_str(s)?;
+        Ok(Directory::new(
+            entries
+                .into_iter()
+                .map(|e| Entry::new(e.tile_id, e.offset, e.length, e.run_length))
+                .collect(),
+        ))
+    }
+
     /// Create a new directory from a buffer
     pub fn from_buffer(buffer: &mut Buffer) -> Directory {
         let num_entries = buffer.read_varint::<usize>();
@@ -236,6 +679,20 @@ impl Directory {
         buffer.take()
     }
 
+    /// The size of `serialize`'s output, computed from the varint length of every field
+    /// without allocating, so it is cheap enough to check before serializing.
+    /// Exact for sorted entries, which serializing requires. For unsorted entries (like a
+    /// writer's before it sorts them to commit) it is an approximation of the sorted size
+    pub fn approximate_byte_size(&self) -> usize {
+        self.encoded_size()
+    }
+
+    /// The exact size of `serialize`'s output for sorted entries: the entry count and every
+    /// `Entry::encoded_size`, without allocating
+    pub fn encoded_size(&self) -> usize {
+        entries_byte_size(&self.entries)
+    }
+
     /// Check if the directory is empty
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
@@ -289,6 +746,183 @@ impl Directory {
     pub fn last_mut(&mut self) -> Option<&mut Entry> {
         self.entries.last_mut()
     }
+
+    /// Get the entries that address tiles in the range, including an entry starting before the
+    /// range whose run reaches into it. The entries must be sorted.
+    pub fn entries_in_range(&self, range: &TileRange) -> &[Entry] {
+        let mut start = self
+            .entries
+            .partition_point(|e| e.tile_id < range.min_tile_id);
+        let end = self
+            .entries
+            .partition_point(|e| e.tile_id <= range.max_tile_id);
+        if let Some(prev) = start.checked_sub(1).map(|i| &self.entries[i]) {
+            if prev.tile_id + prev.run_length as u64 > range.min_tile_id {
+                start -= 1;
+            }
+        }
+        &self.entries[start..end]
+    }
+
+    /// Sort the entries by tile ID
+    pub fn sort(&mut self) {
+        self.entries.sort_by_key(|e| e.tile_id);
+    }
+
+    /// Merge consecutive entries pointing at the same data into runs, see `Entry::try_merge`.
+    /// The entries must be sorted
+    pub fn merge_runs(&mut self) {
+        let mut entries: Vec<Entry> = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.drain(..) {
+            match entries.last_mut() {
+                Some(last) => match last.try_merge(&entry) {
+                    Some(merged) => *last = merged,
+                    None => entries.push(entry),
+                },
+                None => entries.push(entry),
+            }
+        }
+        self.entries = entries;
+    }
+
+    /// Remove a single tile, splitting the run covering it if needed. Leaf directory entries
+    /// are left alone. The entries must be sorted. Returns true if a tile was removed
+    pub fn remove_tile_id(&mut self, tile_id: u64) -> bool {
+        match find_tile(&self.entries, tile_id) {
+            Some(entry) if entry.run_length > 0 => self.remove_range(tile_id, tile_id) > 0,
+            _ => false,
+        }
+    }
+
+    /// Remove every tile from min_id to max_id (inclusive), trimming or splitting the runs
+    /// that cross the bounds. Leaf directory entries are left alone.
+    /// Returns the number of entries removed or modified
+    pub fn remove_range(&mut self, min_id: u64, max_id: u64) -> usize {
+        let mut changed = 0;
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.drain(..) {
+            let end = entry.tile_id + entry.run_length as u64;
+            if entry.run_length == 0 || end <= min_id || entry.tile_id > max_id {
+                entries.push(entry);
+                continue;
+            }
+            changed += 1;
+            // keep the parts of the run outside the range
+            if let Some((head, _)) = entry.split_run(min_id) {
+                entries.push(head);
+            }
+            if let Some((_, tail)) = entry.split_run(max_id.saturating_add(1)) {
+                entries.push(tail);
+            }
+        }
+        self.entries = entries;
+
+        changed
+    }
+
+    /// Merge two directories into a sorted one. If both have an entry for the same tile ID,
+    /// the entry of `other` is kept.
+    pub fn merge(self, other: Directory) -> Directory {
+        let mut merged = BTreeMap::new();
+        for entry in self.entries.into_iter().chain(other.entries) {
+            merged.insert(entry.tile_id, entry);
+        }
+        Directory::new(merged.into_values().collect())
+    }
+
+    /// Check the directory is sorted, free of duplicate tile IDs, overlapping runs and empty
+    /// entries, returning every problem found
+    pub fn validate(&self) -> Result<(), Vec<DirectoryError>> {
+        let mut errors = Vec::new();
+        let mut prev: Option<&Entry> = None;
+        for entry in &self.entries {
+            let tile_id = entry.tile_id;
+            if entry.length == 0 {
+                errors.push(DirectoryError::EmptyEntry { tile_id });
+            }
+            if let Some(prev) = prev {
+                if tile_id < prev.tile_id {
+                    errors.push(DirectoryError::Unsorted { tile_id });
+                } else if tile_id == prev.tile_id {
+                    errors.push(DirectoryError::DuplicateTileId { tile_id });
+                } else if tile_id < prev.tile_id + prev.run_length as u64 {
+                    errors.push(DirectoryError::OverlappingRun { tile_id });
+                }
+            }
+            prev = Some(entry);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The serialized size of a directory holding these entries, see
+/// `Directory::approximate_byte_size`
+pub(crate) fn entries_byte_size(entries: &[Entry]) -> usize {
+    let mut size = varint_size(entries.len() as u64);
+    let mut prev = None;
+    for e in entries {
+        size += e.encoded_size(prev);
+        prev = Some(e);
+    }
+
+    size
+}
+
+/// A problem found by `Directory::validate`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DirectoryError {
+    /// the entry comes before the previous entry's tile ID
+    Unsorted {
+        /// the tile ID of the entry
+        tile_id: u64,
+    },
+    /// the entry has the same tile ID as the previous entry
+    DuplicateTileId {
+        /// the tile ID of the entry
+        tile_id: u64,
+    },
+    /// the entry starts inside the run of the previous entry
+    OverlappingRun {
+        /// the tile ID of the entry
+        tile_id: u64,
+    },
+    /// the entry points to no data
+    EmptyEntry {
+        /// the tile ID of the entry
+        tile_id: u64,
+    },
+}
+
+/// The JSON form of a directory entry
+#[derive(Serialize, Deserialize)]
+struct EntryJson {
+    tile_id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tile_xyz: Option<TileJson>,
+    offset: u64,
+    length: u32,
+    run_length: u32,
+}
+
+/// The JSON form of a tile's coordinates
+#[derive(Serialize, Deserialize)]
+struct TileJson {
+    zoom: u8,
+    x: u64,
+    y: u64,
+}
+impl From<Tile> for TileJson {
+    fn from(tile: Tile) -> Self {
+        TileJson {
+            zoom: tile.zoom,
+            x: tile.x,
+            y: tile.y,
+        }
+    }
 }
 
 /// Enum representing a compression algorithm used.
@@ -297,7 +931,9 @@ impl Directory {
 /// 2 = gzip
 /// 3 = brotli
 /// 4 = zstd
-#[derive(Debug, Copy, Clone, Default, PartialEq)]
+/// 5 = zlib (non-standard)
+/// 6 = lz4 (non-standard)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Compression {
     /// unknown compression, for if you must use a different or unspecified algorithm
     Unknown = 0,
@@ -310,6 +946,10 @@ pub enum Compression {
     Brotli = 3,
     /// zstd
     Zstd = 4,
+    /// zlib. Not part of the PMTiles v3 spec, only supported for reading older archives.
+    Zlib = 5,
+    /// lz4 (frame format). Not part of the PMTiles v3 spec, only supported for reading.
+    Lz4 = 6,
 }
 impl From<u8> for Compression {
     fn from(value: u8) -> Self {
@@ -318,6 +958,8 @@ impl From<u8> for Compression {
             2 => Compression::Gzip,
             3 => Compression::Brotli,
             4 => Compression::Zstd,
+            5 => Compression::Zlib,
+            6 => Compression::Lz4,
             _ => Compression::Unknown,
         }
     }
@@ -330,7 +972,47 @@ impl From<Compression> for u8 {
             Compression::Gzip => 2,
             Compression::Brotli => 3,
             Compression::Zstd => 4,
+            Compression::Zlib => 5,
+            Compression::Lz4 => 6,
+        }
+    }
+}
+impl Compression {
+    /// Parse an HTTP `Accept-Encoding` header into the compressions it accepts, most preferred
+    /// first (by q-value, then in header order). `identity` is `None` and `deflate` is `Zlib`.
+    /// Codings refused with `q=0`, unknown ones and `*` are left out. If a compression is
+    /// listed twice (`gzip` and `x-gzip`), the first one counts
+    pub fn from_accept_encoding(s: &str) -> Vec<Compression> {
+        let mut accepted: Vec<(Compression, f32)> = Vec::new();
+        for coding in s.split(',') {
+            let mut parts = coding.split(';');
+            let name = parts.next().unwrap_or_default().trim();
+            let mut q = 1.0;
+            for param in parts {
+                if let Some((key, value)) = param.split_once('=') {
+                    if key.trim().eq_ignore_ascii_case("q") {
+                        // a malformed q-value refuses the coding
+                        q = value.trim().parse::<f32>().unwrap_or(0.0);
+                    }
+                }
+            }
+            let compression = match name.to_ascii_lowercase().as_str() {
+                "identity" => Compression::None,
+                "gzip" | "x-gzip" => Compression::Gzip,
+                "br" => Compression::Brotli,
+                "zstd" => Compression::Zstd,
+                "deflate" => Compression::Zlib,
+                _ => continue,
+            };
+            if !accepted.iter().any(|(c, _)| *c == compression) {
+                accepted.push((compression, q));
+            }
         }
+        accepted.retain(|(_, q)| *q > 0.0);
+        // stable, so equal q-values keep the header order
+        accepted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(core::cmp::Ordering::Equal));
+
+        accepted.into_iter().map(|(c, _)| c).collect()
     }
 }
 impl From<Compression> for String {
@@ -340,6 +1022,8 @@ impl From<Compression> for String {
             Compression::Gzip => "gzip".into(),
             Compression::Brotli => "br".into(),
             Compression::Zstd => "zstd".into(),
+            Compression::Zlib => "zlib".into(),
+            Compression::Lz4 => "lz4".into(),
             Compression::Unknown => "unknown".into(),
         }
     }
@@ -347,7 +1031,7 @@ impl From<Compression> for String {
 
 /// Describe the type of tiles stored in the archive.
 /// 0 is unknown/other, 1 is "MVT" vector tiles.
-#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TileType {
     /// unknown/other.
     Unknown = 0,
@@ -362,6 +1046,87 @@ pub enum TileType {
     Webp = 4,
     /// Image tiles.
     Avif = 5,
+    /// Image tiles (legacy overlays). Reserved value, not part of the PMTiles v3 spec.
+    Gif = 6,
+    /// GeoTIFF image tiles. Reserved value, not part of the PMTiles v3 spec.
+    Tiff = 7,
+}
+impl TileType {
+    /// The MIME type of the tiles
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            TileType::Unknown => "application/octet-stream",
+            TileType::Pbf => "application/x-protobuf",
+            TileType::Png => "image/png",
+            TileType::Jpeg => "image/jpeg",
+            TileType::Webp => "image/webp",
+            TileType::Avif => "image/avif",
+            TileType::Gif => "image/gif",
+            TileType::Tiff => "image/tiff",
+        }
+    }
+
+    /// The media type registered with IANA for the tiles, `application/octet-stream` if unknown.
+    /// Unlike `mime_type`, vector tiles are `application/vnd.mapbox-vector-tile`
+    pub fn to_iana_media_type(&self) -> &'static str {
+        match self {
+            TileType::Pbf => "application/vnd.mapbox-vector-tile",
+            _ => self.mime_type(),
+        }
+    }
+
+    /// Find the tile type of a media type, like a `Content-Type` header. Matching ignores case
+    /// and any parameters (`; charset=utf-8`). The types returned by `mime_type` are accepted
+    /// too, `application/octet-stream` is `Unknown` and anything else is None
+    pub fn from_iana_media_type(s: &str) -> Option<TileType> {
+        let media_type = s.split(';').next().unwrap_or_default().trim();
+        [
+            TileType::Pbf,
+            TileType::Png,
+            TileType::Jpeg,
+            TileType::Webp,
+            TileType::Avif,
+            TileType::Gif,
+            TileType::Tiff,
+            TileType::Unknown,
+        ]
+        .into_iter()
+        .find(|t| {
+            media_type.eq_ignore_ascii_case(t.to_iana_media_type())
+                || media_type.eq_ignore_ascii_case(t.mime_type())
+        })
+    }
+
+    /// The file extension of the tiles, empty if unknown
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TileType::Unknown => "",
+            TileType::Pbf => ".pbf",
+            TileType::Png => ".png",
+            TileType::Jpeg => ".jpg",
+            TileType::Webp => ".webp",
+            TileType::Avif => ".avif",
+            TileType::Gif => ".gif",
+            TileType::Tiff => ".tif",
+        }
+    }
+
+    /// Guess the tile type from the first bytes of a tile, `Unknown` if they match nothing.
+    /// Vector tiles only need to start with a valid protobuf field tag, so they are checked
+    /// last. Compressed tiles (gzip, brotli, ...) are not decompressed and come out `Unknown`.
+    pub fn from_file_bytes(bytes: &[u8]) -> TileType {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => TileType::Png,
+            [0xFF, 0xD8, 0xFF, ..] => TileType::Jpeg,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => TileType::Webp,
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => TileType::Avif,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => TileType::Gif,
+            [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => TileType::Tiff,
+            // field number above 0 and wire type below 6
+            [tag, ..] if tag >> 3 != 0 && tag & 0x07 < 6 => TileType::Pbf,
+            _ => TileType::Unknown,
+        }
+    }
 }
 impl From<u8> for TileType {
     fn from(value: u8) -> Self {
@@ -371,6 +1136,8 @@ impl From<u8> for TileType {
             3 => TileType::Jpeg,
             4 => TileType::Webp,
             5 => TileType::Avif,
+            6 => TileType::Gif,
+            7 => TileType::Tiff,
             _ => TileType::Unknown,
         }
     }
@@ -384,6 +1151,8 @@ impl From<TileType> for u8 {
             TileType::Jpeg => 3,
             TileType::Webp => 4,
             TileType::Avif => 5,
+            TileType::Gif => 6,
+            TileType::Tiff => 7,
         }
     }
 }
@@ -396,6 +1165,37 @@ impl From<TileType> for String {
             TileType::Jpeg => "jpeg".into(),
             TileType::Webp => "webp".into(),
             TileType::Avif => "avif".into(),
+            TileType::Gif => "gif".into(),
+            TileType::Tiff => "tiff".into(),
+        }
+    }
+}
+
+/// A geographic bounding box in decimal degrees
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BoundingBox {
+    /// the min longitude
+    pub min_longitude: f32,
+    /// the min latitude
+    pub min_latitude: f32,
+    /// the max longitude
+    pub max_longitude: f32,
+    /// the max latitude
+    pub max_latitude: f32,
+}
+impl BoundingBox {
+    /// Create a new bounding box
+    pub fn new(
+        min_longitude: f32,
+        min_latitude: f32,
+        max_longitude: f32,
+        max_latitude: f32,
+    ) -> Self {
+        BoundingBox {
+            min_longitude,
+            min_latitude,
+            max_longitude,
+            max_latitude,
         }
     }
 }
@@ -456,6 +1256,11 @@ pub struct Header {
     pub center_latitude: f32,
 }
 impl Header {
+    /// true if the bytes start with the PMTiles magic bytes "PM"
+    pub fn magic_bytes_valid(bytes: &[u8]) -> bool {
+        bytes.starts_with(&[0x50, 0x4D])
+    }
+
     /// Create a new Header from a buffer
     pub fn from_bytes(buffer: &mut Buffer) -> Header {
         Header {
@@ -483,11 +1288,13 @@ impl Header {
             max_latitude: (buffer.get_i32_at(114) as f32) / 10_000_000.0,
             center_zoom: buffer.get_u8_at(118),
             center_longitude: (buffer.get_i32_at(119) as f32) / 10_000_000.0,
-            center_latitude: (buffer.get_i32_at(123) as f32) / 10_000_000.0,
+            center_latitude: (buffer.get_i32_at(CENTER_LATITUDE_OFFSET) as f32) / 10_000_000.0,
         }
     }
 
-    /// Write the header to a buffer
+    /// Write the header to a buffer.
+    /// Coordinates are stored as `i32` values in units of 1/10_000_000 degrees (E7) while the
+    /// Header itself always stores decimal degrees.
     pub fn to_bytes(&self) -> Buffer {
         let mut buffer = Buffer::new();
 
@@ -534,10 +1341,250 @@ impl Header {
         // Center zoom and center coordinates
         buffer.set_u8_at(118, self.center_zoom);
         buffer.set_i32_at(119, (self.center_longitude * 10_000_000.0) as i32);
-        buffer.set_i32_at(123, (self.center_latitude * 10_000_000.0) as i32);
+        buffer.set_i32_at(
+            CENTER_LATITUDE_OFFSET,
+            (self.center_latitude * 10_000_000.0) as i32,
+        );
 
         buffer
     }
+
+    /// A single line summary of the archive
+    pub fn summary(&self) -> String {
+        format!(
+            "PMTiles v{} {} z{}..z{} {} tiles {}",
+            self.version,
+            String::from(self.tile_type),
+            self.min_zoom,
+            self.max_zoom,
+            self.n_addressed_tiles,
+            format_bytes(self.data_length)
+        )
+    }
+}
+impl fmt::Display for Header {
+    /// A human-readable summary, or with `{:#}` one "key=value" line per field, see `FromStr`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write_key_values(
+                f,
+                &[
+                    ("version", self.version.to_string()),
+                    (
+                        "root_directory_offset",
+                        self.root_directory_offset.to_string(),
+                    ),
+                    (
+                        "root_directory_length",
+                        self.root_directory_length.to_string(),
+                    ),
+                    ("metadata_offset", self.metadata_offset.to_string()),
+                    ("metadata_length", self.metadata_length.to_string()),
+                    (
+                        "leaf_directory_offset",
+                        self.leaf_directory_offset.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length",
+                        self.leaf_directory_length.to_string(),
+                    ),
+                    ("data_offset", self.data_offset.to_string()),
+                    ("data_length", self.data_length.to_string()),
+                    ("n_addressed_tiles", self.n_addressed_tiles.to_string()),
+                    ("n_tile_entries", self.n_tile_entries.to_string()),
+                    ("n_tile_contents", self.n_tile_contents.to_string()),
+                    ("clustered", self.clustered.to_string()),
+                    ("internal_compression", self.internal_compression.into()),
+                    ("tile_compression", self.tile_compression.into()),
+                    ("tile_type", self.tile_type.into()),
+                    ("min_zoom", self.min_zoom.to_string()),
+                    ("max_zoom", self.max_zoom.to_string()),
+                    ("min_longitude", self.min_longitude.to_string()),
+                    ("min_latitude", self.min_latitude.to_string()),
+                    ("max_longitude", self.max_longitude.to_string()),
+                    ("max_latitude", self.max_latitude.to_string()),
+                    ("center_zoom", self.center_zoom.to_string()),
+                    ("center_longitude", self.center_longitude.to_string()),
+                    ("center_latitude", self.center_latitude.to_string()),
+                ],
+            );
+        }
+        writeln!(f, "PMTiles v{}", self.version)?;
+        writeln!(
+            f,
+            "tile type: {}, tile compression: {}, internal compression: {}",
+            String::from(self.tile_type),
+            String::from(self.tile_compression),
+            String::from(self.internal_compression)
+        )?;
+        writeln!(f, "zooms: z{}..z{}", self.min_zoom, self.max_zoom)?;
+        writeln!(
+            f,
+            "bounds: [{:.7}, {:.7}, {:.7}, {:.7}]",
+            self.min_longitude, self.min_latitude, self.max_longitude, self.max_latitude
+        )?;
+        writeln!(
+            f,
+            "center: [{:.7}, {:.7}] z{}",
+            self.center_longitude, self.center_latitude, self.center_zoom
+        )?;
+        writeln!(
+            f,
+            "root directory: {}, metadata: {}, leaf directories: {}, tile data: {}",
+            format_bytes(self.root_directory_length),
+            format_bytes(self.metadata_length),
+            format_bytes(self.leaf_directory_length),
+            format_bytes(self.data_length)
+        )?;
+        write!(
+            f,
+            "tiles: {} addressed, {} entries, {} contents, clustered: {}",
+            self.n_addressed_tiles, self.n_tile_entries, self.n_tile_contents, self.clustered
+        )
+    }
+}
+
+impl FromStr for Header {
+    type Err = HeaderParseError;
+
+    /// Parse a header from "key=value" lines, as written by `format!("{header:#}")`.
+    /// Blank lines and lines starting with '#' are skipped, missing keys keep their default
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut h = Header::default();
+        parse_key_values(s, |key, v| {
+            Some(match key {
+                "version" => set(v.parse().ok(), &mut h.version),
+                "root_directory_offset" => set(v.parse().ok(), &mut h.root_directory_offset),
+                "root_directory_length" => set(v.parse().ok(), &mut h.root_directory_length),
+                "metadata_offset" => set(v.parse().ok(), &mut h.metadata_offset),
+                "metadata_length" => set(v.parse().ok(), &mut h.metadata_length),
+                "leaf_directory_offset" => set(v.parse().ok(), &mut h.leaf_directory_offset),
+                "leaf_directory_length" => set(v.parse().ok(), &mut h.leaf_directory_length),
+                "data_offset" => set(v.parse().ok(), &mut h.data_offset),
+                "data_length" => set(v.parse().ok(), &mut h.data_length),
+                "n_addressed_tiles" => set(v.parse().ok(), &mut h.n_addressed_tiles),
+                "n_tile_entries" => set(v.parse().ok(), &mut h.n_tile_entries),
+                "n_tile_contents" => set(v.parse().ok(), &mut h.n_tile_contents),
+                "clustered" => set(v.parse().ok(), &mut h.clustered),
+                "internal_compression" => {
+                    set(compression_from_name(v), &mut h.internal_compression)
+                }
+                "tile_compression" => set(compression_from_name(v), &mut h.tile_compression),
+                "tile_type" => set(tile_type_from_name(v), &mut h.tile_type),
+                "min_zoom" => set(v.parse().ok(), &mut h.min_zoom),
+                "max_zoom" => set(v.parse().ok(), &mut h.max_zoom),
+                "min_longitude" => set(v.parse().ok(), &mut h.min_longitude),
+                "min_latitude" => set(v.parse().ok(), &mut h.min_latitude),
+                "max_longitude" => set(v.parse().ok(), &mut h.max_longitude),
+                "max_latitude" => set(v.parse().ok(), &mut h.max_latitude),
+                "center_zoom" => set(v.parse().ok(), &mut h.center_zoom),
+                "center_longitude" => set(v.parse().ok(), &mut h.center_longitude),
+                "center_latitude" => set(v.parse().ok(), &mut h.center_latitude),
+                _ => return None,
+            })
+        })?;
+
+        Ok(h)
+    }
+}
+
+/// The errors that can occur when parsing a header from "key=value" lines
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeaderParseError {
+    /// the line is not a "key=value" pair
+    MalformedLine {
+        /// the line number, starting at 1
+        line: usize,
+    },
+    /// the key is not a field of the header
+    UnknownKey {
+        /// the line number, starting at 1
+        line: usize,
+    },
+    /// the value can not be parsed for its key
+    InvalidValue {
+        /// the line number, starting at 1
+        line: usize,
+    },
+}
+
+/// Write (key, value) pairs as "key=value" lines
+pub(crate) fn write_key_values(
+    f: &mut fmt::Formatter<'_>,
+    fields: &[(&str, String)],
+) -> fmt::Result {
+    for (i, (key, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{}={}", key, value)?;
+    }
+    Ok(())
+}
+
+/// Parse "key=value" lines, calling `set` with each pair. `set` returns None if the key is
+/// unknown and Some(false) if the value is invalid
+pub(crate) fn parse_key_values(
+    s: &str,
+    mut set: impl FnMut(&str, &str) -> Option<bool>,
+) -> Result<(), HeaderParseError> {
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line_number = i + 1;
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(HeaderParseError::MalformedLine { line: line_number })?;
+        match set(key.trim(), value.trim()) {
+            None => return Err(HeaderParseError::UnknownKey { line: line_number }),
+            Some(false) => return Err(HeaderParseError::InvalidValue { line: line_number }),
+            Some(true) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Store a parsed value, returning false if it could not be parsed
+pub(crate) fn set<T>(value: Option<T>, target: &mut T) -> bool {
+    match value {
+        Some(value) => {
+            *target = value;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Find the compression with the given name, as written by `From<Compression> for String`
+pub(crate) fn compression_from_name(name: &str) -> Option<Compression> {
+    (0..=6u8)
+        .map(Compression::from)
+        .find(|c| String::from(*c) == name)
+}
+
+/// Find the tile type with the given name, as written by `From<TileType> for String`
+pub(crate) fn tile_type_from_name(name: &str) -> Option<TileType> {
+    (0..=7u8)
+        .map(TileType::from)
+        .find(|t| String::from(*t) == name)
+}
+
+/// Format a byte count using human-readable units (B, KB, MB, GB)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
 }
 
 /// rotate xy by n
@@ -585,6 +1632,7 @@ pub fn find_tile(entries: &[Entry], tile_id: u64) -> Option<Entry> {
 mod tests {
     use super::*;
     use alloc::vec;
+    use proptest::prelude::*;
 
     #[test]
     fn test_tile() {
@@ -634,19 +1682,764 @@ mod tests {
         let id = tile.to_id();
         assert_eq!(id, 366567509724);
         assert_eq!(Tile::from_id(id), tile);
+
+        // zooms 27-30
+        let tile = Tile::new(27, 0, 0);
+        let id = tile.to_id();
+        assert_eq!(id, TZ_VALUES[27]);
+        assert_eq!(Tile::from_id(id), tile);
+        let tile = Tile::new(27, 123_456_789, 98_765_432);
+        let id = tile.to_id();
+        assert_eq!(id, 18_650_696_564_664_872);
+        assert_eq!(Tile::from_id(id), tile);
+        let max = (1 << 30) - 1;
+        let tile = Tile::new(30, max, max);
+        let id = tile.to_id();
+        assert_eq!(id, 1_152_921_504_606_846_975);
+        assert_eq!(Tile::from_id(id), tile);
     }
 
-    // Entry
+    // is_valid & checked variants
     #[test]
-    fn test_entry() {
-        let entry = Entry {
-            tile_id: 1,
-            offset: 2,
-            length: 3,
-            run_length: 4,
-        };
-        assert_eq!(entry, Entry::new(1, 2, 3, 4));
-    }
+    fn test_tile_checked() {
+        assert!(Tile::new(0, 0, 0).is_valid());
+        assert!(Tile::new(3, 7, 7).is_valid());
+        assert!(!Tile::new(3, 8, 0).is_valid());
+        assert!(!Tile::new(3, 0, 8).is_valid());
+        assert!(!Tile::new(31, 0, 0).is_valid());
+        assert!(!Tile::new(255, 0, 0).is_valid());
+
+        assert_eq!(Tile::new_checked(3, 7, 7), Some(Tile::new(3, 7, 7)));
+        assert_eq!(Tile::new_checked(3, 8, 7), None);
+
+        assert_eq!(
+            Tile::new(20, 1_002, 6_969).to_id_checked(),
+            Some(366567509724)
+        );
+        assert_eq!(Tile::new(2, 4, 0).to_id_checked(), None);
+
+        assert_eq!(
+            Tile::from_id_checked(366567509724),
+            Some(Tile::new(20, 1_002, 6_969))
+        );
+        let last_id = TZ_VALUES[30] + (1 << 60) - 1;
+        assert!(Tile::from_id_checked(last_id).is_some());
+        assert_eq!(Tile::from_id_checked(last_id + 1), None);
+        assert_eq!(Tile::from_id_checked(u64::MAX), None);
+    }
+
+    // covers
+    #[test]
+    fn test_tile_alternative_orderings() {
+        // known morton codes at zoom 2
+        assert_eq!(Tile::new(2, 0, 0).to_z_order_id(), 0);
+        assert_eq!(Tile::new(2, 1, 0).to_z_order_id(), 1);
+        assert_eq!(Tile::new(2, 0, 1).to_z_order_id(), 2);
+        assert_eq!(Tile::new(2, 1, 1).to_z_order_id(), 3);
+        assert_eq!(Tile::new(2, 2, 0).to_z_order_id(), 4);
+        assert_eq!(Tile::new(2, 0, 2).to_z_order_id(), 8);
+        assert_eq!(Tile::new(2, 3, 3).to_z_order_id(), 15);
+        assert_eq!(Tile::new(3, 5, 6).to_z_order_id(), 0b111001);
+        assert_eq!(Tile::new(2, 3, 1).to_row_major_id(), 7);
+        assert_eq!(Tile::new(30, 0, 1).to_row_major_id(), 1 << 30);
+
+        for zoom in 0..6u8 {
+            let count = 1u64 << (2 * zoom);
+            for id in 0..count {
+                let tile = Tile::from_z_order_id(zoom, id);
+                assert!(tile.is_valid());
+                assert_eq!(tile.to_z_order_id(), id);
+                let tile = Tile::from_row_major_id(zoom, id);
+                assert!(tile.is_valid());
+                assert_eq!(tile.to_row_major_id(), id);
+            }
+        }
+        let tile = Tile::new(30, (1 << 30) - 1, 12345);
+        assert_eq!(Tile::from_z_order_id(30, tile.to_z_order_id()), tile);
+        assert_eq!(Tile::from_row_major_id(30, tile.to_row_major_id()), tile);
+    }
+
+    #[test]
+    fn test_zoom_tile_count() {
+        assert_eq!(Tile::zoom_tile_count(0), 1);
+        assert_eq!(Tile::zoom_tile_count(1), 4);
+        assert_eq!(Tile::zoom_tile_count(2), 16);
+        assert_eq!(Tile::zoom_tile_count(30), 1 << 60);
+        const COUNT: u64 = Tile::zoom_tile_count(3);
+        assert_eq!(COUNT, 64);
+
+        let sum: u64 = (0..=26).map(Tile::zoom_tile_count).sum();
+        assert_eq!(sum, TZ_VALUES[26] + Tile::zoom_tile_count(26));
+        for zoom in 0..30u8 {
+            let range = Tile::global_tile_id_range(zoom);
+            assert_eq!(
+                range,
+                TZ_VALUES[zoom as usize]..TZ_VALUES[zoom as usize + 1]
+            );
+            assert_eq!(Tile::from_id(range.start).zoom, zoom);
+            assert_eq!(Tile::from_id(range.end - 1).zoom, zoom);
+        }
+        assert_eq!(
+            Tile::global_tile_id_range(30).end,
+            TZ_VALUES[30] + (1 << 60)
+        );
+    }
+
+    #[test]
+    fn test_tile_range() {
+        for zoom in 0..8u8 {
+            let range = TileRange::for_zoom(zoom);
+            assert_eq!(range.min_tile_id, TZ_VALUES[zoom as usize]);
+            assert_eq!(range.max_tile_id, TZ_VALUES[zoom as usize + 1] - 1);
+            assert_eq!(range.tile_count(), 1 << (2 * zoom));
+            assert_eq!(
+                range.iter().count() as u64,
+                TZ_VALUES[zoom as usize + 1] - TZ_VALUES[zoom as usize]
+            );
+            assert!(range.iter().all(|id| Tile::from_id(id).zoom == zoom));
+        }
+        let range = TileRange::for_zoom(2);
+        assert!(range.contains(5));
+        assert!(range.contains(20));
+        assert!(!range.contains(4));
+        assert!(!range.contains(21));
+        // the last zoom fits
+        let range = TileRange::for_zoom(30);
+        assert_eq!(range.tile_count(), 1 << 60);
+        assert_eq!(Tile::from_id(range.max_tile_id).zoom, 30);
+
+        // entries_in_range
+        let directory = Directory::new(vec![
+            Entry::new(0, 0, 1, 1),
+            Entry::new(3, 1, 1, 3),
+            Entry::new(6, 2, 1, 1),
+            Entry::new(8, 3, 1, 1),
+            Entry::new(21, 4, 1, 1),
+        ]);
+        // the run of 3 starting at tile 3 covers tile 5
+        assert_eq!(
+            directory.entries_in_range(&TileRange::for_zoom(2)),
+            &directory.entries[1..4]
+        );
+        assert_eq!(
+            directory.entries_in_range(&TileRange::for_zoom(0)),
+            &directory.entries[0..1]
+        );
+        assert!(directory
+            .entries_in_range(&TileRange::for_zoom(1))
+            .contains(&Entry::new(3, 1, 1, 3)));
+        assert_eq!(
+            directory.entries_in_range(&TileRange::for_zoom(3)),
+            &directory.entries[4..]
+        );
+        assert!(directory
+            .entries_in_range(&TileRange::for_zoom(4))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_tile_to_s2_faces() {
+        // near the prime meridian at mid-latitude
+        let tile = Tile::new(4, 8, 6);
+        assert_eq!(tile.to_s2_faces(), vec![Face::Face0]);
+        assert_eq!(tile.primary_s2_face(), Face::Face0);
+        // near 90° east on the equator
+        let tile = Tile::new(4, 12, 7);
+        assert_eq!(tile.to_s2_faces(), vec![Face::Face1]);
+        assert_eq!(tile.primary_s2_face(), Face::Face1);
+        // near the north pole
+        assert_eq!(Tile::new(4, 3, 0).to_s2_faces(), vec![Face::Face2]);
+        // near 180° on the equator and near 90° west in the south
+        assert_eq!(Tile::new(4, 0, 8).primary_s2_face(), Face::Face3);
+        assert_eq!(Tile::new(4, 4, 9).primary_s2_face(), Face::Face4);
+        // near the south pole
+        assert_eq!(Tile::new(4, 10, 15).to_s2_faces(), vec![Face::Face5]);
+        // the whole world
+        assert_eq!(
+            Tile::new(0, 0, 0).to_s2_faces(),
+            vec![
+                Face::Face0,
+                Face::Face1,
+                Face::Face2,
+                Face::Face3,
+                Face::Face4,
+                Face::Face5
+            ]
+        );
+        // the north east quadrant, 0° to 180° east, spans faces 0, 1 and 3 plus the north pole
+        assert_eq!(
+            Tile::new(1, 1, 0).to_s2_faces(),
+            vec![Face::Face0, Face::Face1, Face::Face2, Face::Face3]
+        );
+    }
+
+    #[test]
+    fn test_tile_covers() {
+        let root = Tile::new(0, 0, 0);
+        for zoom in 0..5 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    assert!(root.covers(&Tile::new(zoom, x, y)));
+                }
+            }
+        }
+
+        let tile = Tile::new(3, 5, 2);
+        assert!(tile.covers(&tile));
+        assert!(tile.covers(&Tile::new(4, 10, 4)));
+        assert!(tile.covers(&Tile::new(4, 11, 5)));
+        assert!(tile.covers(&Tile::new(6, 47, 23)));
+        assert!(!tile.covers(&Tile::new(4, 12, 4)));
+        assert!(!tile.covers(&Tile::new(4, 10, 6)));
+        assert!(!tile.covers(&Tile::new(3, 5, 3)));
+        // a parent is never covered by its child
+        assert!(!tile.covers(&Tile::new(2, 2, 1)));
+        assert!(!tile.covers(&root));
+    }
+
+    #[test]
+    fn test_tile_parent() {
+        assert_eq!(Tile::new(0, 0, 0).parent(), None);
+        assert_eq!(Tile::new(1, 1, 0).parent(), Some(Tile::new(0, 0, 0)));
+        assert_eq!(Tile::new(6, 47, 23).parent(), Some(Tile::new(5, 23, 11)));
+    }
+
+    #[test]
+    fn test_tile_common_ancestor() {
+        let a = Tile::new(5, 0, 0);
+        let b = Tile::new(5, 1, 0);
+        assert_eq!(Tile::common_ancestor(&a, &b), Tile::new(4, 0, 0));
+        assert_eq!(Tile::common_ancestor(&b, &a), Tile::new(4, 0, 0));
+        assert_eq!(Tile::common_ancestor(&a, &a), a);
+        // neighbors on each side of a zoom 1 boundary only meet at the root
+        assert_eq!(
+            Tile::common_ancestor(&Tile::new(3, 3, 3), &Tile::new(3, 4, 3)),
+            Tile::new(0, 0, 0)
+        );
+        // different zooms, with one covering the other or not
+        let tile = Tile::new(3, 5, 2);
+        assert_eq!(Tile::common_ancestor(&tile, &Tile::new(6, 47, 23)), tile);
+        assert_eq!(
+            Tile::common_ancestor(&Tile::new(6, 47, 23), &Tile::new(2, 3, 0)),
+            Tile::new(1, 1, 0)
+        );
+        let root = Tile::new(0, 0, 0);
+        assert_eq!(Tile::common_ancestor(&root, &root), root);
+        assert_eq!(Tile::common_ancestor(&root, &tile), root);
+        assert_eq!(Tile::common_ancestor(&tile, &root), root);
+    }
+
+    // Hash
+    #[test]
+    fn test_tile_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::{HashMap, HashSet};
+
+        let mut set: HashSet<Tile> = HashSet::new();
+        assert!(set.insert(Tile::new(3, 5, 2)));
+        assert!(set.insert(Tile::new(3, 2, 5)));
+        assert!(!set.insert(Tile::new(3, 5, 2)));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&Tile::from_id(Tile::new(3, 2, 5).to_id())));
+
+        let mut map: HashMap<Tile, Vec<u8>> = HashMap::new();
+        map.insert(Tile::new(0, 0, 0), b"root".to_vec());
+        map.insert(Tile::new(1, 1, 0), b"child".to_vec());
+        map.insert(Tile::new(0, 0, 0), b"new root".to_vec());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&Tile::new(0, 0, 0)).unwrap(), b"new root");
+        assert_eq!(map.get(&Tile::new(1, 1, 0)).unwrap(), b"child");
+        assert_eq!(map.get(&Tile::new(1, 0, 1)), None);
+
+        let entries: HashSet<Entry> =
+            HashSet::from([Entry::new(1, 0, 10, 1), Entry::new(1, 0, 10, 1)]);
+        assert_eq!(entries.len(), 1);
+        let directories: HashSet<Directory> = HashSet::from([
+            Directory::new(vec![Entry::new(1, 0, 10, 1)]),
+            Directory::new(vec![Entry::new(2, 0, 10, 1)]),
+        ]);
+        assert_eq!(directories.len(), 2);
+
+        // hash_by_id
+        let tile = Tile::new(20, 1_002, 6_969);
+        let mut tile_hasher = DefaultHasher::new();
+        tile.hash_by_id(&mut tile_hasher);
+        let mut id_hasher = DefaultHasher::new();
+        tile.to_id().hash(&mut id_hasher);
+        assert_eq!(tile_hasher.finish(), id_hasher.finish());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tile_to_id_out_of_bounds() {
+        Tile::new(2, 4, 0).to_id();
+    }
+
+    // Display, From<Tile> for String & FromStr
+    #[test]
+    fn test_tile_string() {
+        assert_eq!(Tile::new(5, 12, 30).to_string(), "5/12/30");
+        assert_eq!(String::from(Tile::new(0, 0, 0)), "0/0/0");
+        assert_eq!("5/12/30".parse::<Tile>(), Ok(Tile::new(5, 12, 30)));
+
+        // round trip
+        for zoom in 0..31 {
+            let max = (1_u64 << zoom) - 1;
+            for (x, y) in [(0, 0), (max, max), (max / 2, max / 3), (max / 7, max)] {
+                let tile = Tile::new(zoom, x, y);
+                assert_eq!(tile.to_string().parse::<Tile>(), Ok(tile));
+                assert_eq!(Tile::from_str(&String::from(tile)), Ok(tile));
+            }
+        }
+
+        // errors
+        assert_eq!("".parse::<Tile>(), Err(TileParseError::WrongPartCount));
+        assert_eq!("1/2".parse::<Tile>(), Err(TileParseError::WrongPartCount));
+        assert_eq!(
+            "1/2/3/4".parse::<Tile>(),
+            Err(TileParseError::WrongPartCount)
+        );
+        assert_eq!(
+            "abc/def/ghi".parse::<Tile>(),
+            Err(TileParseError::InvalidZoom)
+        );
+        assert_eq!("-1/0/0".parse::<Tile>(), Err(TileParseError::InvalidZoom));
+        assert_eq!(
+            "99/0/0".parse::<Tile>(),
+            Err(TileParseError::ZoomOutOfRange)
+        );
+        assert_eq!(
+            "31/0/0".parse::<Tile>(),
+            Err(TileParseError::ZoomOutOfRange)
+        );
+        assert_eq!("1/x/0".parse::<Tile>(), Err(TileParseError::InvalidX));
+        assert_eq!("1/0/".parse::<Tile>(), Err(TileParseError::InvalidY));
+        // coordinates outside of the zoom
+        assert_eq!("3/100/0".parse::<Tile>(), Err(TileParseError::InvalidX));
+        assert_eq!("3/8/0".parse::<Tile>(), Err(TileParseError::InvalidX));
+        assert_eq!("3/7/8".parse::<Tile>(), Err(TileParseError::InvalidY));
+        assert_eq!("0/0/1".parse::<Tile>(), Err(TileParseError::InvalidY));
+        assert_eq!(
+            "30/1073741824/0".parse::<Tile>(),
+            Err(TileParseError::InvalidX)
+        );
+        assert_eq!("3/7/7".parse::<Tile>(), Ok(Tile::new(3, 7, 7)));
+    }
+
+    #[test]
+    fn test_tile_urls() {
+        let osm = "https://tile.openstreetmap.org";
+        let tile = Tile::new(5, 12, 30);
+        assert_eq!(
+            tile.to_slippy_url(osm, "png"),
+            "https://tile.openstreetmap.org/5/12/30.png"
+        );
+        assert_eq!(tile.to_slippy_url("/tiles/", ".pbf"), "/tiles/5/12/30.pbf");
+        assert_eq!(tile.to_slippy_url("", ""), "/5/12/30");
+        assert_eq!(tile.to_tms_url("/tms", "pbf"), "/tms/5/12/1.pbf");
+        assert_eq!(
+            Tile::new(0, 0, 0).to_tms_url("/tms", "pbf"),
+            "/tms/0/0/0.pbf"
+        );
+        assert_eq!(
+            Tile::new(2, 3, 3).to_tms_url("/tms", "pbf"),
+            "/tms/2/3/0.pbf"
+        );
+
+        // known URLs
+        assert_eq!(
+            Tile::from_url_path("https://tile.openstreetmap.org/0/0/0.png"),
+            Ok(Tile::new(0, 0, 0))
+        );
+        assert_eq!(
+            Tile::from_url_path("https://a.tile.openstreetmap.org/18/132803/90185.png"),
+            Ok(Tile::new(18, 132803, 90185))
+        );
+        assert_eq!(Tile::from_url_path("/tiles/5/12/30.pbf"), Ok(tile));
+        assert_eq!(Tile::from_url_path("5/12/30"), Ok(tile));
+        assert_eq!(Tile::from_url_path("/tiles/5/12/30/"), Ok(tile));
+        assert_eq!(
+            Tile::from_url_path("/tiles/5/12/30.mvt?key=abc#x"),
+            Ok(tile)
+        );
+        assert_eq!(Tile::from_url_path("tiles%2F5%2F12%2F30.pbf"), Ok(tile));
+        assert_eq!(Tile::from_url_path("/tiles/5%2f12%2f30.pbf"), Ok(tile));
+
+        // round trips
+        for zoom in [0, 1, 7, 14, 30] {
+            let max = (1_u64 << zoom) - 1;
+            for (x, y) in [(0, 0), (max, max), (max / 2, max / 3)] {
+                let tile = Tile::new(zoom, x, y);
+                assert_eq!(
+                    Tile::from_url_path(&tile.to_slippy_url(osm, "png")),
+                    Ok(tile)
+                );
+                let tms = Tile::from_url_path(&tile.to_tms_url(osm, "png")).unwrap();
+                assert_eq!(tms.to_tms_url(osm, "png"), tile.to_slippy_url(osm, "png"));
+            }
+        }
+
+        // errors
+        assert_eq!(Tile::from_url_path(""), Err(TileParseError::WrongPartCount));
+        assert_eq!(
+            Tile::from_url_path("12/30.png"),
+            Err(TileParseError::WrongPartCount)
+        );
+        assert_eq!(
+            Tile::from_url_path("/tiles/a/12/30.png"),
+            Err(TileParseError::InvalidZoom)
+        );
+        assert_eq!(
+            Tile::from_url_path("/tiles/5/12/.png"),
+            Err(TileParseError::InvalidY)
+        );
+        assert_eq!(
+            Tile::from_url_path("/tiles/5/12/32.png"),
+            Err(TileParseError::InvalidY)
+        );
+    }
+
+    // Entry
+    #[test]
+    fn test_entry() {
+        let entry = Entry {
+            tile_id: 1,
+            offset: 2,
+            length: 3,
+            run_length: 4,
+        };
+        assert_eq!(entry, Entry::new(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn test_directory_roundtrip_edge_cases() {
+        let roundtrip = |entries: Vec<Entry>| {
+            let directory = Directory::new(entries);
+            let data = directory.serialize();
+            assert_eq!(
+                Directory::from_buffer(&mut data.as_slice().into()),
+                directory
+            );
+        };
+        // empty
+        roundtrip(vec![]);
+        // single entry
+        roundtrip(vec![Entry::new(7, 100, 20, 1)]);
+        // run_length 0 (leaf pointers)
+        roundtrip(vec![Entry::new(0, 0, 50, 0), Entry::new(20, 50, 50, 0)]);
+        // maximum varint values
+        roundtrip(vec![Entry::new(u64::MAX, u64::MAX - 1, u32::MAX, u32::MAX)]);
+        roundtrip(vec![
+            Entry::new(0, 0, u32::MAX, 1),
+            Entry::new(u64::MAX, u32::MAX as u64, u32::MAX, u32::MAX),
+        ]);
+        // consecutive offsets are stored as delta 0
+        let directory = Directory::new(vec![Entry::new(0, 0, 10, 1), Entry::new(1, 10, 5, 1)]);
+        assert_eq!(*directory.serialize().last().unwrap(), 0);
+        roundtrip(directory.entries);
+    }
+
+    proptest! {
+        #[test]
+        fn test_directory_roundtrip(
+            raw in prop::collection::vec(
+                (0..=u32::MAX as u64, any::<bool>(), 0..u64::MAX / 2, any::<u32>(), any::<u32>()),
+                0..200,
+            )
+        ) {
+            let mut entries: Vec<Entry> = Vec::with_capacity(raw.len());
+            let mut tile_id = 0;
+            for (delta, consecutive, offset, length, run_length) in raw {
+                // ids are sorted and unique after the first entry
+                tile_id += if entries.is_empty() { delta } else { delta.max(1) };
+                let offset = match entries.last() {
+                    Some(last) if consecutive => last.offset + last.length as u64,
+                    _ => offset,
+                };
+                entries.push(Entry::new(tile_id, offset, length, run_length));
+            }
+            let directory = Directory::new(entries);
+            let data = directory.serialize();
+            prop_assert_eq!(directory.approximate_byte_size(), data.len());
+            prop_assert_eq!(directory.encoded_size(), data.len());
+            let parsed = Directory::from_buffer(&mut data.as_slice().into());
+            prop_assert_eq!(parsed, directory);
+        }
+    }
+
+    #[test]
+    fn test_directory_json() {
+        let directory = Directory::new(vec![
+            Entry::new(0, 0, 10, 1),
+            Entry::new(Tile::new(3, 2, 5).to_id(), 10, 20, 3),
+            Entry::new(100, 30, 5, 0),
+        ]);
+        let json = directory.to_json();
+        assert!(json.starts_with(
+            r#"[{"tile_id":0,"tile_xyz":{"zoom":0,"x":0,"y":0},"offset":0,"length":10,"run_length":1},"#
+        ));
+        assert!(json.contains(r#""tile_xyz":{"zoom":3,"x":2,"y":5}"#));
+        assert_eq!(Directory::from_json(&json).unwrap(), directory);
+
+        // tile_xyz is optional
+        let json = r#"[{"tile_id":5,"offset":1,"length":2,"run_length":3}]"#;
+        assert_eq!(
+            Directory::from_json(json).unwrap(),
+            Directory::new(vec![Entry::new(5, 1, 2, 3)])
+        );
+        assert!(Directory::from_json("[{}]").is_err());
+        assert_eq!(Directory::default().to_json(), "[]");
+    }
+
+    #[test]
+    fn test_approximate_byte_size() {
+        let mut directory = Directory::new(vec![
+            Entry::new(1, 0, 3, 1),
+            Entry::new(5, 3, 7, 1),
+            Entry::new(200, 1_000, 11, 2),
+        ]);
+        assert_eq!(
+            directory.approximate_byte_size(),
+            directory.serialize().len()
+        );
+        assert_eq!(Directory::default().approximate_byte_size(), 1);
+        assert_eq!(varint_size(0), 1);
+        assert_eq!(varint_size(127), 1);
+        assert_eq!(varint_size(128), 2);
+        assert_eq!(varint_size(u64::MAX), 10);
+
+        directory.entries = (0..5_000).map(|i| Entry::new(i, i * 10, 10, 1)).collect();
+        assert_eq!(
+            directory.approximate_byte_size(),
+            directory.serialize().len()
+        );
+    }
+
+    #[test]
+    fn test_encoded_size() {
+        let first = Entry::new(1, 0, 3, 1);
+        // tile id delta, run length, length, offset + 1
+        assert_eq!(first.encoded_size(None), 4);
+        // contiguous offsets take a single byte
+        assert_eq!(Entry::new(5, 3, 7, 1).encoded_size(Some(&first)), 4);
+        assert_eq!(Entry::new(5, 1_000, 7, 1).encoded_size(Some(&first)), 5);
+        assert_eq!(
+            Entry::new(1 << 40, 1 << 50, u32::MAX, u32::MAX).encoded_size(Some(&first)),
+            6 + 5 + 5 + 8
+        );
+        assert_eq!(Directory::default().encoded_size(), 1);
+
+        let directories = [
+            vec![Entry::new(0, 0, 1, 1)],
+            vec![Entry::new(u64::MAX / 2, u64::MAX / 2, u32::MAX, u32::MAX)],
+            (0..200).map(|i| Entry::new(i, i * 10, 10, 1)).collect(),
+            (0..200)
+                .map(|i| Entry::new(i * 300, i * 20, 10, 3))
+                .collect(),
+            (0..1_000)
+                .map(|i| Entry::new(i * i, (1_000 - i) * 1_000, i as u32, i as u32 % 7 + 1))
+                .collect(),
+            // leaf directory entries
+            (0..50)
+                .map(|i| Entry::new(i << 20, i * 70_000, 70_000, 0))
+                .collect(),
+        ];
+        for entries in directories {
+            let directory = Directory::new(entries);
+            assert_eq!(directory.encoded_size(), directory.serialize().len());
+            let entries_size: usize = (0..directory.len())
+                .map(|i| {
+                    let prev = i.checked_sub(1).map(|p| &directory.entries[p]);
+                    directory.entries[i].encoded_size(prev)
+                })
+                .sum();
+            assert_eq!(
+                entries_size + varint_size(directory.len() as u64),
+                directory.encoded_size()
+            );
+        }
+    }
+
+    #[test]
+    fn test_directory_entries() {
+        let entries = vec![Entry::new(1, 0, 10, 2), Entry::new(5, 10, 3, 1)];
+        let directory = Directory::from_entries(entries.clone());
+        assert_eq!(directory, Directory::new(entries.clone()));
+        let ptr = directory.entries.as_ptr();
+        let moved = directory.into_entries();
+        // moved, not cloned
+        assert_eq!(moved.as_ptr(), ptr);
+        assert_eq!(moved, entries);
+        assert_eq!(Directory::from_entries(Vec::new()).into_entries(), vec![]);
+
+        let mut directory = Directory::with_capacity(100);
+        assert!(directory.is_empty());
+        let ptr = directory.entries.as_ptr();
+        for i in 0..100 {
+            directory.insert(Entry::new(i, i * 4, 4, 1));
+        }
+        assert_eq!(directory.len(), 100);
+        assert_eq!(directory.entries.as_ptr(), ptr);
+        assert_eq!(directory.entries.capacity(), 100);
+    }
+
+    #[test]
+    fn test_directory_remove() {
+        // the only entry
+        let mut dir = Directory::new(vec![Entry::new(4, 0, 10, 1)]);
+        assert!(!dir.remove_tile_id(5));
+        assert!(dir.remove_tile_id(4));
+        assert!(dir.is_empty());
+        assert!(!dir.remove_tile_id(4));
+
+        // the middle of a run
+        let mut dir = Directory::new(vec![Entry::new(0, 0, 10, 1), Entry::new(1, 10, 5, 5)]);
+        assert!(dir.remove_tile_id(3));
+        assert_eq!(
+            dir.entries,
+            vec![
+                Entry::new(0, 0, 10, 1),
+                Entry::new(1, 10, 5, 2),
+                Entry::new(4, 10, 5, 2)
+            ]
+        );
+        // the edges of a run
+        assert!(dir.remove_tile_id(1));
+        assert!(dir.remove_tile_id(5));
+        assert_eq!(
+            dir.entries,
+            vec![
+                Entry::new(0, 0, 10, 1),
+                Entry::new(2, 10, 5, 1),
+                Entry::new(4, 10, 5, 1)
+            ]
+        );
+        // leaf directories and gaps are not tiles
+        let mut dir = Directory::new(vec![Entry::new(0, 0, 10, 0), Entry::new(10, 10, 5, 2)]);
+        assert!(!dir.remove_tile_id(3));
+        assert!(!dir.remove_tile_id(12));
+        assert_eq!(dir.len(), 2);
+
+        // ranges
+        let mut dir = Directory::new(vec![
+            Entry::new(0, 0, 10, 4),
+            Entry::new(4, 10, 10, 1),
+            Entry::new(5, 20, 10, 1),
+            Entry::new(8, 30, 10, 10),
+        ]);
+        assert_eq!(dir.remove_range(20, 30), 0);
+        // an entire run
+        assert_eq!(dir.remove_range(0, 3), 1);
+        assert_eq!(dir.remove_range(5, 9), 2);
+        assert_eq!(
+            dir.entries,
+            vec![Entry::new(4, 10, 10, 1), Entry::new(10, 30, 10, 8)]
+        );
+        assert_eq!(dir.remove_range(0, u64::MAX), 2);
+        assert!(dir.is_empty());
+    }
+
+    #[test]
+    fn test_directory_validate() {
+        let dir = Directory::new(vec![
+            Entry::new(0, 0, 10, 3),
+            Entry::new(3, 10, 10, 1),
+            Entry::new(10, 20, 10, 0),
+        ]);
+        assert_eq!(dir.validate(), Ok(()));
+
+        let dir = Directory::new(vec![
+            Entry::new(0, 0, 10, 3),
+            Entry::new(2, 10, 10, 1),
+            Entry::new(1, 20, 0, 1),
+        ]);
+        assert_eq!(
+            dir.validate(),
+            Err(vec![
+                DirectoryError::OverlappingRun { tile_id: 2 },
+                DirectoryError::EmptyEntry { tile_id: 1 },
+                DirectoryError::Unsorted { tile_id: 1 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_entry_split_run() {
+        let entry = Entry::new(10, 200, 30, 5);
+        assert_eq!(
+            entry.split_run(12),
+            Some((Entry::new(10, 200, 30, 2), Entry::new(12, 200, 30, 3)))
+        );
+        assert_eq!(
+            entry.split_run(14),
+            Some((Entry::new(10, 200, 30, 4), Entry::new(14, 200, 30, 1)))
+        );
+        // outside the run or an empty part
+        assert_eq!(entry.split_run(9), None);
+        assert_eq!(entry.split_run(10), None);
+        assert_eq!(entry.split_run(15), None);
+        assert_eq!(Entry::new(10, 200, 30, 1).split_run(10), None);
+        assert_eq!(Entry::new(10, 200, 30, 0).split_run(11), None);
+
+        // split then merge is identity
+        for at in 11..15 {
+            let (head, tail) = entry.split_run(at).unwrap();
+            assert_eq!(head.tile_id + head.run_length as u64, tail.tile_id);
+            assert_eq!(head.try_merge(&tail), Some(entry));
+            assert_eq!(tail.try_merge(&head), None);
+        }
+    }
+
+    #[test]
+    fn test_entry_try_merge() {
+        let entry = Entry::new(10, 200, 30, 2);
+        assert_eq!(
+            entry.try_merge(&Entry::new(12, 200, 30, 3)),
+            Some(Entry::new(10, 200, 30, 5))
+        );
+        // a gap, an overlap, other data and leaf directories
+        assert_eq!(entry.try_merge(&Entry::new(13, 200, 30, 1)), None);
+        assert_eq!(entry.try_merge(&Entry::new(11, 200, 30, 1)), None);
+        assert_eq!(entry.try_merge(&Entry::new(12, 230, 30, 1)), None);
+        assert_eq!(entry.try_merge(&Entry::new(12, 200, 31, 1)), None);
+        assert_eq!(entry.try_merge(&Entry::new(12, 200, 30, 0)), None);
+        assert_eq!(Entry::new(10, 200, 30, 0).try_merge(&entry), None);
+        assert_eq!(
+            Entry::new(0, 0, 1, u32::MAX).try_merge(&Entry::new(u32::MAX as u64, 0, 1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_directory_merge_runs() {
+        let mut directory = Directory::new((0..100).map(|id| Entry::new(id, 0, 10, 1)).collect());
+        directory.merge_runs();
+        assert_eq!(directory.entries, vec![Entry::new(0, 0, 10, 100)]);
+
+        let mut directory = Directory::new(vec![
+            Entry::new(0, 0, 10, 1),
+            Entry::new(1, 0, 10, 2),
+            Entry::new(3, 10, 5, 1),
+            Entry::new(4, 10, 5, 1),
+            Entry::new(6, 10, 5, 1),
+            Entry::new(7, 0, 20, 0),
+            Entry::new(9, 0, 20, 0),
+        ]);
+        directory.merge_runs();
+        assert_eq!(
+            directory.entries,
+            vec![
+                Entry::new(0, 0, 10, 3),
+                Entry::new(3, 10, 5, 2),
+                Entry::new(6, 10, 5, 1),
+                Entry::new(7, 0, 20, 0),
+                Entry::new(9, 0, 20, 0),
+            ]
+        );
+        let mut empty = Directory::default();
+        empty.merge_runs();
+        assert!(empty.is_empty());
+    }
 
     // Directory
     #[test]
@@ -676,6 +2469,34 @@ mod tests {
         let d2 = Directory::from_buffer(&mut buf);
         assert_eq!(d2, directory);
 
+        // from_tile_ids
+        let directory =
+            Directory::from_tile_ids(&[(9, 30, 3), (1, 0, 10), (5, 10, 20), (1, 40, 4)]);
+        assert_eq!(
+            directory.entries,
+            vec![
+                Entry::new(1, 40, 4, 1),
+                Entry::new(5, 10, 20, 1),
+                Entry::new(9, 30, 3, 1),
+            ]
+        );
+        // from_tile_data
+        let directory = Directory::from_tile_data(&[
+            (Tile::new(1, 1, 1), 0, 10),
+            (Tile::new(0, 0, 0), 10, 5),
+            (Tile::new(1, 0, 0), 15, 7),
+            (Tile::new(0, 0, 0), 22, 6),
+        ]);
+        assert_eq!(
+            directory.entries,
+            vec![
+                Entry::new(0, 22, 6, 1),
+                Entry::new(Tile::new(1, 0, 0).to_id(), 15, 7, 1),
+                Entry::new(Tile::new(1, 1, 1).to_id(), 0, 10, 1),
+            ]
+        );
+        assert_eq!(Directory::from_tile_data(&[]), Directory::default());
+
         // is_empty
         let directory = Directory::new(vec![]);
         assert!(directory.is_empty());
@@ -732,6 +2553,9 @@ mod tests {
         assert_eq!(Compression::Gzip, 2_u8.into());
         assert_eq!(Compression::Brotli, 3_u8.into());
         assert_eq!(Compression::Zstd, 4_u8.into());
+        assert_eq!(Compression::Zlib, 5_u8.into());
+        assert_eq!(Compression::Lz4, 6_u8.into());
+        assert_eq!(Compression::Unknown, 7_u8.into());
 
         // into_u8
         assert_eq!(0_u8, u8::from(Compression::Unknown));
@@ -739,6 +2563,8 @@ mod tests {
         assert_eq!(2_u8, u8::from(Compression::Gzip));
         assert_eq!(3_u8, u8::from(Compression::Brotli));
         assert_eq!(4_u8, u8::from(Compression::Zstd));
+        assert_eq!(5_u8, u8::from(Compression::Zlib));
+        assert_eq!(6_u8, u8::from(Compression::Lz4));
 
         // to_string
         assert_eq!("unknown".to_string(), String::from(Compression::Unknown));
@@ -746,9 +2572,42 @@ mod tests {
         assert_eq!("gzip".to_string(), String::from(Compression::Gzip));
         assert_eq!("br".to_string(), String::from(Compression::Brotli));
         assert_eq!("zstd".to_string(), String::from(Compression::Zstd));
+        assert_eq!("zlib".to_string(), String::from(Compression::Zlib));
+        assert_eq!("lz4".to_string(), String::from(Compression::Lz4));
     }
 
     // TileType
+    #[test]
+    fn test_enum_ordering() {
+        let compressions: Vec<Compression> = (0..7u8).map(Compression::from).collect();
+        let mut sorted = compressions.clone();
+        sorted.sort();
+        assert_eq!(sorted, compressions);
+        let tile_types: Vec<TileType> = (0..8u8).map(TileType::from).collect();
+        let mut sorted = tile_types.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, tile_types);
+        assert!(TileType::Unknown < TileType::Pbf && TileType::Gif < TileType::Tiff);
+
+        // usable as collection keys
+        let mut cache = crate::DirCache::new(2);
+        cache.set(Compression::Gzip, "gzip");
+        cache.set(Compression::None, "none");
+        assert_eq!(cache.get(&Compression::Gzip), Some(&"gzip"));
+        let mut counts: BTreeMap<TileType, usize> = BTreeMap::new();
+        for tile_type in [TileType::Png, TileType::Pbf, TileType::Png] {
+            *counts.entry(tile_type).or_default() += 1;
+        }
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![(TileType::Pbf, 1), (TileType::Png, 2)]
+        );
+        let set: std::collections::HashSet<Compression> =
+            [Compression::Zstd, Compression::Zstd, Compression::Brotli].into();
+        assert_eq!(set.len(), 2);
+    }
+
     #[test]
     fn test_tile_type() {
         // default
@@ -760,6 +2619,9 @@ mod tests {
         assert_eq!(TileType::Jpeg, 3_u8.into());
         assert_eq!(TileType::Webp, 4_u8.into());
         assert_eq!(TileType::Avif, 5_u8.into());
+        assert_eq!(TileType::Gif, 6_u8.into());
+        assert_eq!(TileType::Tiff, 7_u8.into());
+        assert_eq!(TileType::Unknown, 8_u8.into());
         // into_u8
         assert_eq!(0_u8, u8::from(TileType::Unknown));
         assert_eq!(1_u8, u8::from(TileType::Pbf));
@@ -767,6 +2629,8 @@ mod tests {
         assert_eq!(3_u8, u8::from(TileType::Jpeg));
         assert_eq!(4_u8, u8::from(TileType::Webp));
         assert_eq!(5_u8, u8::from(TileType::Avif));
+        assert_eq!(6_u8, u8::from(TileType::Gif));
+        assert_eq!(7_u8, u8::from(TileType::Tiff));
         // to_string
         assert_eq!("unknown".to_string(), String::from(TileType::Unknown));
         assert_eq!("pbf".to_string(), String::from(TileType::Pbf));
@@ -774,6 +2638,141 @@ mod tests {
         assert_eq!("jpeg".to_string(), String::from(TileType::Jpeg));
         assert_eq!("webp".to_string(), String::from(TileType::Webp));
         assert_eq!("avif".to_string(), String::from(TileType::Avif));
+        assert_eq!("gif".to_string(), String::from(TileType::Gif));
+        assert_eq!("tiff".to_string(), String::from(TileType::Tiff));
+
+        assert_eq!(TileType::Pbf.mime_type(), "application/x-protobuf");
+        assert_eq!(TileType::Gif.mime_type(), "image/gif");
+        assert_eq!(TileType::Tiff.mime_type(), "image/tiff");
+        assert_eq!(TileType::Unknown.extension(), "");
+        assert_eq!(TileType::Jpeg.extension(), ".jpg");
+        assert_eq!(TileType::Gif.extension(), ".gif");
+        assert_eq!(TileType::Tiff.extension(), ".tif");
+    }
+
+    #[test]
+    fn test_tile_type_iana_media_type() {
+        assert_eq!(
+            TileType::Pbf.to_iana_media_type(),
+            "application/vnd.mapbox-vector-tile"
+        );
+        assert_eq!(TileType::Webp.to_iana_media_type(), "image/webp");
+        assert_eq!(
+            TileType::Unknown.to_iana_media_type(),
+            "application/octet-stream"
+        );
+        for t in (0..8u8).map(TileType::from) {
+            assert_eq!(
+                TileType::from_iana_media_type(t.to_iana_media_type()),
+                Some(t)
+            );
+            assert_eq!(TileType::from_iana_media_type(t.mime_type()), Some(t));
+        }
+
+        assert_eq!(
+            TileType::from_iana_media_type("application/vnd.mapbox-vector-tile; charset=utf-8"),
+            Some(TileType::Pbf)
+        );
+        assert_eq!(
+            TileType::from_iana_media_type("Application/VND.Mapbox-Vector-Tile"),
+            Some(TileType::Pbf)
+        );
+        assert_eq!(
+            TileType::from_iana_media_type("application/x-protobuf;type=mvt"),
+            Some(TileType::Pbf)
+        );
+        assert_eq!(
+            TileType::from_iana_media_type(" IMAGE/PNG "),
+            Some(TileType::Png)
+        );
+        assert_eq!(TileType::from_iana_media_type("image/png8"), None);
+        assert_eq!(
+            TileType::from_iana_media_type("text/html; charset=utf-8"),
+            None
+        );
+        assert_eq!(TileType::from_iana_media_type(""), None);
+    }
+
+    #[test]
+    fn test_compression_from_accept_encoding() {
+        use Compression::*;
+        // Chrome, Firefox and curl --compressed
+        assert_eq!(
+            Compression::from_accept_encoding("gzip, deflate, br, zstd"),
+            vec![Gzip, Zlib, Brotli, Zstd]
+        );
+        assert_eq!(
+            Compression::from_accept_encoding("gzip, deflate, br"),
+            vec![Gzip, Zlib, Brotli]
+        );
+        assert_eq!(
+            Compression::from_accept_encoding("deflate, gzip, br, zstd"),
+            vec![Zlib, Gzip, Brotli, Zstd]
+        );
+        // q-values, from the RFC 9110 examples
+        assert_eq!(
+            Compression::from_accept_encoding("br;q=1.0, gzip;q=0.8, *;q=0.1"),
+            vec![Brotli, Gzip]
+        );
+        assert_eq!(
+            Compression::from_accept_encoding("gzip;q=1.0, identity; q=0.5, *;q=0"),
+            vec![Gzip, None]
+        );
+        assert_eq!(
+            Compression::from_accept_encoding("zstd;q=0.5, GZIP;Q=0.9, br"),
+            vec![Brotli, Gzip, Zstd]
+        );
+        // refused, unknown, repeated or malformed codings
+        assert_eq!(
+            Compression::from_accept_encoding("gzip;q=0, compress, x-gzip, br;q=abc"),
+            vec![]
+        );
+        assert_eq!(
+            Compression::from_accept_encoding("br, x-gzip, gzip;q=0.5"),
+            vec![Brotli, Gzip]
+        );
+        assert_eq!(Compression::from_accept_encoding(""), vec![]);
+        assert_eq!(Compression::from_accept_encoding("identity"), vec![None]);
+    }
+
+    #[test]
+    fn test_tile_type_from_file_bytes() {
+        let png = include_bytes!("../test/fixtures/tile.png");
+        let jpeg = include_bytes!("../test/fixtures/tile.jpg");
+        let webp = include_bytes!("../test/fixtures/tile.webp");
+        assert_eq!(TileType::from_file_bytes(png), TileType::Png);
+        assert_eq!(TileType::from_file_bytes(jpeg), TileType::Jpeg);
+        assert_eq!(TileType::from_file_bytes(webp), TileType::Webp);
+        assert_eq!(
+            TileType::from_file_bytes(b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf"),
+            TileType::Avif
+        );
+        assert_eq!(
+            TileType::from_file_bytes(b"GIF89a\x01\0\x01\0"),
+            TileType::Gif
+        );
+        assert_eq!(
+            TileType::from_file_bytes(b"GIF87a\x01\0\x01\0"),
+            TileType::Gif
+        );
+        assert_eq!(
+            TileType::from_file_bytes(b"II*\0\x08\0\0\0"),
+            TileType::Tiff
+        );
+        // field 3 (layers) as a length delimited message
+        assert_eq!(
+            TileType::from_file_bytes(&[0x1a, 0x02, 0x08, 0x01]),
+            TileType::Pbf
+        );
+
+        // gzip (wire type 7), field 0, a truncated JPEG and nothing at all
+        assert_eq!(
+            TileType::from_file_bytes(&[0x1f, 0x8b, 0x08]),
+            TileType::Unknown
+        );
+        assert_eq!(TileType::from_file_bytes(&[0x02, 0x00]), TileType::Unknown);
+        assert_eq!(TileType::from_file_bytes(&jpeg[..2]), TileType::Unknown);
+        assert_eq!(TileType::from_file_bytes(&[]), TileType::Unknown);
     }
 
     // Header, from_bytes, to_bytes
@@ -795,6 +2794,7 @@ mod tests {
                 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
             ]
         );
+        assert_eq!(bytes.len(), HEADER_SIZE_BYTES);
         let from_bytes = Header::from_bytes(&mut Buffer::from(bytes.as_slice()));
         assert_eq!(default_header, from_bytes);
 
@@ -827,8 +2827,122 @@ mod tests {
             center_latitude: 16.0,
         };
         let mut bytes = header.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE_BYTES);
         let from_bytes = Header::from_bytes(&mut bytes);
         assert_eq!(header, from_bytes);
+        assert_eq!(Header::default().to_bytes().len(), HEADER_SIZE_BYTES);
+    }
+
+    // Display, summary & format_bytes
+    #[test]
+    fn test_header_magic_bytes_valid() {
+        assert!(Header::magic_bytes_valid(b"PMTiles"));
+        assert!(Header::magic_bytes_valid(&[0x50, 0x4D]));
+        assert!(!Header::magic_bytes_valid(b"S2PMTiles"));
+        assert!(!Header::magic_bytes_valid(b"pm"));
+        assert!(!Header::magic_bytes_valid(b"P"));
+        assert!(!Header::magic_bytes_valid(&[]));
+    }
+
+    #[test]
+    fn test_header_from_str() {
+        let header = Header {
+            version: 3,
+            root_directory_offset: 127,
+            root_directory_length: 25,
+            metadata_offset: 152,
+            metadata_length: 247,
+            leaf_directory_offset: 1_000,
+            leaf_directory_length: 64,
+            data_offset: 399,
+            data_length: 69,
+            n_addressed_tiles: 1,
+            n_tile_entries: 1,
+            n_tile_contents: 1,
+            clustered: true,
+            internal_compression: Compression::Gzip,
+            tile_compression: Compression::Brotli,
+            tile_type: TileType::Avif,
+            min_zoom: 2,
+            max_zoom: 14,
+            min_longitude: -179.99998,
+            min_latitude: -85.05113,
+            max_longitude: 180.0,
+            max_latitude: 85.05113,
+            center_zoom: 7,
+            center_longitude: 11.123_457,
+            center_latitude: -0.000_000_1,
+        };
+        let text = format!("{header:#}");
+        assert_eq!(text.lines().count(), 25);
+        assert!(text.starts_with("version=3\nroot_directory_offset=127\n"));
+        assert!(text.contains("\ninternal_compression=gzip\ntile_compression=br\ntile_type=avif\n"));
+        assert_eq!(text.parse::<Header>(), Ok(header));
+        assert_eq!(Header::from_str(""), Ok(Header::default()));
+
+        assert_eq!(
+            "version=3\nmin_zoom".parse::<Header>(),
+            Err(HeaderParseError::MalformedLine { line: 2 })
+        );
+        assert_eq!(
+            "\ntile_type=svg".parse::<Header>(),
+            Err(HeaderParseError::InvalidValue { line: 2 })
+        );
+        assert_eq!(
+            "max_zoom=300".parse::<Header>(),
+            Err(HeaderParseError::InvalidValue { line: 1 })
+        );
+        assert_eq!(
+            "is_s2=true".parse::<Header>(),
+            Err(HeaderParseError::UnknownKey { line: 1 })
+        );
+    }
+
+    #[test]
+    fn test_header_display() {
+        let header = Header {
+            version: 3,
+            root_directory_length: 2,
+            metadata_length: 2_048,
+            leaf_directory_length: 3_145_728,
+            data_length: 5_368_709_120,
+            n_addressed_tiles: 9,
+            n_tile_entries: 10,
+            n_tile_contents: 11,
+            clustered: true,
+            internal_compression: Compression::None,
+            tile_compression: Compression::Gzip,
+            tile_type: TileType::Pbf,
+            min_zoom: 0,
+            max_zoom: 5,
+            min_longitude: -180.0,
+            min_latitude: -85.5,
+            max_longitude: 180.0,
+            max_latitude: 85.5,
+            center_zoom: 2,
+            center_longitude: 15.25,
+            center_latitude: -16.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            header.to_string(),
+            "PMTiles v3\n\
+             tile type: pbf, tile compression: gzip, internal compression: none\n\
+             zooms: z0..z5\n\
+             bounds: [-180.0000000, -85.5000000, 180.0000000, 85.5000000]\n\
+             center: [15.2500000, -16.5000000] z2\n\
+             root directory: 2 B, metadata: 2.0 KB, leaf directories: 3.0 MB, tile data: 5.0 \
+             GB\n\
+             tiles: 9 addressed, 10 entries, 11 contents, clustered: true"
+        );
+        assert_eq!(header.summary(), "PMTiles v3 pbf z0..z5 9 tiles 5.0 GB");
+        assert_eq!(format!("{header:#}").parse(), Ok(header));
+
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1_023), "1023 B");
+        assert_eq!(format_bytes(1_536), "1.5 KB");
+        assert_eq!(format_bytes(1_048_576), "1.0 MB");
+        assert_eq!(format_bytes(1_099_511_627_776), "1024.0 GB");
     }
 
     // find_tile
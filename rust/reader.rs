@@ -2,25 +2,34 @@
 extern crate alloc;
 
 #[cfg(feature = "std")]
-use flate2::read::GzDecoder;
+use flate2::read::{GzDecoder, ZlibDecoder};
+#[cfg(feature = "lz4")]
+use lz4_flex::frame::FrameDecoder;
 #[cfg(feature = "std")]
 use std::fs::File;
 #[cfg(feature = "std")]
 use std::io::{Read, Seek};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 use crate::{
-    find_tile, Compression, DirCache, Directory, S2Entries, S2Header, Tile, S2_HEADER_SIZE_BYTES,
-    S2_ROOT_SIZE,
+    find_tile, BoundingBox, Compression, DirCache, Directory, Entry, Header, S2Entries, S2Header,
+    Tile, TileType, HEADER_SIZE_BYTES, S2_HEADER_SIZE_BYTES, S2_ROOT_SIZE,
 };
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Write;
 use s2_tilejson::{Face, Metadata};
 
 /// The data manager trait for the reader
-pub trait DataManager: core::fmt::Debug {
+pub trait DataManager: core::fmt::Debug + Send {
     /// Get a range of bytes using the offset and length (both in byte sizes)
     fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8>;
+    /// Create an independent data manager for the same archive, if supported
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        None
+    }
 }
 
 /// The file manager if using STD
@@ -48,6 +57,104 @@ impl DataManager for FileManager {
 
         buf
     }
+
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        let file = self.file.try_clone().ok()?;
+        Some(Box::new(FileManager { file }))
+    }
+}
+
+/// The HTTP range manager reads archives from a URL (S3, GCS, Azure Blob, any static file
+/// server) with `Range: bytes=start-end` requests. The first `S2_ROOT_SIZE` bytes, holding the
+/// header, root directories and metadata, are fetched once and then served from memory,
+/// including by clones. Until that block is read whole, smaller reads inside it (like those of
+/// `PMTilesReader::with_lazy_root`) only fetch the requested range.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct HttpRangeManager {
+    url: String,
+    client: reqwest::blocking::Client,
+    root: Option<Vec<u8>>,
+}
+#[cfg(feature = "http")]
+impl HttpRangeManager {
+    /// Create a new HTTP range manager. Nothing is fetched until the first read
+    pub fn new(url: String) -> Result<Self, reqwest::Error> {
+        let client = reqwest::blocking::Client::builder().build()?;
+        Ok(Self {
+            url,
+            client,
+            root: None,
+        })
+    }
+
+    /// Fetch a range of bytes, also handling servers that ignore the range and send it all
+    fn fetch(&self, offset: u64, length: u64) -> Result<Vec<u8>, reqwest::Error> {
+        let range = format!("bytes={}-{}", offset, offset + length - 1);
+        let resp = self
+            .client
+            .get(&self.url)
+            .header(reqwest::header::RANGE, range)
+            .send()?
+            .error_for_status()?;
+        let partial = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let body = resp.bytes()?;
+        if partial {
+            return Ok(body.to_vec());
+        }
+        let start = (offset as usize).min(body.len());
+        let end = ((offset + length) as usize).min(body.len());
+        Ok(body[start..end].to_vec())
+    }
+}
+#[cfg(feature = "http")]
+impl DataManager for HttpRangeManager {
+    fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+        if length == 0 {
+            return Vec::new();
+        }
+        if offset + length > S2_ROOT_SIZE as u64 {
+            return self.fetch(offset, length).unwrap();
+        }
+        if self.root.is_none() {
+            if offset != 0 || length != S2_ROOT_SIZE as u64 {
+                return self.fetch(offset, length).unwrap();
+            }
+            self.root = Some(self.fetch(0, S2_ROOT_SIZE as u64).unwrap());
+        }
+        let root = self.root.as_ref().unwrap();
+        let start = (offset as usize).min(root.len());
+        let end = ((offset + length) as usize).min(root.len());
+        root[start..end].to_vec()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// A boxed data manager, for readers that pick their data manager at runtime
+impl DataManager for Box<dyn DataManager> {
+    fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+        self.as_mut().get_range(offset, length)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        self.as_ref().try_clone()
+    }
+}
+
+/// Any std reader that can seek may be used as a data manager, like a `std::io::Cursor` holding
+/// an HTTP response body. Ranges past the end are truncated like the local manager
+#[cfg(feature = "std")]
+impl<T: Read + Seek + core::fmt::Debug + Send> DataManager for T {
+    fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(length as usize);
+        self.seek(std::io::SeekFrom::Start(offset)).unwrap();
+        self.take(length).read_to_end(&mut buf).unwrap();
+
+        buf
+    }
 }
 
 /// The local manager if not using STD
@@ -67,42 +174,209 @@ impl DataManager for LocalManager {
         let length = (length as usize).min(self.data.len() - offset);
         self.data[offset..(offset + length)].to_vec()
     }
+
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        Some(Box::new(LocalManager::new(self.data.clone())))
+    }
+}
+
+/// The embedded manager for archives stored in static read-only memory, like memory mapped
+/// QSPI flash
+#[derive(Debug)]
+pub struct EmbeddedManager {
+    data: &'static [u8],
+}
+impl EmbeddedManager {
+    /// Create a new embedded manager
+    pub fn new(data: &'static [u8]) -> Self {
+        Self { data }
+    }
+}
+impl DataManager for EmbeddedManager {
+    fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+        let offset = (offset as usize).min(self.data.len());
+        let length = (length as usize).min(self.data.len() - offset);
+        self.data[offset..(offset + length)].to_vec()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn DataManager>> {
+        Some(Box::new(EmbeddedManager::new(self.data)))
+    }
 }
 
+/// Parse a S2 header, or a WM header (magic bytes "PM") into a S2Header
+fn parse_header(data: &[u8]) -> S2Header {
+    if data.starts_with(b"S2") {
+        S2Header::from_bytes(&mut data.into())
+    } else {
+        S2Header::from_header(&Header::from_bytes(&mut data.into()))
+    }
+}
+
+/// All six S2 faces in order
+const FACES: [Face; 6] = [
+    Face::Face0,
+    Face::Face1,
+    Face::Face2,
+    Face::Face3,
+    Face::Face4,
+    Face::Face5,
+];
+
+/// Summary statistics of an archive
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ArchiveStats {
+    /// the version of the archive format
+    pub format_version: u8,
+    /// true if this is an S2PMTiles archive
+    pub is_s2: bool,
+    /// the type of the tiles
+    pub tile_type: TileType,
+    /// the compression used for the tiles
+    pub tile_compression: Compression,
+    /// the min zoom level
+    pub min_zoom: u8,
+    /// the max zoom level
+    pub max_zoom: u8,
+    /// the number of addressed tiles
+    pub addressed_tiles: u64,
+    /// the number of unique tile contents
+    pub unique_tile_contents: u64,
+    /// the number of tile entries as stored in the header
+    pub directory_entries: u64,
+    /// the number of leaf directories found walking the directories
+    pub leaf_directories: u64,
+    /// the length of the tile data
+    pub data_bytes: u64,
+    /// the size of the whole archive, up to the end of its last section
+    pub total_bytes: u64,
+    /// the number of tile entries of each face (S2 only)
+    pub entries_per_face: [u64; 6],
+}
+
+/// The errors that can occur when reading an archive
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ReadError {
+    /// the directories are nested deeper than the max depth of the reader
+    MaxDepthExceeded,
+    /// the metadata is not valid UTF-8
+    InvalidUtf8,
+    /// the data does not start with the magic bytes of a PMTiles or S2PMTiles archive
+    InvalidMagicBytes,
+    /// the data ends before the header or the metadata it describes
+    Truncated,
+    /// the metadata is not valid JSON for `Metadata`
+    InvalidMetadata,
+}
+
+/// The default max number of nested directories (root included) a reader walks through
+pub const DEFAULT_MAX_DEPTH: u8 = 4;
+
+/// The default max number of decompressed tiles kept by a reader, see
+/// `PMTilesReader::get_tile_decompressed`
+pub const DEFAULT_TILE_CACHE_SIZE: usize = 32;
+
 /// The File reader is to be used by the local filesystem.
+/// Generic over its data manager, which defaults to a boxed one (see `PMTilesReaderDyn`)
 #[derive(Debug)]
-pub struct PMTilesReader {
+pub struct PMTilesReader<M: DataManager = Box<dyn DataManager>> {
     header: Option<S2Header>,
+    magic: Option<(bool, u8)>,
     root_dir: Directory,
     root_dir_s2: S2Entries,
-    metadata: Metadata,
+    /// parsed from `metadata_raw` on the first call to `get_metadata`
+    metadata: Option<Metadata>,
+    metadata_raw: Vec<u8>,
+    /// set by `clear_metadata_cache`, the metadata is read again when next asked for
+    metadata_stale: bool,
+    /// how long the metadata is kept before being read again, see `with_metadata_cache_ttl`
+    #[cfg(feature = "std")]
+    metadata_ttl: Option<Duration>,
+    #[cfg(feature = "std")]
+    metadata_fetched_at: Option<Instant>,
     dir_cache: DirCache<u64, Directory>,
-    data_manager: Box<dyn DataManager>,
+    /// decompressed tile data keyed by its offset, shared by deduplicated tiles
+    tile_cache: DirCache<u64, Vec<u8>>,
+    max_depth: u8,
+    /// read the header, root directories and metadata separately, see `with_lazy_root`
+    lazy_root: bool,
+    data_manager: M,
+}
+
+/// A `PMTilesReader` over a boxed data manager, chosen at runtime
+pub type PMTilesReaderDyn = PMTilesReader<Box<dyn DataManager>>;
+
+impl PMTilesReaderDyn {
+    /// Same as `new`, with a boxed data manager
+    pub fn from_boxed(data_manager: Box<dyn DataManager>, max_size: Option<usize>) -> Self {
+        Self::new(data_manager, max_size)
+    }
 }
-impl PMTilesReader {
+
+impl<M: DataManager> PMTilesReader<M> {
     /// Given an input path, read in the header and root directory
-    pub fn new(data_manager: Box<dyn DataManager>, max_size: Option<usize>) -> Self {
+    pub fn new(data_manager: M, max_size: Option<usize>) -> Self {
+        Self::with_max_depth(data_manager, max_size, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Same as `new`, but with the max number of nested directories (root included) to walk
+    /// through when looking up a tile
+    pub fn with_max_depth(data_manager: M, max_size: Option<usize>, max_depth: u8) -> Self {
         let max_size = max_size.unwrap_or(20);
         Self {
             header: None,
+            magic: None,
             root_dir: Directory::default(),
             root_dir_s2: S2Entries::default(),
-            metadata: Metadata::default(),
+            metadata: None,
+            metadata_raw: Vec::new(),
+            metadata_stale: false,
+            #[cfg(feature = "std")]
+            metadata_ttl: None,
+            #[cfg(feature = "std")]
+            metadata_fetched_at: None,
             dir_cache: DirCache::new(max_size),
+            tile_cache: DirCache::new(DEFAULT_TILE_CACHE_SIZE),
+            max_depth,
+            lazy_root: false,
             data_manager,
         }
     }
 
-    /// fetch the s2 metadata as needed
+    /// fetch the header, failing early if the data does not start with the magic bytes of a
+    /// PMTiles ("PM") or S2PMTiles ("S2") archive
+    pub fn try_get_header(&mut self) -> Result<S2Header, ReadError> {
+        if self.header.is_none() {
+            let magic = self.data_manager.get_range(0, 2);
+            if !Header::magic_bytes_valid(&magic) && !S2Header::magic_bytes_valid(&magic) {
+                return Err(ReadError::InvalidMagicBytes);
+            }
+        }
+
+        Ok(self.get_header())
+    }
+
+    /// Fetch the header, then only the bytes of the root directories and of the metadata, rather
+    /// than the whole `S2_ROOT_SIZE` block at once. Three requests instead of one, but much
+    /// less data for small roots, which is what matters for remote archives
+    pub fn with_lazy_root(mut self) -> Self {
+        self.lazy_root = true;
+        self
+    }
+
+    /// fetch the s2 metadata as needed.
+    /// The data is assumed to be an archive, see `try_get_header` to check it first
     pub fn get_header(&mut self) -> S2Header {
         if self.header.is_some() {
             return self.header.unwrap();
         }
+        if self.lazy_root {
+            return self.get_header_lazy();
+        }
 
         let data = self.data_manager.get_range(0, S2_ROOT_SIZE as u64);
-        let header_data = &data[0..S2_HEADER_SIZE_BYTES];
         // header
-        let mut header = S2Header::from_bytes(&mut header_data.into());
+        let mut header = parse_header(&data[0..S2_HEADER_SIZE_BYTES]);
 
         // json metadata
         let json_offset = header.metadata_offset as usize;
@@ -111,8 +385,14 @@ impl PMTilesReader {
             &data[json_offset..(json_offset + json_length)],
             header.internal_compression,
         );
-        self.metadata = serde_json::from_str(&String::from_utf8_lossy(&json_metadata))
-            .unwrap_or_else(|e| panic!("ERROR: {}", e));
+        // only parsed once asked for, see `get_metadata`
+        self.metadata = None;
+        self.metadata_raw = json_metadata;
+        self.metadata_stale = false;
+        #[cfg(feature = "std")]
+        {
+            self.metadata_fetched_at = Some(Instant::now());
+        }
 
         // root directory data
         let root_dir_offset = header.root_directory_offset as usize;
@@ -125,6 +405,59 @@ impl PMTilesReader {
 
         if header.is_s2 {
             self.get_s2_metadata(&data, &mut header);
+        } else {
+            // WM archives can also be read as face 0
+            self.root_dir_s2.face_0 = self.root_dir.clone();
+        }
+
+        self.header = Some(header);
+
+        header
+    }
+
+    /// `get_header` for `with_lazy_root`: the header, then every root directory in a single
+    /// range spanning them, then the metadata
+    fn get_header_lazy(&mut self) -> S2Header {
+        let data = self.data_manager.get_range(0, S2_HEADER_SIZE_BYTES as u64);
+        let header = parse_header(&data);
+        let faces: &[Face] = if header.is_s2 { &FACES } else { &FACES[..1] };
+
+        // root directories
+        let start = faces
+            .iter()
+            .map(|f| header.get_root_offset(*f))
+            .min()
+            .unwrap_or_default();
+        let end = faces
+            .iter()
+            .map(|f| header.get_root_offset(*f) + header.get_root_length(*f))
+            .max()
+            .unwrap_or_default();
+        let roots = self.data_manager.get_range(start, end - start);
+        for face in faces {
+            let offset = (header.get_root_offset(*face) - start) as usize;
+            let length = header.get_root_length(*face) as usize;
+            let root_dir_data = decompress(
+                &roots[offset..(offset + length)],
+                header.internal_compression,
+            );
+            self.root_dir_s2.set_dir(
+                *face,
+                Directory::from_buffer(&mut (&root_dir_data[..]).into()),
+            );
+        }
+        self.root_dir = self.root_dir_s2.face_0.clone();
+
+        // json metadata, only parsed once asked for, see `get_metadata`
+        let json_metadata = self
+            .data_manager
+            .get_range(header.metadata_offset, header.metadata_length);
+        self.metadata = None;
+        self.metadata_raw = decompress(&json_metadata, header.internal_compression);
+        self.metadata_stale = false;
+        #[cfg(feature = "std")]
+        {
+            self.metadata_fetched_at = Some(Instant::now());
         }
 
         self.header = Some(header);
@@ -132,6 +465,166 @@ impl PMTilesReader {
         header
     }
 
+    /// Read and parse the header again every time, without storing it: the cached header,
+    /// directories and metadata used to serve tiles are left as they are. Meant for health
+    /// checks of an archive that may have changed or been corrupted since it was opened
+    pub fn get_header_uncached(&mut self) -> Result<S2Header, ReadError> {
+        let data = self.data_manager.get_range(0, S2_HEADER_SIZE_BYTES as u64);
+        let header_size = if S2Header::magic_bytes_valid(&data) {
+            S2_HEADER_SIZE_BYTES
+        } else if Header::magic_bytes_valid(&data) {
+            HEADER_SIZE_BYTES
+        } else {
+            return Err(ReadError::InvalidMagicBytes);
+        };
+        if data.len() < header_size {
+            return Err(ReadError::Truncated);
+        }
+
+        Ok(parse_header(&data))
+    }
+
+    /// Read and parse the metadata (and the header locating it) again every time, without
+    /// storing it, see `get_header_uncached`. Unlike `get_metadata`, metadata that does not
+    /// parse is an error rather than left as its default
+    pub fn get_metadata_uncached(&mut self) -> Result<Metadata, ReadError> {
+        let json = self.read_metadata_raw()?;
+
+        serde_json::from_slice(&json).map_err(|_| ReadError::InvalidMetadata)
+    }
+
+    /// Read the decompressed metadata bytes located by a freshly read header
+    fn read_metadata_raw(&mut self) -> Result<Vec<u8>, ReadError> {
+        let header = self.get_header_uncached()?;
+        let data = self
+            .data_manager
+            .get_range(header.metadata_offset, header.metadata_length);
+        if (data.len() as u64) < header.metadata_length {
+            return Err(ReadError::Truncated);
+        }
+
+        Ok(decompress(&data, header.internal_compression))
+    }
+
+    /// Forget the cached metadata so the next call to `get_metadata` (or any other metadata
+    /// getter) reads it again from the data manager. The header and directories are kept,
+    /// see `reset` to forget everything
+    pub fn clear_metadata_cache(&mut self) {
+        self.metadata = None;
+        self.metadata_stale = true;
+    }
+
+    /// Read the metadata again from the data manager when it was last fetched more than `ttl`
+    /// ago, as if `clear_metadata_cache` was called. Useful for long lived readers of archives
+    /// that get replaced
+    #[cfg(feature = "std")]
+    pub fn with_metadata_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.metadata_ttl = Some(ttl);
+        self
+    }
+
+    /// Fetch the header, then read the metadata again if it was cleared or expired. Metadata
+    /// that can no longer be read is kept as it was
+    fn refresh_metadata(&mut self) {
+        self.get_header();
+        #[cfg(feature = "std")]
+        if let (Some(ttl), Some(fetched_at)) = (self.metadata_ttl, self.metadata_fetched_at) {
+            if fetched_at.elapsed() > ttl {
+                self.metadata_stale = true;
+            }
+        }
+        if !self.metadata_stale {
+            return;
+        }
+        self.metadata_stale = false;
+        if let Ok(json_metadata) = self.read_metadata_raw() {
+            self.metadata = None;
+            self.metadata_raw = json_metadata;
+        }
+        #[cfg(feature = "std")]
+        {
+            self.metadata_fetched_at = Some(Instant::now());
+        }
+    }
+
+    /// true if the archive is an S2PMTiles archive. Only the first 8 bytes are read.
+    /// Fails if the archive is shorter than 8 bytes
+    pub fn is_s2(&mut self) -> Result<bool, ReadError> {
+        Ok(self.get_magic()?.0)
+    }
+
+    /// the version of the archive format. Only the first 8 bytes are read.
+    /// Fails if the archive is shorter than 8 bytes
+    pub fn format_version(&mut self) -> Result<u8, ReadError> {
+        Ok(self.get_magic()?.1)
+    }
+
+    /// Read the magic bytes and version without parsing the full header
+    fn get_magic(&mut self) -> Result<(bool, u8), ReadError> {
+        if let Some(header) = self.header {
+            return Ok((header.is_s2, header.version));
+        }
+        if let Some(magic) = self.magic {
+            return Ok(magic);
+        }
+        let data = self.data_manager.get_range(0, 8);
+        if data.len() < 8 {
+            return Err(ReadError::Truncated);
+        }
+        let magic = (data[0] == b'S' && data[1] == b'2', data[7]);
+        self.magic = Some(magic);
+
+        Ok(magic)
+    }
+
+    /// get the data manager
+    pub fn inner_manager(&self) -> &M {
+        &self.data_manager
+    }
+
+    /// get the data manager mutable
+    pub fn inner_manager_mut(&mut self) -> &mut M {
+        &mut self.data_manager
+    }
+
+    /// Consume the reader and return its data manager
+    pub fn into_inner(self) -> M {
+        self.data_manager
+    }
+
+    /// Create a fresh reader over the same archive that shares no state with this one, so each
+    /// thread can serve tiles with its own reader. None if the data manager can't be cloned
+    pub fn try_clone(&self) -> Option<PMTilesReaderDyn> {
+        let mut reader = PMTilesReader::with_max_depth(
+            self.data_manager.try_clone()?,
+            Some(self.dir_cache.max_size()),
+            self.max_depth,
+        );
+        reader.set_tile_cache_size(self.tile_cache.max_size());
+        reader.lazy_root = self.lazy_root;
+        #[cfg(feature = "std")]
+        {
+            reader.metadata_ttl = self.metadata_ttl;
+        }
+
+        Some(reader)
+    }
+
+    /// Forget the cached header, root directories, metadata, leaf directories and tiles.
+    /// The next call to `get_header` reads them again from the data manager, which is useful
+    /// when the underlying archive was replaced.
+    pub fn reset(&mut self) {
+        self.header = None;
+        self.magic = None;
+        self.root_dir = Directory::default();
+        self.root_dir_s2 = S2Entries::default();
+        self.metadata = None;
+        self.metadata_raw.clear();
+        self.metadata_stale = false;
+        self.dir_cache.clear();
+        self.tile_cache.clear();
+    }
+
     /// If S2, we need to build the other face's root directories
     pub fn get_s2_metadata(&mut self, data: &[u8], header: &mut S2Header) {
         // move the root directory to the s2 root
@@ -157,9 +650,66 @@ impl PMTilesReader {
         }
     }
 
-    /// get the metadata
+    /// get the geographic bounds. None if unset (all zero) or if the archive is S2, whose header
+    /// does not store bounds
+    pub fn bounds(&mut self) -> Option<BoundingBox> {
+        let header = self.get_header();
+        if header.is_s2 {
+            return None;
+        }
+        let bounds = BoundingBox::new(
+            header.min_longitude,
+            header.min_latitude,
+            header.max_longitude,
+            header.max_latitude,
+        );
+        if bounds == BoundingBox::default() {
+            None
+        } else {
+            Some(bounds)
+        }
+    }
+
+    /// get the (min, max) zoom range
+    pub fn zoom_range(&mut self) -> (u8, u8) {
+        let header = self.get_header();
+        (header.min_zoom, header.max_zoom)
+    }
+
+    /// get the metadata, parsed the first time it is asked for. Metadata that does not fit the
+    /// spec is left as its default, it is still available through `get_metadata_raw`
     pub fn get_metadata(&mut self) -> &Metadata {
-        &self.metadata
+        self.refresh_metadata();
+        let raw = &self.metadata_raw;
+        self.metadata
+            .get_or_insert_with(|| serde_json::from_slice(raw).unwrap_or_default())
+    }
+
+    /// get the metadata parsed as any type, for schemas with fields `Metadata` does not have.
+    /// Parsed on every call, nothing is cached
+    pub fn get_metadata_as<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<T, serde_json::Error> {
+        self.refresh_metadata();
+        serde_json::from_slice(&self.metadata_raw)
+    }
+
+    /// get every field of the metadata as a JSON value, including the ones `Metadata` does not
+    /// have. Parsed on every call, nothing is cached
+    pub fn get_all_metadata_fields(&mut self) -> Result<serde_json::Value, serde_json::Error> {
+        self.get_metadata_as()
+    }
+
+    /// get the decompressed metadata bytes without parsing them, useful for archives whose
+    /// metadata does not conform to `Metadata` (which is then left as its default)
+    pub fn get_metadata_raw(&mut self) -> Vec<u8> {
+        self.refresh_metadata();
+        self.metadata_raw.clone()
+    }
+
+    /// get the metadata as a JSON string
+    pub fn get_metadata_str(&mut self) -> Result<String, ReadError> {
+        String::from_utf8(self.get_metadata_raw()).map_err(|_| ReadError::InvalidUtf8)
     }
 
     /// get an S2 tile
@@ -172,39 +722,392 @@ impl PMTilesReader {
         self.get_tile(None, zoom, x, y)
     }
 
-    /// get a tile, wheather WM or S2
+    /// get a tile, wheather WM or S2.
+    /// Returns None if the directories are nested too deep, see `try_get_tile`
     pub fn get_tile(&mut self, face: Option<Face>, zoom: u8, x: u64, y: u64) -> Option<Vec<u8>> {
+        self.try_get_tile(face, zoom, x, y).ok().flatten()
+    }
+
+    /// get a tile, wheather WM or S2.
+    /// Fails if the tile is nested deeper than the max depth of the reader
+    pub fn try_get_tile(
+        &mut self,
+        face: Option<Face>,
+        zoom: u8,
+        x: u64,
+        y: u64,
+    ) -> Result<Option<Vec<u8>>, ReadError> {
         let header = self.get_header();
         let tile_id = Tile::new(zoom, x, y).to_id();
         // if zoom < header.min_zoom || zoom > header.max_zoom { return None; }
+        match self.find_entry(face, tile_id)? {
+            Some(entry) => {
+                let entry_data =
+                    self.get_range(header.data_offset + entry.offset, entry.length as u64);
+                Ok(Some(decompress(&entry_data, header.internal_compression)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// get a WM tile decompressed with the tile compression of the header, whatever the
+    /// internal compression is (which is what `get_tile` uses). Tiles without compression are
+    /// returned as is. The most recently used tiles are cached decompressed, see
+    /// `set_tile_cache_size`. None if the tile does not exist, or if its data is corrupt or
+    /// compressed with a compression this build can't decompress (like Brotli or Zstd)
+    pub fn get_tile_decompressed(&mut self, zoom: u8, x: u64, y: u64) -> Option<Vec<u8>> {
+        self.get_tile_decompressed_face(None, zoom, x, y)
+    }
+
+    /// get an S2 tile decompressed, see `get_tile_decompressed`
+    pub fn get_tile_s2_decompressed(
+        &mut self,
+        face: Face,
+        zoom: u8,
+        x: u64,
+        y: u64,
+    ) -> Option<Vec<u8>> {
+        self.get_tile_decompressed_face(Some(face), zoom, x, y)
+    }
+
+    /// Set the max number of decompressed tiles to keep (0 disables the cache), evicting the
+    /// least recently used ones if needed
+    pub fn set_tile_cache_size(&mut self, max_size: usize) {
+        self.tile_cache.update_max_size(max_size);
+    }
+
+    /// get a tile, wheather WM or S2, decompressed with the tile compression
+    fn get_tile_decompressed_face(
+        &mut self,
+        face: Option<Face>,
+        zoom: u8,
+        x: u64,
+        y: u64,
+    ) -> Option<Vec<u8>> {
+        let header = self.get_header();
+        let entry = self
+            .find_entry(face, Tile::new(zoom, x, y).to_id())
+            .ok()
+            .flatten()?;
+        if let Some(data) = self.tile_cache.get(&entry.offset) {
+            return Some(data.clone());
+        }
+        let data = try_decompress(&self.get_entry_data(&entry), header.tile_compression)?;
+        self.tile_cache.set(entry.offset, data.clone());
+
+        Some(data)
+    }
 
+    /// get many WM tiles given their (zoom, x, y) coordinates in one call. Directories are
+    /// walked in tile ID order and the tile data of neighbouring tiles is fetched with a single
+    /// range request. The results are in the same order as the input
+    pub fn get_tiles_batch(
+        &mut self,
+        tiles: &[(u8, u64, u64)],
+    ) -> Vec<(u8, u64, u64, Option<Vec<u8>>)> {
+        let header = self.get_header();
+        let mut order: Vec<(u64, usize)> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, (zoom, x, y))| (Tile::new(*zoom, *x, *y).to_id(), i))
+            .collect();
+        order.sort_unstable();
+        let mut entries: Vec<(Entry, usize)> = Vec::with_capacity(tiles.len());
+        for (tile_id, i) in order {
+            if let Ok(Some(entry)) = self.find_entry(None, tile_id) {
+                entries.push((entry, i));
+            }
+        }
+        entries.sort_by_key(|(entry, _)| entry.offset);
+
+        let mut result: Vec<_> = tiles.iter().map(|&(z, x, y)| (z, x, y, None)).collect();
+        let mut start = 0;
+        while start < entries.len() {
+            // merge the ranges that touch or overlap
+            let range_start = entries[start].0.offset;
+            let mut range_end = range_start + entries[start].0.length as u64;
+            let mut end = start + 1;
+            while end < entries.len() && entries[end].0.offset <= range_end {
+                range_end = range_end.max(entries[end].0.offset + entries[end].0.length as u64);
+                end += 1;
+            }
+            let data = self.get_range(header.data_offset + range_start, range_end - range_start);
+            for (entry, i) in &entries[start..end] {
+                let from = (entry.offset - range_start) as usize;
+                let to = from + entry.length as usize;
+                result[*i].3 = Some(decompress(&data[from..to], header.internal_compression));
+            }
+            start = end;
+        }
+
+        result
+    }
+
+    /// find the entry of a tile (run_length > 0), walking through the leaf directories as needed
+    fn find_entry(&mut self, face: Option<Face>, tile_id: u64) -> Result<Option<Entry>, ReadError> {
+        let header = self.get_header();
         let mut d_o = header.root_directory_offset;
         let mut d_l = header.root_directory_length;
 
-        for _ in 0..4 {
+        for _ in 0..self.max_depth {
             let directory = self.get_directory(d_o, d_l, face);
             if directory.is_empty() {
-                return None;
+                return Ok(None);
             }
             let entry = find_tile(&directory.entries, tile_id);
             match entry {
                 None => {
-                    return None;
+                    return Ok(None);
                 }
                 Some(entry) => {
                     if entry.run_length > 0 {
-                        let entry_data =
-                            self.get_range(header.data_offset + entry.offset, entry.length as u64);
-                        return Some(decompress(&entry_data, header.internal_compression));
+                        return Ok(Some(entry));
                     } else {
-                        d_o = header.leaf_directory_offset + entry.offset;
+                        d_o = leaf_directory_offset(&header, face) + entry.offset;
                         d_l = entry.length as u64;
                     }
                 }
             }
         }
 
-        panic!("Maximum directory depth exceeded");
+        Err(ReadError::MaxDepthExceeded)
+    }
+
+    /// Get every tile entry for an S2 face (or the WM directory if `None`), walking into any
+    /// leaf directories. Entries are returned in the order they are stored.
+    /// Fails if the directories are nested deeper than the max depth of the reader
+    pub fn get_entries(&mut self, face: Option<Face>) -> Result<Vec<Entry>, ReadError> {
+        let header = self.get_header();
+        let mut entries = Vec::new();
+        self.collect_entries(
+            header.root_directory_offset,
+            header.root_directory_length,
+            face,
+            0,
+            &mut entries,
+        )?;
+
+        Ok(entries)
+    }
+
+    /// Iterate every tile of an S2 archive, face by face (Face0 to Face5) and in tile ID order
+    /// within a face. Leaf directories are only loaded once the iterator reaches them.
+    /// Leaf directories nested deeper than the max depth of the reader are skipped
+    pub fn iter_s2_tiles(&mut self) -> S2TileIter<'_, M> {
+        S2TileIter {
+            reader: self,
+            face: 0,
+            stack: Vec::new(),
+            run: None,
+        }
+    }
+
+    /// Get the root directory of a face (None for WM)
+    pub fn get_root_directory(&mut self, face: Option<Face>) -> Directory {
+        self.get_header();
+        match face {
+            None => self.root_dir.clone(),
+            Some(f) => self.root_dir_s2.get(f).clone(),
+        }
+    }
+
+    /// Get the leaf directory stored at the byte offset (from the start of the archive) and
+    /// length. None if there is no directory there
+    pub fn get_leaf_directory(&mut self, offset: u64, length: u64) -> Option<Directory> {
+        let header = self.get_header();
+        if length == 0 {
+            return None;
+        }
+        if let Some(directory) = self.dir_cache.get(&offset) {
+            return Some(directory.clone());
+        }
+        let data = decompress(&self.get_range(offset, length), header.internal_compression);
+        if data.is_empty() {
+            return None;
+        }
+        let directory = Directory::from_buffer(&mut (&data[..]).into());
+        if directory.is_empty() {
+            return None;
+        }
+        self.dir_cache.set(offset, directory.clone());
+
+        Some(directory)
+    }
+
+    /// Describe the whole directory tree for debugging, one entry per line as
+    /// `tile_id=N (z/x/y) offset=M length=L run_length=R`. Leaf directories are printed indented
+    /// below the entry pointing at them, up to `max_depth` levels of leaves (0 for the root
+    /// only), so a corrupt archive can not send the dump in circles. S2 archives get one
+    /// section per face
+    pub fn dump_directory_tree(&mut self, max_depth: u8) -> String {
+        let header = self.get_header();
+        let faces: Vec<Option<Face>> = if header.is_s2 {
+            FACES.iter().map(|f| Some(*f)).collect()
+        } else {
+            Vec::from([None])
+        };
+        let mut out = String::new();
+        for face in faces {
+            match face {
+                Some(f) => {
+                    let _ = writeln!(out, "face {}:", u8::from(f));
+                }
+                None => out.push_str("root:\n"),
+            }
+            let root = self.get_root_directory(face);
+            let leaf_offset = leaf_directory_offset(&header, face);
+            self.dump_directory(&root, leaf_offset, 1, max_depth, &mut out);
+        }
+
+        out
+    }
+
+    /// Write the entries of a directory at the given indentation, walking into its leaves
+    fn dump_directory(
+        &mut self,
+        directory: &Directory,
+        leaf_offset: u64,
+        depth: u8,
+        max_depth: u8,
+        out: &mut String,
+    ) {
+        let indent = "  ".repeat(depth as usize);
+        for entry in &directory.entries {
+            let _ = write!(out, "{indent}tile_id={} ", entry.tile_id);
+            let _ = match Tile::from_id_checked(entry.tile_id) {
+                Some(tile) => write!(out, "({}/{}/{})", tile.zoom, tile.x, tile.y),
+                None => write!(out, "(?)"),
+            };
+            let _ = writeln!(
+                out,
+                " offset={} length={} run_length={}",
+                entry.offset, entry.length, entry.run_length
+            );
+            if entry.run_length > 0 {
+                continue;
+            }
+            if depth > max_depth {
+                let _ = writeln!(out, "{indent}  ... (max depth reached)");
+                continue;
+            }
+            match self.get_leaf_directory(leaf_offset + entry.offset, entry.length as u64) {
+                Some(leaf) => self.dump_directory(&leaf, leaf_offset, depth + 1, max_depth, out),
+                None => {
+                    let _ = writeln!(out, "{indent}  ... (unreadable leaf directory)");
+                }
+            }
+        }
+    }
+
+    /// Get the number of leaf directories, across all faces.
+    /// Fails if the directories are nested deeper than the max depth of the reader
+    pub fn leaf_directory_count(&mut self) -> Result<u64, ReadError> {
+        Ok(self.statistics()?.leaf_directories)
+    }
+
+    /// Get the number of tiles addressed by each face of an S2 archive, summing the run lengths
+    /// of every entry (leaf directories included). None if the archive is not S2.
+    /// Fails if the directories are nested deeper than the max depth of the reader
+    pub fn face_tile_count(&mut self) -> Result<Option<[u64; 6]>, ReadError> {
+        if !self.get_header().is_s2 {
+            return Ok(None);
+        }
+        let mut counts = [0; 6];
+        for (count, face) in counts.iter_mut().zip(FACES) {
+            *count = self
+                .get_entries(Some(face))?
+                .iter()
+                .map(|e| e.run_length as u64)
+                .sum();
+        }
+
+        Ok(Some(counts))
+    }
+
+    /// Get the number of tiles addressed by the archive, as stored in the header
+    pub fn total_tile_count(&mut self) -> u64 {
+        self.get_header().n_addressed_tiles
+    }
+
+    /// Get summary statistics of the archive.
+    /// Fails if the directories are nested deeper than the max depth of the reader
+    pub fn statistics(&mut self) -> Result<ArchiveStats, ReadError> {
+        let header = self.get_header();
+        let mut entries_per_face = [0; 6];
+        let mut leaf_directories = 0;
+        let faces: Vec<Option<Face>> = if header.is_s2 {
+            FACES.iter().map(|f| Some(*f)).collect()
+        } else {
+            Vec::from([None])
+        };
+        for (i, face) in faces.into_iter().enumerate() {
+            let mut entries = Vec::new();
+            leaf_directories += self.collect_entries(
+                header.root_directory_offset,
+                header.root_directory_length,
+                face,
+                0,
+                &mut entries,
+            )?;
+            if header.is_s2 {
+                entries_per_face[i] = entries.len() as u64;
+            }
+        }
+
+        Ok(ArchiveStats {
+            format_version: header.version,
+            is_s2: header.is_s2,
+            tile_type: header.tile_type,
+            tile_compression: header.tile_compression,
+            min_zoom: header.min_zoom,
+            max_zoom: header.max_zoom,
+            addressed_tiles: header.n_addressed_tiles,
+            unique_tile_contents: header.n_tile_contents,
+            directory_entries: header.n_tile_entries,
+            leaf_directories,
+            data_bytes: header.data_length,
+            total_bytes: header.archive_length(),
+            entries_per_face,
+        })
+    }
+
+    /// Get the raw (still tile compressed) data an entry points to
+    pub fn get_entry_data(&mut self, entry: &Entry) -> Vec<u8> {
+        let header = self.get_header();
+        self.get_range(header.data_offset + entry.offset, entry.length as u64)
+    }
+
+    /// Recursively collect the tile entries of a directory, returning the number of leaf
+    /// directories visited
+    fn collect_entries(
+        &mut self,
+        offset: u64,
+        length: u64,
+        face: Option<Face>,
+        depth: u8,
+        entries: &mut Vec<Entry>,
+    ) -> Result<u64, ReadError> {
+        if depth >= self.max_depth {
+            return Err(ReadError::MaxDepthExceeded);
+        }
+        let leaf_directory_offset = leaf_directory_offset(&self.header.unwrap(), face);
+        let directory = self.get_directory(offset, length, face);
+        let mut leaves = 0;
+        for entry in directory.entries {
+            if entry.run_length > 0 {
+                entries.push(entry);
+            } else {
+                leaves += 1 + self.collect_entries(
+                    leaf_directory_offset + entry.offset,
+                    entry.length as u64,
+                    face,
+                    depth + 1,
+                    entries,
+                )?;
+            }
+        }
+
+        Ok(leaves)
     }
 
     /// Get a full directory
@@ -244,33 +1147,250 @@ impl PMTilesReader {
     }
 }
 
-/// Decompress the data based on the compression type
-/// NOTE: Currently only supports `Compression::None`
-fn decompress(data: &[u8], compression: Compression) -> Vec<u8> {
-    match compression {
-        Compression::None => data.to_vec(),
-        #[cfg(feature = "std")]
-        Compression::Gzip => {
-            let mut gz = GzDecoder::new(data);
-            let mut decompressed_data = Vec::new();
-            gz.read_to_end(&mut decompressed_data)
-                .expect("Failed to decompress gzip data");
-            decompressed_data
-        }
-        _ => panic!("Decompression error"),
-    }
+/// An iterator over the (face, tile, data) of every tile of an S2 archive,
+/// see `PMTilesReader::iter_s2_tiles`
+#[derive(Debug)]
+pub struct S2TileIter<'a, M: DataManager = Box<dyn DataManager>> {
+    reader: &'a mut PMTilesReader<M>,
+    /// index of the next face to walk
+    face: usize,
+    /// the directories being walked with the index of their next entry
+    stack: Vec<(Directory, usize)>,
+    /// the current entry, the next tile of its run and its data
+    run: Option<(Entry, u32, Vec<u8>)>,
+}
+impl<M: DataManager> Iterator for S2TileIter<'_, M> {
+    type Item = (Face, Tile, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.reader.get_header();
+        loop {
+            if let Some((entry, i, data)) = &mut self.run {
+                if *i < entry.run_length {
+                    let tile = Tile::from_id(entry.tile_id + *i as u64);
+                    *i += 1;
+                    return Some((FACES[self.face - 1], tile, data.clone()));
+                }
+                self.run = None;
+            }
+            let depth = self.stack.len();
+            if let Some((directory, i)) = self.stack.last_mut() {
+                let Some(entry) = directory.entries.get(*i).copied() else {
+                    self.stack.pop();
+                    continue;
+                };
+                *i += 1;
+                if entry.run_length > 0 {
+                    let data = self.reader.get_entry_data(&entry);
+                    let data = decompress(&data, header.internal_compression);
+                    self.run = Some((entry, 0, data));
+                } else if depth < self.reader.max_depth as usize {
+                    let face = Some(FACES[self.face - 1]);
+                    let offset = leaf_directory_offset(&header, face) + entry.offset;
+                    if let Some(leaf) = self.reader.get_leaf_directory(offset, entry.length as u64)
+                    {
+                        self.stack.push((leaf, 0));
+                    }
+                }
+                continue;
+            }
+            // move on to the next face
+            let face = *FACES.get(self.face)?;
+            self.face += 1;
+            self.stack
+                .push((self.reader.get_root_directory(Some(face)), 0));
+        }
+    }
+}
+
+/// The number of directories a `PMTilesReaderStatic` keeps in its cache
+pub const STATIC_DIR_CACHE_SIZE: usize = 8;
+/// The max metadata size in bytes a `PMTilesReaderStatic` keeps
+pub const STATIC_METADATA_SIZE: usize = 4_096;
+
+/// A reader for constrained embedded targets. Unlike `PMTilesReader`, the data manager is
+/// stored by value, directories (roots included) are kept in a fixed size cache and the
+/// metadata is kept as raw JSON in a fixed size buffer.
+#[derive(Debug)]
+pub struct PMTilesReaderStatic<M: DataManager> {
+    header: Option<S2Header>,
+    metadata: [u8; STATIC_METADATA_SIZE],
+    metadata_length: usize,
+    dir_cache: [Option<(u64, Directory)>; STATIC_DIR_CACHE_SIZE],
+    next_cache_slot: usize,
+    max_depth: u8,
+    data_manager: M,
+}
+impl<M: DataManager> PMTilesReaderStatic<M> {
+    /// Create a reader that owns its data manager
+    pub fn new(data_manager: M) -> Self {
+        Self::with_max_depth(data_manager, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Same as `new`, but with the max number of nested directories (root included) to walk
+    /// through when looking up a tile
+    pub fn with_max_depth(data_manager: M, max_depth: u8) -> Self {
+        Self {
+            header: None,
+            metadata: [0; STATIC_METADATA_SIZE],
+            metadata_length: 0,
+            dir_cache: Default::default(),
+            next_cache_slot: 0,
+            max_depth,
+            data_manager,
+        }
+    }
+
+    /// fetch the header (and metadata) as needed
+    pub fn get_header(&mut self) -> S2Header {
+        if let Some(header) = self.header {
+            return header;
+        }
+
+        let header_data = self.data_manager.get_range(0, S2_HEADER_SIZE_BYTES as u64);
+        let header = parse_header(&header_data);
+
+        // json metadata, skipped if it does not fit the buffer
+        let json_metadata = decompress(
+            &self
+                .data_manager
+                .get_range(header.metadata_offset, header.metadata_length),
+            header.internal_compression,
+        );
+        if json_metadata.len() <= STATIC_METADATA_SIZE {
+            self.metadata[..json_metadata.len()].copy_from_slice(&json_metadata);
+            self.metadata_length = json_metadata.len();
+        }
+
+        self.header = Some(header);
+
+        header
+    }
+
+    /// get the raw JSON metadata. Empty if the metadata is larger than `STATIC_METADATA_SIZE`
+    pub fn get_metadata(&mut self) -> &[u8] {
+        self.get_header();
+        &self.metadata[..self.metadata_length]
+    }
+
+    /// get an S2 tile
+    pub fn get_tile_s2(&mut self, face: Face, zoom: u8, x: u64, y: u64) -> Option<Vec<u8>> {
+        self.get_tile(Some(face), zoom, x, y)
+    }
+
+    /// get an WM tile
+    pub fn get_tile_zxy(&mut self, zoom: u8, x: u64, y: u64) -> Option<Vec<u8>> {
+        self.get_tile(None, zoom, x, y)
+    }
+
+    /// get a tile, wheather WM or S2.
+    /// Returns None if the directories are nested too deep, see `try_get_tile`
+    pub fn get_tile(&mut self, face: Option<Face>, zoom: u8, x: u64, y: u64) -> Option<Vec<u8>> {
+        self.try_get_tile(face, zoom, x, y).ok().flatten()
+    }
+
+    /// get a tile, wheather WM or S2.
+    /// Fails if the tile is nested deeper than the max depth of the reader
+    pub fn try_get_tile(
+        &mut self,
+        face: Option<Face>,
+        zoom: u8,
+        x: u64,
+        y: u64,
+    ) -> Result<Option<Vec<u8>>, ReadError> {
+        let header = self.get_header();
+        let tile_id = Tile::new(zoom, x, y).to_id();
+
+        let (mut d_o, mut d_l) = match face {
+            None => (header.root_directory_offset, header.root_directory_length),
+            Some(f) => (header.get_root_offset(f), header.get_root_length(f)),
+        };
+        let leaf_offset = leaf_directory_offset(&header, face);
+
+        for _ in 0..self.max_depth {
+            let directory = self.get_directory(d_o, d_l);
+            let Some(entry) = find_tile(&directory.entries, tile_id) else {
+                return Ok(None);
+            };
+            if entry.run_length > 0 {
+                let entry_data = self
+                    .data_manager
+                    .get_range(header.data_offset + entry.offset, entry.length as u64);
+                return Ok(Some(decompress(&entry_data, header.internal_compression)));
+            }
+            d_o = leaf_offset + entry.offset;
+            d_l = entry.length as u64;
+        }
+
+        Err(ReadError::MaxDepthExceeded)
+    }
+
+    /// Get a directory from the cache or the archive, replacing the oldest cached directory
+    fn get_directory(&mut self, offset: u64, length: u64) -> Directory {
+        if let Some((_, dir)) = self.dir_cache.iter().flatten().find(|(o, _)| *o == offset) {
+            return dir.clone();
+        }
+        let internal_compression = self.header.unwrap().internal_compression;
+        let data = decompress(
+            &self.data_manager.get_range(offset, length),
+            internal_compression,
+        );
+        let directory = Directory::from_buffer(&mut (&data[..]).into());
+        self.dir_cache[self.next_cache_slot] = Some((offset, directory.clone()));
+        self.next_cache_slot = (self.next_cache_slot + 1) % STATIC_DIR_CACHE_SIZE;
+
+        directory
+    }
+}
+
+/// Get the leaf directory offset of a face (or the WM leaf directory if `None`)
+fn leaf_directory_offset(header: &S2Header, face: Option<Face>) -> u64 {
+    header.get_leaf_offset(face.unwrap_or(Face::Face0))
+}
+
+/// Decompress the data based on the compression type, panics if it can't, see `try_decompress`
+fn decompress(data: &[u8], compression: Compression) -> Vec<u8> {
+    try_decompress(data, compression).expect("Decompression error")
+}
+
+/// Decompress the data based on the compression type, None if the data is corrupt or the
+/// compression is not supported.
+/// NOTE: Currently supports `Compression::None`, `Compression::Gzip` and `Compression::Zlib`,
+/// and `Compression::Lz4` if the `lz4` feature is enabled
+fn try_decompress(data: &[u8], compression: Compression) -> Option<Vec<u8>> {
+    match compression {
+        Compression::None => Some(data.to_vec()),
+        #[cfg(feature = "std")]
+        Compression::Gzip => read_decoder(GzDecoder::new(data)),
+        #[cfg(feature = "std")]
+        Compression::Zlib => read_decoder(ZlibDecoder::new(data)),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => read_decoder(FrameDecoder::new(data)),
+        _ => None,
+    }
+}
+
+/// Read a decoder to the end, None if the data is corrupt
+#[cfg(feature = "std")]
+fn read_decoder(mut decoder: impl Read) -> Option<Vec<u8>> {
+    let mut decompressed_data = Vec::new();
+    decoder.read_to_end(&mut decompressed_data).ok()?;
+
+    Some(decompressed_data)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::TileType;
+    use crate::writer::{LocalWriter, PMTilesWriter};
+    use crate::{Header, HEADER_SIZE_BYTES};
     use s2_tilejson::{Encoding, Scheme, SourceType, VectorLayer};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_fixture_1() {
         let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
-        let mut reader = PMTilesReader::new(Box::new(file_manager), None);
+        let mut reader = PMTilesReader::new(file_manager, None);
 
         let header = reader.get_header();
         assert_eq!(
@@ -366,7 +1486,7 @@ mod tests {
         // read in "./test/fixtures/test_fixture_1.pmtiles" to a Vec<u8>
         let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
         let local_manager = LocalManager::new(data);
-        let mut reader = PMTilesReader::new(Box::new(local_manager), None);
+        let mut reader = PMTilesReader::new(local_manager, None);
 
         let header = reader.get_header();
         assert_eq!(
@@ -457,6 +1577,1116 @@ mod tests {
         );
     }
 
+    /// The range (first and last byte) of every request served by `serve_file`
+    #[cfg(feature = "http")]
+    type ServedRanges = Arc<Mutex<Vec<(usize, usize)>>>;
+
+    /// Serve a file on a loopback port, answering range requests, and record their ranges
+    #[cfg(feature = "http")]
+    fn serve_file(data: Vec<u8>) -> (String, ServedRanges) {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/archive.pmtiles", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(bytes) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        let (start, end) = bytes.split_once('-').unwrap();
+                        range = Some((
+                            start.parse::<usize>().unwrap(),
+                            end.parse::<usize>().unwrap(),
+                        ));
+                    }
+                }
+                let (start, end) = range.unwrap_or((0, data.len() - 1));
+                counter.lock().unwrap().push((start, end));
+                let body = &data[start.min(data.len())..(end + 1).min(data.len())];
+                let head = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    #[ignore = "opens a loopback HTTP server"]
+    fn test_http_range_manager() {
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let (url, requests) = serve_file(data);
+        let manager = HttpRangeManager::new(url).unwrap();
+        let mut reader = PMTilesReader::new(manager, None);
+        assert_eq!(reader.get_header().version, 3);
+        assert!(reader.get_tile_zxy(0, 0, 0).is_some());
+        // the header, root directory, metadata and tile all live in the cached root block
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let mut local = PMTilesReader::new(
+            FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap(),
+            None,
+        );
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), local.get_tile_zxy(0, 0, 0));
+        assert_eq!(reader.get_metadata(), local.get_metadata());
+
+        // clones share the cached root block
+        let mut clone = reader.try_clone().unwrap();
+        assert_eq!(clone.get_header(), reader.get_header());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        // ranges past the root block are requested as is
+        let mut manager = reader.into_inner();
+        let tail = manager.get_range(S2_ROOT_SIZE as u64, 16);
+        assert!(tail.is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // a lazy reader only fetches the header, root directory and metadata ranges
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let (url, requests) = serve_file(data);
+        let manager = HttpRangeManager::new(url).unwrap();
+        let mut reader = PMTilesReader::new(manager, None).with_lazy_root();
+        let header = reader.get_header();
+        assert_eq!(header, local.get_header());
+        let root_start = header.root_directory_offset as usize;
+        let metadata_start = header.metadata_offset as usize;
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                (0, S2_HEADER_SIZE_BYTES - 1),
+                (
+                    root_start,
+                    root_start + header.root_directory_length as usize - 1
+                ),
+                (
+                    metadata_start,
+                    metadata_start + header.metadata_length as usize - 1
+                ),
+            ]
+        );
+        assert_eq!(reader.get_metadata(), local.get_metadata());
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), local.get_tile_zxy(0, 0, 0));
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_generic_reader() {
+        let path = "./test/fixtures/test_fixture_1.pmtiles";
+        let mut file_reader: PMTilesReader<FileManager> =
+            PMTilesReader::new(FileManager::new(path).unwrap(), None);
+        let expected = file_reader.get_tile_zxy(0, 0, 0).unwrap();
+        assert!(!expected.is_empty());
+
+        let mut local_reader: PMTilesReader<LocalManager> =
+            PMTilesReader::new(LocalManager::new(std::fs::read(path).unwrap()), None);
+        assert_eq!(local_reader.get_tile_zxy(0, 0, 0).unwrap(), expected);
+        assert_eq!(local_reader.get_header(), file_reader.get_header());
+
+        // chosen at runtime
+        let managers: [Box<dyn DataManager>; 2] = [
+            Box::new(FileManager::new(path).unwrap()),
+            Box::new(local_reader.into_inner()),
+        ];
+        for manager in managers {
+            let mut reader: PMTilesReaderDyn = PMTilesReader::from_boxed(manager, None);
+            assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), expected);
+            let mut clone = reader.try_clone().unwrap();
+            assert_eq!(clone.get_tile_zxy(0, 0, 0).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_cursor_manager() {
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut local = PMTilesReader::new(LocalManager::new(data.clone()), None);
+        // any Read + Seek works through the blanket impl
+        let mut cursor = PMTilesReader::new(std::io::Cursor::new(data), None);
+
+        assert_eq!(local.get_header(), cursor.get_header());
+        for (z, x, y) in [(0, 0, 0), (1, 0, 0), (1, 0, 1), (1, 1, 1), (2, 0, 0)] {
+            assert_eq!(local.get_tile_zxy(z, x, y), cursor.get_tile_zxy(z, x, y));
+        }
+        assert!(cursor.get_tile_zxy(0, 0, 0).is_some());
+
+        // ranges past the end are truncated like the local manager
+        let mut cursor = std::io::Cursor::new(vec![1, 2, 3]);
+        assert_eq!(cursor.get_range(1, 10), vec![2, 3]);
+        assert_eq!(cursor.get_range(5, 10), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let coords = [
+            (0, 0, 0),
+            (1, 0, 0),
+            (1, 0, 1),
+            (1, 1, 1),
+            (2, 1, 1),
+            (3, 4, 2),
+        ];
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, Some(5));
+        let expected: Vec<_> = coords
+            .iter()
+            .map(|&(z, x, y)| reader.get_tile_zxy(z, x, y))
+            .collect();
+        assert!(expected[0].is_some());
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let mut clone = reader.try_clone().unwrap();
+                assert!(clone.header.is_none());
+                std::thread::spawn(move || {
+                    coords
+                        .iter()
+                        .map(|&(z, x, y)| clone.get_tile_zxy(z, x, y))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+
+        // local
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let reader = PMTilesReader::with_max_depth(LocalManager::new(data), None, 2);
+        let mut clone = reader.try_clone().unwrap();
+        assert_eq!(clone.max_depth, 2);
+        assert_eq!(clone.get_tile_zxy(0, 0, 0), expected[0]);
+
+        // managers that can't be cloned
+        let cursor = std::io::Cursor::new(vec![]);
+        let reader = PMTilesReader::new(cursor, None);
+        assert!(reader.try_clone().is_none());
+    }
+
+    #[test]
+    fn test_bounds_and_zoom_range() {
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(
+            reader.bounds(),
+            Some(BoundingBox::new(0.0, 0.0, 0.9999999, 1.0))
+        );
+        assert_eq!(reader.zoom_range(), (0, 0));
+
+        // s2 headers do not store bounds
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_bounds(-10.0, -5.0, 10.0, 5.0);
+        writer
+            .write_tile_s2(Face::Face2, 1, 0, 0, b"hello")
+            .unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.bounds(), None);
+
+        // unset bounds
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(2, 0, 0, b"hello").unwrap();
+        writer.write_tile_xyz(5, 0, 0, b"world").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.bounds(), None);
+        assert_eq!(reader.zoom_range(), (2, 5));
+    }
+
+    #[test]
+    fn test_inner_manager() {
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(data.clone()), None);
+        assert!(reader.get_tile_zxy(0, 0, 0).is_some());
+        assert!(format!("{:?}", reader.inner_manager()).starts_with("LocalManager"));
+        assert_eq!(reader.inner_manager_mut().get_range(0, 2), b"PM");
+
+        let mut manager = reader.into_inner();
+        assert_eq!(manager.get_range(0, data.len() as u64), data);
+    }
+
+    /// counts the range requests made to a local manager
+    #[derive(Debug)]
+    struct CountingManager(LocalManager, Arc<Mutex<usize>>);
+    impl DataManager for CountingManager {
+        fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+            *self.1.lock().unwrap() += 1;
+            self.0.get_range(offset, length)
+        }
+    }
+
+    #[test]
+    fn test_get_tiles_batch() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for zoom in 0..4 {
+            for id in crate::TileRange::for_zoom(zoom).iter() {
+                let data = format!("tile {id}");
+                writer.write_tile(id, data.as_bytes(), None).unwrap();
+            }
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let data = writer.take();
+
+        let zxy = |id: u64| {
+            let tile = Tile::from_id(id);
+            (tile.zoom, tile.x, tile.y)
+        };
+        // tile data is stored in tile ID order
+        let tiles = [zxy(6), zxy(0), (9, 0, 0), zxy(5), zxy(7), zxy(6)];
+        let count = Arc::new(Mutex::new(0));
+        let manager = CountingManager(LocalManager::new(data.clone()), count.clone());
+        let mut reader = PMTilesReader::new(manager, None);
+        reader.get_header();
+        *count.lock().unwrap() = 0;
+        let batch = reader.get_tiles_batch(&tiles);
+        // tile 0 on its own, tiles 5 to 7 together
+        assert_eq!(*count.lock().unwrap(), 2);
+
+        let mut single = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(batch.len(), tiles.len());
+        for ((zoom, x, y, tile), input) in batch.into_iter().zip(tiles) {
+            assert_eq!((zoom, x, y), input);
+            assert_eq!(tile, single.get_tile_zxy(zoom, x, y));
+        }
+        assert_eq!(reader.get_tiles_batch(&[(9, 0, 0)]), vec![(9, 0, 0, None)]);
+        assert!(reader.get_tiles_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_leaf_directories() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for id in 0..20_000u64 {
+            writer.write_tile(id, &id.to_le_bytes(), None).unwrap();
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+
+        let root = reader.get_root_directory(None);
+        assert_eq!(root.len(), 5);
+        assert!(root.entries.iter().all(|e| e.run_length == 0));
+        assert_eq!(reader.leaf_directory_count().unwrap(), 5);
+
+        let mut tile_id = 0;
+        for leaf in &root.entries {
+            let offset = header.leaf_directory_offset + leaf.offset;
+            let directory = reader
+                .get_leaf_directory(offset, leaf.length as u64)
+                .unwrap();
+            assert_eq!(directory.first().unwrap().tile_id, leaf.tile_id);
+            for entry in directory.entries {
+                assert_eq!(entry, Entry::new(tile_id, tile_id * 8, 8, 1));
+                tile_id += 1;
+            }
+        }
+        assert_eq!(tile_id, 20_000);
+        assert_eq!(
+            reader.get_leaf_directory(header.leaf_directory_offset, 0),
+            None
+        );
+
+        // wm archives are face 0 too, without leaves here
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        let root = reader.get_root_directory(None);
+        assert_eq!(root, reader.get_root_directory(Some(Face::Face0)));
+        assert_eq!(root.entries, reader.get_entries(None).unwrap());
+        assert_eq!(reader.leaf_directory_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_s2_leaf_directories_per_face() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for face in [Face::Face0, Face::Face3] {
+            for id in 0..20_000u64 {
+                let data = [u8::from(face).to_le_bytes().as_slice(), &id.to_le_bytes()].concat();
+                writer.write_tile(id, &data, Some(face)).unwrap();
+            }
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let data = writer.take();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(data.clone()), None);
+        let header = reader.get_header();
+        assert!(header.get_leaf_length(Face::Face0) > 0);
+        assert!(header.get_leaf_length(Face::Face3) > 0);
+        assert_ne!(
+            header.get_leaf_offset(Face::Face0),
+            header.get_leaf_offset(Face::Face3)
+        );
+        for id in [0, 7_777, 19_999] {
+            let tile = Tile::from_id(id);
+            for face in [Face::Face0, Face::Face3] {
+                let expected =
+                    [u8::from(face).to_le_bytes().as_slice(), &id.to_le_bytes()].concat();
+                assert_eq!(
+                    reader.get_tile_s2(face, tile.zoom, tile.x, tile.y),
+                    Some(expected)
+                );
+            }
+        }
+        assert_eq!(reader.get_entries(Some(Face::Face3)).unwrap().len(), 20_000);
+        assert_eq!(reader.iter_s2_tiles().count(), 40_000);
+        assert_eq!(
+            reader.face_tile_count().unwrap(),
+            Some([20_000, 0, 0, 20_000, 0, 0])
+        );
+        assert_eq!(reader.total_tile_count(), 40_000);
+
+        let mut reader = PMTilesReaderStatic::new(LocalManager::new(data));
+        let tile = Tile::from_id(12_345);
+        let expected = [3u8.to_le_bytes().as_slice(), &12_345u64.to_le_bytes()].concat();
+        assert_eq!(
+            reader.get_tile_s2(Face::Face3, tile.zoom, tile.x, tile.y),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn test_iter_s2_tiles() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        let mut expected = Vec::new();
+        // written out of face order on purpose
+        for face in [Face::Face4, Face::Face0, Face::Face2] {
+            for tile in crate::TileRange::for_zoom(2).iter().map(Tile::from_id) {
+                let data = vec![u8::from(face), tile.x as u8, tile.y as u8];
+                writer
+                    .write_tile_s2(face, tile.zoom, tile.x, tile.y, &data)
+                    .unwrap();
+            }
+        }
+        // a run of identical tiles on face 2
+        for tile in crate::TileRange::for_zoom(3).iter().map(Tile::from_id) {
+            writer
+                .write_tile_s2(Face::Face2, tile.zoom, tile.x, tile.y, &[9])
+                .unwrap();
+        }
+        for face in [Face::Face0, Face::Face2, Face::Face4] {
+            for tile in crate::TileRange::for_zoom(2).iter().map(Tile::from_id) {
+                expected.push((face, tile, vec![u8::from(face), tile.x as u8, tile.y as u8]));
+            }
+            if face == Face::Face2 {
+                for tile in crate::TileRange::for_zoom(3).iter().map(Tile::from_id) {
+                    expected.push((face, tile, vec![9]));
+                }
+            }
+        }
+        writer.commit(&Metadata::default()).unwrap();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let tiles: Vec<_> = reader.iter_s2_tiles().collect();
+        assert_eq!(tiles.len(), 16 * 3 + 64);
+        assert_eq!(tiles, expected);
+        // the run on face 2 is a single entry addressing 64 tiles
+        let counts = reader.face_tile_count().unwrap().unwrap();
+        assert_eq!(counts, [16, 0, 16 + 64, 0, 16, 0]);
+        assert_eq!(counts.iter().sum::<u64>(), reader.total_tile_count());
+        // the iterator can be restarted
+        assert_eq!(reader.iter_s2_tiles().count(), tiles.len());
+    }
+
+    #[test]
+    fn test_statistics() {
+        let path = "./test/fixtures/test_fixture_1.pmtiles";
+        let file_manager = FileManager::new(path).unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(
+            reader.statistics().unwrap(),
+            ArchiveStats {
+                format_version: 3,
+                is_s2: false,
+                tile_type: TileType::Pbf,
+                tile_compression: Compression::Gzip,
+                min_zoom: 0,
+                max_zoom: 0,
+                addressed_tiles: 1,
+                unique_tile_contents: 1,
+                directory_entries: 1,
+                leaf_directories: 0,
+                data_bytes: 69,
+                total_bytes: std::fs::metadata(path).unwrap().len(),
+                entries_per_face: [0; 6],
+            }
+        );
+        assert_eq!(reader.face_tile_count(), Ok(None));
+        assert_eq!(reader.total_tile_count(), 1);
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer
+            .write_tile_s2(Face::Face0, 0, 0, 0, b"hello world")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 1, 1, 0, b"hello world")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 2, 1, 1, b"hello")
+            .unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let archive = writer.take();
+        let total_bytes = archive.len() as u64;
+        let mut reader = PMTilesReader::new(LocalManager::new(archive), None);
+        let stats = reader.statistics().unwrap();
+        assert!(stats.is_s2);
+        assert_eq!(stats.total_bytes, total_bytes);
+        assert_eq!(stats.addressed_tiles, 3);
+        assert_eq!(stats.unique_tile_contents, 2);
+        assert_eq!(stats.leaf_directories, 0);
+        assert_eq!(stats.data_bytes, 16);
+        assert_eq!(stats.entries_per_face, [1, 0, 0, 2, 0, 0]);
+
+        // leaf directories are appended after the tile data
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for id in 0..Tile::global_tile_id_range(7).end {
+            writer
+                .write_tile(id, format!("tile {id}").as_bytes(), None)
+                .unwrap();
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let archive = writer.take();
+        let total_bytes = archive.len() as u64;
+        let mut reader = PMTilesReader::new(LocalManager::new(archive), None);
+        let stats = reader.statistics().unwrap();
+        assert!(stats.leaf_directories > 0);
+        assert_eq!(stats.total_bytes, total_bytes);
+    }
+
+    /// An archive with the tile "hello" behind two nested leaf directories
+    fn nested_leaves_archive() -> Vec<u8> {
+        // root -> leaf 1 -> leaf 2 -> tile
+        let leaf_2 = Directory::new(vec![Entry::new(0, 0, 5, 1)]).serialize();
+        let leaf_1 = Directory::new(vec![Entry::new(0, 0, leaf_2.len() as u32, 0)]).serialize();
+        let root = Directory::new(vec![Entry::new(
+            0,
+            leaf_2.len() as u64,
+            leaf_1.len() as u32,
+            0,
+        )])
+        .serialize();
+        let metadata = serde_json::to_vec(&Metadata::default()).unwrap();
+        let root_offset = HEADER_SIZE_BYTES as u64;
+        let metadata_offset = root_offset + root.len() as u64;
+        let leaf_offset = metadata_offset + metadata.len() as u64;
+        let data_offset = leaf_offset + (leaf_2.len() + leaf_1.len()) as u64;
+        let header = Header {
+            version: 3,
+            root_directory_offset: root_offset,
+            root_directory_length: root.len() as u64,
+            metadata_offset,
+            metadata_length: metadata.len() as u64,
+            leaf_directory_offset: leaf_offset,
+            leaf_directory_length: (leaf_2.len() + leaf_1.len()) as u64,
+            data_offset,
+            data_length: 5,
+            internal_compression: Compression::None,
+            ..Default::default()
+        };
+        let mut data = header.to_bytes().take();
+        data.truncate(HEADER_SIZE_BYTES);
+        data.extend(root);
+        data.extend(metadata);
+        data.extend(leaf_2);
+        data.extend(leaf_1);
+        data.extend(b"hello");
+
+        data
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let data = nested_leaves_archive();
+
+        let mut reader = PMTilesReader::with_max_depth(LocalManager::new(data.clone()), None, 3);
+        assert_eq!(
+            reader.try_get_tile(None, 0, 0, 0),
+            Ok(Some(b"hello".to_vec()))
+        );
+        let mut reader = PMTilesReader::new(LocalManager::new(data.clone()), None);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"hello");
+        let mut reader = PMTilesReaderStatic::with_max_depth(LocalManager::new(data.clone()), 3);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"hello");
+        let mut reader = PMTilesReaderStatic::with_max_depth(LocalManager::new(data.clone()), 2);
+        assert_eq!(
+            reader.try_get_tile(None, 0, 0, 0),
+            Err(ReadError::MaxDepthExceeded)
+        );
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), None);
+        let mut reader = PMTilesReader::with_max_depth(LocalManager::new(data), None, 2);
+        assert_eq!(
+            reader.try_get_tile(None, 0, 0, 0),
+            Err(ReadError::MaxDepthExceeded)
+        );
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), None);
+        // walking every entry fails the same way instead of panicking
+        assert_eq!(reader.get_entries(None), Err(ReadError::MaxDepthExceeded));
+        assert_eq!(reader.statistics(), Err(ReadError::MaxDepthExceeded));
+        assert_eq!(
+            reader.leaf_directory_count(),
+            Err(ReadError::MaxDepthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_dump_directory_tree() {
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(
+            reader.dump_directory_tree(8),
+            "root:\n  tile_id=0 (0/0/0) offset=0 length=69 run_length=1\n"
+        );
+
+        let mut reader = PMTilesReader::new(LocalManager::new(nested_leaves_archive()), None);
+        assert_eq!(
+            reader.dump_directory_tree(8),
+            "root:\n\
+            \x20 tile_id=0 (0/0/0) offset=5 length=5 run_length=0\n\
+            \x20   tile_id=0 (0/0/0) offset=0 length=5 run_length=0\n\
+            \x20     tile_id=0 (0/0/0) offset=0 length=5 run_length=1\n"
+        );
+        assert_eq!(
+            reader.dump_directory_tree(1),
+            "root:\n\
+            \x20 tile_id=0 (0/0/0) offset=5 length=5 run_length=0\n\
+            \x20   tile_id=0 (0/0/0) offset=0 length=5 run_length=0\n\
+            \x20     ... (max depth reached)\n"
+        );
+        assert_eq!(
+            reader.dump_directory_tree(0),
+            "root:\n\
+            \x20 tile_id=0 (0/0/0) offset=5 length=5 run_length=0\n\
+            \x20   ... (max depth reached)\n"
+        );
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_s2(Face::Face0, 0, 0, 0, b"a").unwrap();
+        writer.write_tile_s2(Face::Face3, 1, 1, 0, b"bc").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(
+            reader.dump_directory_tree(8),
+            "face 0:\n\
+            \x20 tile_id=0 (0/0/0) offset=0 length=1 run_length=1\n\
+            face 1:\n\
+            face 2:\n\
+            face 3:\n\
+            \x20 tile_id=4 (1/1/0) offset=1 length=2 run_length=1\n\
+            face 4:\n\
+            face 5:\n"
+        );
+    }
+
+    #[test]
+    fn test_wm_as_face_0() {
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        let tile = reader.get_tile_zxy(0, 0, 0).unwrap();
+        assert_eq!(reader.get_tile_s2(Face::Face0, 0, 0, 0).unwrap(), tile);
+        assert_eq!(reader.get_tile_s2(Face::Face1, 0, 0, 0), None);
+        assert_eq!(
+            reader.get_entries(Some(Face::Face0)).unwrap(),
+            reader.get_entries(None).unwrap()
+        );
+    }
+
+    /// a WM archive with an empty root directory and the given metadata bytes
+    fn archive_with_metadata(metadata: &[u8]) -> Vec<u8> {
+        let root = Directory::default().serialize();
+        let header = Header {
+            version: 3,
+            root_directory_offset: HEADER_SIZE_BYTES as u64,
+            root_directory_length: root.len() as u64,
+            metadata_offset: (HEADER_SIZE_BYTES + root.len()) as u64,
+            metadata_length: metadata.len() as u64,
+            internal_compression: Compression::None,
+            ..Default::default()
+        };
+        let mut data = header.to_bytes().take();
+        data.truncate(HEADER_SIZE_BYTES);
+        data.extend(root);
+        data.extend(metadata);
+        // the reader always reads a full S2 header
+        data.resize(data.len().max(S2_HEADER_SIZE_BYTES), 0);
+        data
+    }
+
+    #[test]
+    fn test_try_get_header() {
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(reader.try_get_header().unwrap().version, 3);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(vec![0; 512]), None);
+        assert_eq!(reader.try_get_header(), Err(ReadError::InvalidMagicBytes));
+        let mut reader = PMTilesReader::new(
+            LocalManager::new(b"{\"not\": \"an archive\"}".to_vec()),
+            None,
+        );
+        assert_eq!(reader.try_get_header(), Err(ReadError::InvalidMagicBytes));
+    }
+
+    #[test]
+    fn test_uncached_header_and_metadata() {
+        let data = std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(std::io::Cursor::new(data.clone()), None);
+        let header = reader.get_header_uncached().unwrap();
+        assert!(reader.header.is_none());
+        assert_eq!(header, reader.get_header());
+        let metadata = reader.get_metadata_uncached().unwrap();
+        assert_eq!(&metadata, reader.get_metadata());
+        let tile = reader.get_tile_zxy(0, 0, 0).unwrap();
+
+        // corrupt the archive once cached: tiles are still served from the cached directories
+        // while the uncached reads see the damage
+        reader.inner_manager_mut().get_mut()[0] = b'X';
+        assert_eq!(
+            reader.get_header_uncached(),
+            Err(ReadError::InvalidMagicBytes)
+        );
+        assert_eq!(
+            reader.get_metadata_uncached(),
+            Err(ReadError::InvalidMagicBytes)
+        );
+        assert_eq!(reader.get_header(), header);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), tile);
+
+        // truncated header or metadata
+        let mut reader = PMTilesReader::new(LocalManager::new(data[..100].to_vec()), None);
+        assert_eq!(reader.get_header_uncached(), Err(ReadError::Truncated));
+        let mut reader = PMTilesReader::new(LocalManager::new(data[..200].to_vec()), None);
+        assert_eq!(reader.get_header_uncached(), Ok(header));
+        assert_eq!(reader.get_metadata_uncached(), Err(ReadError::Truncated));
+
+        // metadata that does not parse
+        let data = archive_with_metadata(b"{\"name\": \"broken");
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(
+            reader.get_metadata_uncached(),
+            Err(ReadError::InvalidMetadata)
+        );
+        assert_eq!(reader.get_metadata(), &Metadata::default());
+    }
+
+    #[test]
+    fn test_lazy_metadata() {
+        let data = archive_with_metadata(b"{\"name\": \"broken");
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        let header = reader.get_header();
+        assert_eq!(header.version, 3);
+        assert_eq!(reader.zoom_range(), (0, 0));
+        assert_eq!(reader.metadata, None);
+        assert_eq!(reader.get_metadata(), &Metadata::default());
+        assert_eq!(reader.metadata, Some(Metadata::default()));
+
+        // parsed without calling get_header first, and forgotten on reset
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(reader.get_metadata().name, "test_fixture_1.pmtiles");
+        reader.reset();
+        assert_eq!(reader.metadata, None);
+        reader.get_header();
+        assert_eq!(reader.metadata, None);
+    }
+
+    #[test]
+    fn test_metadata_as() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct CustomMeta {
+            name: String,
+            generator: String,
+            layer_count: u32,
+        }
+
+        let custom = CustomMeta {
+            name: "roads".into(),
+            generator: "custom-tiler 2.1".into(),
+            layer_count: 3,
+        };
+        let json = serde_json::to_string(&custom).unwrap();
+        let data = archive_with_metadata(json.as_bytes());
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(reader.get_metadata_as::<CustomMeta>().unwrap(), custom);
+        // the standard fields are still there, the others are dropped
+        assert_eq!(reader.get_metadata().name, "roads");
+        let value: serde_json::Value = reader.get_metadata_as().unwrap();
+        assert_eq!(value["generator"], "custom-tiler 2.1");
+
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        let metadata: Metadata = reader.get_metadata_as().unwrap();
+        assert_eq!(&metadata, reader.get_metadata());
+        assert!(reader.get_metadata_as::<CustomMeta>().is_err());
+    }
+
+    #[test]
+    fn test_metadata_cache() {
+        let mut reader = PMTilesReader::new(
+            LocalManager::new(archive_with_metadata(br#"{"name":"v1"}"#)),
+            None,
+        );
+        assert_eq!(reader.get_metadata().name, "v1");
+        // the archive is replaced by a new version, with a longer metadata
+        *reader.inner_manager_mut() =
+            LocalManager::new(archive_with_metadata(br#"{"name":"version 2"}"#));
+        assert_eq!(reader.get_metadata().name, "v1");
+        reader.clear_metadata_cache();
+        assert_eq!(reader.get_metadata().name, "version 2");
+        assert_eq!(reader.get_metadata_raw(), br#"{"name":"version 2"}"#);
+
+        // unreadable metadata keeps the cached one
+        *reader.inner_manager_mut() = LocalManager::new(b"not an archive".to_vec());
+        reader.clear_metadata_cache();
+        assert_eq!(reader.get_metadata().name, "version 2");
+
+        // expired metadata is read again
+        let ttl = std::time::Duration::from_millis(50);
+        let mut reader = PMTilesReader::new(
+            LocalManager::new(archive_with_metadata(br#"{"name":"v1"}"#)),
+            None,
+        )
+        .with_metadata_cache_ttl(ttl);
+        assert_eq!(reader.get_metadata().name, "v1");
+        *reader.inner_manager_mut() = LocalManager::new(archive_with_metadata(br#"{"name":"v2"}"#));
+        assert_eq!(reader.get_metadata().name, "v1");
+        let value: serde_json::Value = reader.get_metadata_as().unwrap();
+        assert_eq!(value["name"], "v1");
+        std::thread::sleep(ttl * 2);
+        assert_eq!(reader.get_metadata().name, "v2");
+        *reader.inner_manager_mut() = LocalManager::new(archive_with_metadata(br#"{"name":"v3"}"#));
+        assert_eq!(reader.get_metadata().name, "v2");
+        std::thread::sleep(ttl * 2);
+        let value: serde_json::Value = reader.get_metadata_as().unwrap();
+        assert_eq!(value["name"], "v3");
+        assert_eq!(reader.get_metadata().name, "v3");
+    }
+
+    #[test]
+    fn test_metadata_raw() {
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        let raw = reader.get_metadata_raw();
+        let parsed: Metadata = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(&parsed, reader.get_metadata());
+        assert_eq!(reader.get_metadata_str().unwrap().as_bytes(), raw);
+
+        // metadata that is not a `Metadata` is still readable
+        let data = archive_with_metadata(b"[1, 2, 3]");
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(reader.get_metadata_raw(), b"[1, 2, 3]");
+        assert_eq!(reader.metadata, None);
+        assert_eq!(reader.get_metadata_str(), Ok("[1, 2, 3]".into()));
+        assert_eq!(reader.get_metadata(), &Metadata::default());
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), None);
+
+        // invalid UTF-8
+        let data = archive_with_metadata(&[0xff, 0xfe, 0xfd]);
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(reader.get_metadata_raw(), vec![0xff, 0xfe, 0xfd]);
+        assert_eq!(reader.get_metadata_str(), Err(ReadError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_magic() {
+        let file_manager = FileManager::new("./test/fixtures/s2.s2pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(reader.is_s2(), Ok(true));
+        assert_eq!(reader.format_version(), Ok(1));
+        assert_eq!(reader.header, None);
+
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_2.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(reader.is_s2(), Ok(false));
+        assert_eq!(reader.format_version(), Ok(3));
+        assert_eq!(reader.magic, Some((false, 3)));
+
+        // too short to hold the magic bytes and version
+        let mut reader = PMTilesReader::new(LocalManager::new(b"PMTiles".to_vec()), None);
+        assert_eq!(reader.is_s2(), Err(ReadError::Truncated));
+        assert_eq!(reader.format_version(), Err(ReadError::Truncated));
+        let mut reader = PMTilesReader::new(LocalManager::new(Vec::new()), None);
+        assert_eq!(reader.is_s2(), Err(ReadError::Truncated));
+    }
+
+    #[test]
+    fn test_static_reader() {
+        static FIXTURE: &[u8] = include_bytes!("../test/fixtures/test_fixture_1.pmtiles");
+        let mut reader = PMTilesReaderStatic::new(EmbeddedManager::new(FIXTURE));
+        let header = reader.get_header();
+        assert!(!header.is_s2);
+        assert_eq!(header.tile_type, TileType::Pbf);
+        let metadata: Metadata = serde_json::from_slice(reader.get_metadata()).unwrap();
+        assert_eq!(metadata.name, "test_fixture_1.pmtiles");
+        let tile = reader.get_tile_zxy(0, 0, 0).unwrap();
+        let mut boxed_reader = PMTilesReader::new(EmbeddedManager::new(FIXTURE), None);
+        assert_eq!(tile, boxed_reader.get_tile_zxy(0, 0, 0).unwrap());
+        assert_eq!(reader.get_tile_zxy(1, 0, 0), None);
+
+        // an S2 archive with leaf directories on face 3
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer
+            .write_tile_s2(Face::Face0, 0, 0, 0, b"face-0")
+            .unwrap();
+        for zoom in 0..8 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let tmp_str = format!("{}-{}-{}", zoom, x, y);
+                    writer
+                        .write_tile_s2(Face::Face3, zoom, x, y, tmp_str.as_bytes())
+                        .unwrap();
+                }
+            }
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let data: &'static [u8] = writer.take().leak();
+        let mut reader = PMTilesReaderStatic::new(EmbeddedManager::new(data));
+        let header = reader.get_header();
+        assert!(header.is_s2);
+        assert!(header.leaf_directory_length3 > 0);
+        assert_eq!(reader.get_metadata().len(), 417);
+        assert_eq!(reader.get_tile_s2(Face::Face0, 0, 0, 0).unwrap(), b"face-0");
+        assert_eq!(reader.get_tile_s2(Face::Face1, 0, 0, 0), None);
+        assert_eq!(
+            reader.get_tile_s2(Face::Face3, 6, 22, 45).unwrap(),
+            b"6-22-45"
+        );
+        assert_eq!(
+            reader.get_tile_s2(Face::Face3, 7, 100, 3).unwrap(),
+            b"7-100-3"
+        );
+        // enough lookups to cycle through the directory cache
+        for x in (0..128).step_by(7) {
+            for y in (0..128).step_by(5) {
+                let tmp_str = format!("7-{}-{}", x, y);
+                let tile = reader.get_tile_s2(Face::Face3, 7, x, y).unwrap();
+                assert_eq!(tile, tmp_str.as_bytes());
+            }
+        }
+        assert!(reader.dir_cache.iter().all(|dir| dir.is_some()));
+    }
+
+    #[test]
+    fn test_lazy_root() {
+        /// records the length of every range requested
+        #[derive(Debug)]
+        struct RecordingManager(LocalManager, Arc<Mutex<Vec<u64>>>);
+        impl DataManager for RecordingManager {
+            fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+                self.1.lock().unwrap().push(length);
+                self.0.get_range(offset, length)
+            }
+        }
+
+        // WM and S2 archives, with and without leaf directories
+        let mut wm_leaves = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for id in 0..Tile::global_tile_id_range(7).end {
+            wm_leaves
+                .write_tile(id, format!("tile {id}").as_bytes(), None)
+                .unwrap();
+        }
+        wm_leaves.commit(&Metadata::default()).unwrap();
+        let mut s2 = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for face in FACES {
+            s2.write_tile_s2(face, 0, 0, 0, format!("face {face:?}").as_bytes())
+                .unwrap();
+            s2.write_tile_s2(face, 3, 2, 5, b"deeper").unwrap();
+        }
+        s2.commit(&Metadata {
+            name: "six faces".into(),
+            ..Default::default()
+        })
+        .unwrap();
+        let archives = [
+            std::fs::read("./test/fixtures/test_fixture_1.pmtiles").unwrap(),
+            std::fs::read("./test/fixtures/test_fixture_2.pmtiles").unwrap(),
+            wm_leaves.take(),
+            s2.take(),
+        ];
+
+        for data in archives {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let manager = RecordingManager(LocalManager::new(data.clone()), requests.clone());
+            let mut lazy = PMTilesReader::new(manager, None).with_lazy_root();
+            let mut eager = PMTilesReader::new(LocalManager::new(data), None);
+
+            let header = lazy.get_header();
+            assert_eq!(header, eager.get_header());
+            let root_length: u64 = FACES
+                .iter()
+                .take(if header.is_s2 { 6 } else { 1 })
+                .map(|f| header.get_root_length(*f))
+                .sum();
+            assert_eq!(
+                *requests.lock().unwrap(),
+                vec![
+                    S2_HEADER_SIZE_BYTES as u64,
+                    root_length,
+                    header.metadata_length
+                ]
+            );
+            assert_eq!(lazy.root_dir, eager.root_dir);
+            assert_eq!(lazy.root_dir_s2, eager.root_dir_s2);
+            assert_eq!(lazy.get_metadata_raw(), eager.get_metadata_raw());
+            assert_eq!(lazy.get_metadata(), eager.get_metadata());
+            let stores: Vec<Option<Face>> = if header.is_s2 {
+                FACES.into_iter().map(Some).collect()
+            } else {
+                vec![None]
+            };
+            for face in stores {
+                let entries = eager.get_entries(face).unwrap();
+                assert_eq!(lazy.get_entries(face).unwrap(), entries);
+                for entry in entries.iter().step_by(7) {
+                    let tile = Tile::from_id(entry.tile_id);
+                    assert_eq!(
+                        lazy.get_tile(face, tile.zoom, tile.x, tile.y),
+                        eager.get_tile(face, tile.zoom, tile.x, tile.y)
+                    );
+                }
+            }
+        }
+    }
+
+    /// A data manager whose data can be replaced while a reader owns it
+    #[derive(Debug)]
+    struct SharedManager(Arc<Mutex<LocalManager>>);
+    impl DataManager for SharedManager {
+        fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+            self.0.lock().unwrap().get_range(offset, length)
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let build = |zoom: u8, data: &[u8]| {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            writer.write_tile_xyz(zoom, 0, 0, data).unwrap();
+            writer.commit(&Metadata::default()).unwrap();
+            writer.take()
+        };
+        let manager = Arc::new(Mutex::new(LocalManager::new(build(0, b"first"))));
+        let mut reader = PMTilesReader::new(SharedManager(manager.clone()), None);
+        assert_eq!(reader.get_header().max_zoom, 0);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"first");
+
+        // replace the archive, the cached header is still used
+        *manager.lock().unwrap() = LocalManager::new(build(3, b"second archive"));
+        assert_eq!(reader.get_header().max_zoom, 0);
+
+        reader.reset();
+        let header = reader.get_header();
+        assert_eq!((header.min_zoom, header.max_zoom), (3, 3));
+        assert_eq!(header.data_length, 14);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), None);
+        assert_eq!(reader.get_tile_zxy(3, 0, 0).unwrap(), b"second archive");
+    }
+
+    #[test]
+    fn test_get_tile_decompressed() {
+        use flate2::write::{GzEncoder, ZlibEncoder};
+        use std::io::Write;
+
+        // internal and tile compression are both gzip
+        let file_manager = FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap();
+        let mut reader = PMTilesReader::new(file_manager, None);
+        assert_eq!(reader.get_header().tile_compression, Compression::Gzip);
+        let tile = reader.get_tile_zxy(0, 0, 0);
+        assert!(tile.is_some());
+        assert_eq!(reader.get_tile_decompressed(0, 0, 0), tile);
+        assert_eq!(reader.get_tile_decompressed(1, 0, 0), None);
+
+        let original = b"hello world hello world hello world".to_vec();
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&original).unwrap();
+        let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(&original).unwrap();
+        let compressed = [
+            (Compression::None, original.clone()),
+            (Compression::Gzip, gzip.finish().unwrap()),
+            (Compression::Zlib, zlib.finish().unwrap()),
+        ];
+        for (compression, data) in compressed {
+            // the writer never compresses the directories, so `get_tile` returns the stored data.
+            // zlib can not be written, so its archive is written as gzip and the header patched
+            let written = match compression {
+                Compression::Zlib => Compression::Gzip,
+                _ => compression,
+            };
+            let mut writer = PMTilesWriter::new(written, Box::new(LocalWriter::new()));
+            writer.write_tile_xyz(0, 0, 0, &data).unwrap();
+            writer.write_tile_xyz(1, 0, 0, &data).unwrap();
+            writer.commit(&Metadata::default()).unwrap();
+            let mut archive = writer.take();
+            archive[98] = compression.into();
+            let mut reader = PMTilesReader::new(LocalManager::new(archive), None);
+            assert_eq!(reader.get_header().tile_compression, compression);
+            assert_eq!(reader.get_tile_zxy(0, 0, 0), Some(data));
+            assert_eq!(
+                reader.get_tile_decompressed(0, 0, 0),
+                Some(original.clone())
+            );
+            // deduplicated tiles share a cache entry
+            assert_eq!(reader.tile_cache.len(), 1);
+            assert_eq!(
+                reader.get_tile_decompressed(1, 0, 0),
+                Some(original.clone())
+            );
+            assert_eq!(reader.tile_cache.len(), 1);
+        }
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for i in 0..4u8 {
+            writer
+                .write_tile_s2(Face::Face1, 1, (i % 2) as u64, (i / 2) as u64, &[i])
+                .unwrap();
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        reader.set_tile_cache_size(2);
+        for i in 0..4u8 {
+            let (x, y) = ((i % 2) as u64, (i / 2) as u64);
+            assert_eq!(
+                reader.get_tile_s2_decompressed(Face::Face1, 1, x, y),
+                Some(vec![i])
+            );
+            assert_eq!(
+                reader.get_tile_s2_decompressed(Face::Face1, 1, x, y),
+                reader.get_tile_s2(Face::Face1, 1, x, y)
+            );
+        }
+        assert_eq!(reader.tile_cache.len(), 2);
+        assert_eq!(reader.try_clone().unwrap().tile_cache.max_size(), 2);
+        reader.set_tile_cache_size(0);
+        assert!(reader.tile_cache.is_empty());
+        assert_eq!(
+            reader.get_tile_s2_decompressed(Face::Face1, 1, 0, 0),
+            Some(vec![0])
+        );
+        reader.reset();
+        assert!(reader.tile_cache.is_empty());
+
+        // compressions that can't be decompressed, and corrupt data, are not tiles
+        let mut writer = PMTilesWriter::new(Compression::Gzip, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"not gzip").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let archive = writer.take();
+        let mut reader = PMTilesReader::new(LocalManager::new(archive.clone()), None);
+        assert_eq!(reader.get_tile_decompressed(0, 0, 0), None);
+        for compression in [Compression::Brotli, Compression::Zstd, Compression::Unknown] {
+            let mut archive = archive.clone();
+            archive[98] = compression.into();
+            let mut reader = PMTilesReader::new(LocalManager::new(archive), None);
+            assert_eq!(reader.get_header().tile_compression, compression);
+            assert_eq!(reader.get_tile_zxy(0, 0, 0), Some(b"not gzip".to_vec()));
+            assert_eq!(reader.get_tile_decompressed(0, 0, 0), None);
+            assert!(reader.tile_cache.is_empty());
+        }
+    }
+
     #[test]
     fn decompress_test() {
         let data = vec![0, 1, 2, 3, 4];
@@ -464,10 +2694,43 @@ mod tests {
         assert_eq!(decompressed, data);
     }
 
+    #[test]
+    fn decompress_zlib_test() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let data = b"hello world hello world".to_vec();
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress(&compressed, Compression::Zlib), data);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn decompress_lz4_test() {
+        use lz4_flex::frame::FrameEncoder;
+        use std::io::Write;
+
+        let data = b"hello world hello world".to_vec();
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress(&compressed, Compression::Lz4), data);
+    }
+
     #[test]
     #[should_panic(expected = "Decompression error")]
     fn decompress_test_panic() {
         let data = vec![0, 1, 2, 3, 4];
         let _ = decompress(&data, Compression::Brotli);
     }
+
+    #[test]
+    fn try_decompress_test() {
+        let data = vec![0, 1, 2, 3, 4];
+        assert_eq!(try_decompress(&data, Compression::None), Some(data.clone()));
+        assert_eq!(try_decompress(&data, Compression::Gzip), None);
+        assert_eq!(try_decompress(&data, Compression::Zstd), None);
+    }
 }
@@ -4,7 +4,15 @@ extern crate alloc;
 use s2_tilejson::Face;
 
 use crate::buffer::Buffer;
-use crate::pmtiles::{Compression, Directory, TileType};
+use crate::pmtiles::{
+    compression_from_name, format_bytes, parse_key_values, set, tile_type_from_name,
+    write_key_values, Compression, Directory, DirectoryError, Header, HeaderParseError, TileType,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
 
 /// Store entries for each Face
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -58,10 +66,72 @@ impl S2Entries {
             Face::Face5 => self.face_5 = dir,
         }
     }
+
+    /// Merge two S2Entries face by face, see `Directory::merge`
+    pub fn merge(mut self, other: S2Entries) -> S2Entries {
+        self.merge_mut(other);
+        self
+    }
+
+    /// Merge another S2Entries into this one face by face, see `Directory::merge`
+    pub fn merge_mut(&mut self, other: S2Entries) {
+        for (face, dir) in FACES.into_iter().zip(other.into_dirs()) {
+            let merged = core::mem::take(self.get_mut(face)).merge(dir);
+            self.set_dir(face, merged);
+        }
+    }
+
+    /// Sort the entries of every face by tile ID
+    pub fn sort_all(&mut self) {
+        for face in FACES {
+            self.get_mut(face).sort();
+        }
+    }
+
+    /// Validate the directory of every face, see `Directory::validate`.
+    /// Returns the problems found, grouped by face
+    pub fn validate_all(&self) -> Result<(), Vec<(Face, Vec<DirectoryError>)>> {
+        let errors: Vec<_> = FACES
+            .into_iter()
+            .filter_map(|face| self.get(face).validate().err().map(|e| (face, e)))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The directories of each face, in face order
+    fn into_dirs(self) -> [Directory; 6] {
+        [
+            self.face_0,
+            self.face_1,
+            self.face_2,
+            self.face_3,
+            self.face_4,
+            self.face_5,
+        ]
+    }
 }
 
+/// All six S2 faces in order
+const FACES: [Face; 6] = [
+    Face::Face0,
+    Face::Face1,
+    Face::Face2,
+    Face::Face3,
+    Face::Face4,
+    Face::Face5,
+];
+
 /// The S2PMTiles v1 header size in bytes
 pub const S2_HEADER_SIZE_BYTES: usize = 262;
+/// The offset of the face 5 leaf directory length, the last field of the S2 header
+const LEAF_DIRECTORY_LENGTH5_OFFSET: usize = 254;
+// `S2Header::to_bytes` must fill exactly `S2_HEADER_SIZE_BYTES`
+const _: () =
+    assert!(LEAF_DIRECTORY_LENGTH5_OFFSET + core::mem::size_of::<u64>() == S2_HEADER_SIZE_BYTES);
 /// The S2PMTiles v1 root directory size in bytes
 pub const S2_ROOT_SIZE: usize = 98_304;
 
@@ -163,6 +233,11 @@ pub struct S2Header {
     pub leaf_directory_length5: u64,
 }
 impl S2Header {
+    /// true if the bytes start with the S2PMTiles magic bytes "S2"
+    pub fn magic_bytes_valid(bytes: &[u8]) -> bool {
+        bytes.starts_with(&[0x53, 0x32])
+    }
+
     /// Convert a buffer into a S2Header
     pub fn from_bytes(buffer: &mut Buffer) -> S2Header {
         let ess = buffer.get_u8_at(0);
@@ -238,7 +313,44 @@ impl S2Header {
             leaf_directory_offset4: if is_s2 { buffer.get_u64_at(230) } else { 0 },
             leaf_directory_length4: if is_s2 { buffer.get_u64_at(238) } else { 0 },
             leaf_directory_offset5: if is_s2 { buffer.get_u64_at(246) } else { 0 },
-            leaf_directory_length5: if is_s2 { buffer.get_u64_at(254) } else { 0 },
+            leaf_directory_length5: if is_s2 {
+                buffer.get_u64_at(LEAF_DIRECTORY_LENGTH5_OFFSET)
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Convert a PMTiles v3 (WM) header into a S2Header with no face 1-5 directories
+    pub fn from_header(header: &Header) -> S2Header {
+        S2Header {
+            is_s2: false,
+            version: header.version,
+            root_directory_offset: header.root_directory_offset,
+            root_directory_length: header.root_directory_length,
+            metadata_offset: header.metadata_offset,
+            metadata_length: header.metadata_length,
+            leaf_directory_offset: header.leaf_directory_offset,
+            leaf_directory_length: header.leaf_directory_length,
+            data_offset: header.data_offset,
+            data_length: header.data_length,
+            n_addressed_tiles: header.n_addressed_tiles,
+            n_tile_entries: header.n_tile_entries,
+            n_tile_contents: header.n_tile_contents,
+            clustered: header.clustered,
+            internal_compression: header.internal_compression,
+            tile_compression: header.tile_compression,
+            tile_type: header.tile_type,
+            min_zoom: header.min_zoom,
+            max_zoom: header.max_zoom,
+            min_longitude: header.min_longitude,
+            min_latitude: header.min_latitude,
+            max_longitude: header.max_longitude,
+            max_latitude: header.max_latitude,
+            center_zoom: header.center_zoom,
+            center_longitude: header.center_longitude,
+            center_latitude: header.center_latitude,
+            ..Default::default()
         }
     }
 
@@ -303,7 +415,7 @@ impl S2Header {
         buffer.set_u64_at(230, self.leaf_directory_offset4);
         buffer.set_u64_at(238, self.leaf_directory_length4);
         buffer.set_u64_at(246, self.leaf_directory_offset5);
-        buffer.set_u64_at(254, self.leaf_directory_length5);
+        buffer.set_u64_at(LEAF_DIRECTORY_LENGTH5_OFFSET, self.leaf_directory_length5);
 
         buffer
     }
@@ -331,6 +443,338 @@ impl S2Header {
             Face::Face5 => self.root_directory_length5,
         }
     }
+
+    /// Get the leaf directory offset for a given face
+    pub fn get_leaf_offset(&self, face: Face) -> u64 {
+        match face {
+            Face::Face0 => self.leaf_directory_offset,
+            Face::Face1 => self.leaf_directory_offset1,
+            Face::Face2 => self.leaf_directory_offset2,
+            Face::Face3 => self.leaf_directory_offset3,
+            Face::Face4 => self.leaf_directory_offset4,
+            Face::Face5 => self.leaf_directory_offset5,
+        }
+    }
+
+    /// Get the leaf directory length for a given face
+    pub fn get_leaf_length(&self, face: Face) -> u64 {
+        match face {
+            Face::Face0 => self.leaf_directory_length,
+            Face::Face1 => self.leaf_directory_length1,
+            Face::Face2 => self.leaf_directory_length2,
+            Face::Face3 => self.leaf_directory_length3,
+            Face::Face4 => self.leaf_directory_length4,
+            Face::Face5 => self.leaf_directory_length5,
+        }
+    }
+
+    /// The combined length of the root directories of all faces
+    pub fn total_root_length(&self) -> u64 {
+        self.root_directory_length
+            + self.root_directory_length1
+            + self.root_directory_length2
+            + self.root_directory_length3
+            + self.root_directory_length4
+            + self.root_directory_length5
+    }
+
+    /// The combined length of the leaf directories of all faces
+    pub fn total_leaf_length(&self) -> u64 {
+        self.leaf_directory_length
+            + self.leaf_directory_length1
+            + self.leaf_directory_length2
+            + self.leaf_directory_length3
+            + self.leaf_directory_length4
+            + self.leaf_directory_length5
+    }
+
+    /// The size of the archive, where its last section (root or leaf directories, metadata or
+    /// tile data) ends. Sections may be stored in any order, with gaps between them
+    pub fn archive_length(&self) -> u64 {
+        FACES
+            .iter()
+            .flat_map(|face| {
+                [
+                    self.get_root_offset(*face) + self.get_root_length(*face),
+                    self.get_leaf_offset(*face) + self.get_leaf_length(*face),
+                ]
+            })
+            .chain([
+                self.metadata_offset + self.metadata_length,
+                self.data_offset + self.data_length,
+            ])
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The name and version of the archive format
+    fn format_name(&self) -> String {
+        if self.is_s2 {
+            format!("S2PMTiles v{}", self.version)
+        } else {
+            format!("PMTiles v{}", self.version)
+        }
+    }
+
+    /// A single line summary of the archive
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} z{}..z{} {} tiles {}",
+            self.format_name(),
+            String::from(self.tile_type),
+            self.min_zoom,
+            self.max_zoom,
+            self.n_addressed_tiles,
+            format_bytes(self.data_length)
+        )
+    }
+}
+impl fmt::Display for S2Header {
+    /// A human-readable summary, or with `{:#}` one "key=value" line per field, see `FromStr`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write_key_values(
+                f,
+                &[
+                    ("is_s2", self.is_s2.to_string()),
+                    ("version", self.version.to_string()),
+                    (
+                        "root_directory_offset",
+                        self.root_directory_offset.to_string(),
+                    ),
+                    (
+                        "root_directory_length",
+                        self.root_directory_length.to_string(),
+                    ),
+                    ("metadata_offset", self.metadata_offset.to_string()),
+                    ("metadata_length", self.metadata_length.to_string()),
+                    (
+                        "leaf_directory_offset",
+                        self.leaf_directory_offset.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length",
+                        self.leaf_directory_length.to_string(),
+                    ),
+                    ("data_offset", self.data_offset.to_string()),
+                    ("data_length", self.data_length.to_string()),
+                    ("n_addressed_tiles", self.n_addressed_tiles.to_string()),
+                    ("n_tile_entries", self.n_tile_entries.to_string()),
+                    ("n_tile_contents", self.n_tile_contents.to_string()),
+                    ("clustered", self.clustered.to_string()),
+                    ("internal_compression", self.internal_compression.into()),
+                    ("tile_compression", self.tile_compression.into()),
+                    ("tile_type", self.tile_type.into()),
+                    ("min_zoom", self.min_zoom.to_string()),
+                    ("max_zoom", self.max_zoom.to_string()),
+                    ("min_longitude", self.min_longitude.to_string()),
+                    ("min_latitude", self.min_latitude.to_string()),
+                    ("max_longitude", self.max_longitude.to_string()),
+                    ("max_latitude", self.max_latitude.to_string()),
+                    ("center_zoom", self.center_zoom.to_string()),
+                    ("center_longitude", self.center_longitude.to_string()),
+                    ("center_latitude", self.center_latitude.to_string()),
+                    (
+                        "root_directory_offset1",
+                        self.root_directory_offset1.to_string(),
+                    ),
+                    (
+                        "root_directory_length1",
+                        self.root_directory_length1.to_string(),
+                    ),
+                    (
+                        "root_directory_offset2",
+                        self.root_directory_offset2.to_string(),
+                    ),
+                    (
+                        "root_directory_length2",
+                        self.root_directory_length2.to_string(),
+                    ),
+                    (
+                        "root_directory_offset3",
+                        self.root_directory_offset3.to_string(),
+                    ),
+                    (
+                        "root_directory_length3",
+                        self.root_directory_length3.to_string(),
+                    ),
+                    (
+                        "root_directory_offset4",
+                        self.root_directory_offset4.to_string(),
+                    ),
+                    (
+                        "root_directory_length4",
+                        self.root_directory_length4.to_string(),
+                    ),
+                    (
+                        "root_directory_offset5",
+                        self.root_directory_offset5.to_string(),
+                    ),
+                    (
+                        "root_directory_length5",
+                        self.root_directory_length5.to_string(),
+                    ),
+                    (
+                        "leaf_directory_offset1",
+                        self.leaf_directory_offset1.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length1",
+                        self.leaf_directory_length1.to_string(),
+                    ),
+                    (
+                        "leaf_directory_offset2",
+                        self.leaf_directory_offset2.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length2",
+                        self.leaf_directory_length2.to_string(),
+                    ),
+                    (
+                        "leaf_directory_offset3",
+                        self.leaf_directory_offset3.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length3",
+                        self.leaf_directory_length3.to_string(),
+                    ),
+                    (
+                        "leaf_directory_offset4",
+                        self.leaf_directory_offset4.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length4",
+                        self.leaf_directory_length4.to_string(),
+                    ),
+                    (
+                        "leaf_directory_offset5",
+                        self.leaf_directory_offset5.to_string(),
+                    ),
+                    (
+                        "leaf_directory_length5",
+                        self.leaf_directory_length5.to_string(),
+                    ),
+                ],
+            );
+        }
+        writeln!(f, "{}", self.format_name())?;
+        writeln!(
+            f,
+            "tile type: {}, tile compression: {}, internal compression: {}",
+            String::from(self.tile_type),
+            String::from(self.tile_compression),
+            String::from(self.internal_compression)
+        )?;
+        writeln!(f, "zooms: z{}..z{}", self.min_zoom, self.max_zoom)?;
+        // S2 archives do not store bounds or a center
+        if !self.is_s2 {
+            writeln!(
+                f,
+                "bounds: [{:.7}, {:.7}, {:.7}, {:.7}]",
+                self.min_longitude, self.min_latitude, self.max_longitude, self.max_latitude
+            )?;
+            writeln!(
+                f,
+                "center: [{:.7}, {:.7}] z{}",
+                self.center_longitude, self.center_latitude, self.center_zoom
+            )?;
+        }
+        writeln!(
+            f,
+            "root directories: {}, metadata: {}, leaf directories: {}, tile data: {}",
+            format_bytes(self.total_root_length()),
+            format_bytes(self.metadata_length),
+            format_bytes(self.total_leaf_length()),
+            format_bytes(self.data_length)
+        )?;
+        if self.is_s2 {
+            writeln!(
+                f,
+                "face | root offset | root length | leaf offset | leaf length"
+            )?;
+            for (i, face) in FACES.into_iter().enumerate() {
+                writeln!(
+                    f,
+                    "{:>4} | {:>11} | {:>11} | {:>11} | {:>11}",
+                    i,
+                    self.get_root_offset(face),
+                    self.get_root_length(face),
+                    self.get_leaf_offset(face),
+                    self.get_leaf_length(face)
+                )?;
+            }
+        }
+        write!(
+            f,
+            "tiles: {} addressed, {} entries, {} contents, clustered: {}",
+            self.n_addressed_tiles, self.n_tile_entries, self.n_tile_contents, self.clustered
+        )
+    }
+}
+
+impl FromStr for S2Header {
+    type Err = HeaderParseError;
+
+    /// Parse a header from "key=value" lines, as written by `format!("{header:#}")`.
+    /// Blank lines and lines starting with '#' are skipped, missing keys keep their default
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut h = S2Header::default();
+        parse_key_values(s, |key, v| {
+            Some(match key {
+                "is_s2" => set(v.parse().ok(), &mut h.is_s2),
+                "version" => set(v.parse().ok(), &mut h.version),
+                "root_directory_offset" => set(v.parse().ok(), &mut h.root_directory_offset),
+                "root_directory_length" => set(v.parse().ok(), &mut h.root_directory_length),
+                "metadata_offset" => set(v.parse().ok(), &mut h.metadata_offset),
+                "metadata_length" => set(v.parse().ok(), &mut h.metadata_length),
+                "leaf_directory_offset" => set(v.parse().ok(), &mut h.leaf_directory_offset),
+                "leaf_directory_length" => set(v.parse().ok(), &mut h.leaf_directory_length),
+                "data_offset" => set(v.parse().ok(), &mut h.data_offset),
+                "data_length" => set(v.parse().ok(), &mut h.data_length),
+                "n_addressed_tiles" => set(v.parse().ok(), &mut h.n_addressed_tiles),
+                "n_tile_entries" => set(v.parse().ok(), &mut h.n_tile_entries),
+                "n_tile_contents" => set(v.parse().ok(), &mut h.n_tile_contents),
+                "clustered" => set(v.parse().ok(), &mut h.clustered),
+                "internal_compression" => {
+                    set(compression_from_name(v), &mut h.internal_compression)
+                }
+                "tile_compression" => set(compression_from_name(v), &mut h.tile_compression),
+                "tile_type" => set(tile_type_from_name(v), &mut h.tile_type),
+                "min_zoom" => set(v.parse().ok(), &mut h.min_zoom),
+                "max_zoom" => set(v.parse().ok(), &mut h.max_zoom),
+                "min_longitude" => set(v.parse().ok(), &mut h.min_longitude),
+                "min_latitude" => set(v.parse().ok(), &mut h.min_latitude),
+                "max_longitude" => set(v.parse().ok(), &mut h.max_longitude),
+                "max_latitude" => set(v.parse().ok(), &mut h.max_latitude),
+                "center_zoom" => set(v.parse().ok(), &mut h.center_zoom),
+                "center_longitude" => set(v.parse().ok(), &mut h.center_longitude),
+                "center_latitude" => set(v.parse().ok(), &mut h.center_latitude),
+                "root_directory_offset1" => set(v.parse().ok(), &mut h.root_directory_offset1),
+                "root_directory_length1" => set(v.parse().ok(), &mut h.root_directory_length1),
+                "root_directory_offset2" => set(v.parse().ok(), &mut h.root_directory_offset2),
+                "root_directory_length2" => set(v.parse().ok(), &mut h.root_directory_length2),
+                "root_directory_offset3" => set(v.parse().ok(), &mut h.root_directory_offset3),
+                "root_directory_length3" => set(v.parse().ok(), &mut h.root_directory_length3),
+                "root_directory_offset4" => set(v.parse().ok(), &mut h.root_directory_offset4),
+                "root_directory_length4" => set(v.parse().ok(), &mut h.root_directory_length4),
+                "root_directory_offset5" => set(v.parse().ok(), &mut h.root_directory_offset5),
+                "root_directory_length5" => set(v.parse().ok(), &mut h.root_directory_length5),
+                "leaf_directory_offset1" => set(v.parse().ok(), &mut h.leaf_directory_offset1),
+                "leaf_directory_length1" => set(v.parse().ok(), &mut h.leaf_directory_length1),
+                "leaf_directory_offset2" => set(v.parse().ok(), &mut h.leaf_directory_offset2),
+                "leaf_directory_length2" => set(v.parse().ok(), &mut h.leaf_directory_length2),
+                "leaf_directory_offset3" => set(v.parse().ok(), &mut h.leaf_directory_offset3),
+                "leaf_directory_length3" => set(v.parse().ok(), &mut h.leaf_directory_length3),
+                "leaf_directory_offset4" => set(v.parse().ok(), &mut h.leaf_directory_offset4),
+                "leaf_directory_length4" => set(v.parse().ok(), &mut h.leaf_directory_length4),
+                "leaf_directory_offset5" => set(v.parse().ok(), &mut h.leaf_directory_offset5),
+                "leaf_directory_length5" => set(v.parse().ok(), &mut h.leaf_directory_length5),
+                _ => return None,
+            })
+        })?;
+
+        Ok(h)
+    }
 }
 
 #[cfg(test)]
@@ -338,6 +782,122 @@ mod tests {
     use super::*;
     use crate::Entry;
 
+    #[test]
+    fn test_magic_bytes_valid() {
+        assert!(S2Header::magic_bytes_valid(b"S2PMTiles"));
+        assert!(S2Header::magic_bytes_valid(&[0x53, 0x32, 0x00]));
+        assert!(!S2Header::magic_bytes_valid(b"PMTiles"));
+        assert!(!S2Header::magic_bytes_valid(b"s2"));
+        assert!(!S2Header::magic_bytes_valid(b"S"));
+        assert!(!S2Header::magic_bytes_valid(&[]));
+    }
+
+    #[test]
+    fn test_from_header() {
+        let header = Header {
+            version: 3,
+            root_directory_offset: 127,
+            root_directory_length: 25,
+            metadata_offset: 152,
+            metadata_length: 247,
+            data_offset: 399,
+            data_length: 69,
+            n_addressed_tiles: 1,
+            n_tile_entries: 1,
+            n_tile_contents: 1,
+            clustered: true,
+            tile_compression: Compression::Gzip,
+            tile_type: TileType::Pbf,
+            max_zoom: 5,
+            min_longitude: -1.5,
+            max_latitude: 2.25,
+            center_zoom: 2,
+            ..Default::default()
+        };
+        let s2_header = S2Header::from_header(&header);
+        assert_eq!(
+            s2_header,
+            S2Header {
+                is_s2: false,
+                version: 3,
+                root_directory_offset: 127,
+                root_directory_length: 25,
+                metadata_offset: 152,
+                metadata_length: 247,
+                data_offset: 399,
+                data_length: 69,
+                n_addressed_tiles: 1,
+                n_tile_entries: 1,
+                n_tile_contents: 1,
+                clustered: true,
+                tile_compression: Compression::Gzip,
+                tile_type: TileType::Pbf,
+                max_zoom: 5,
+                min_longitude: -1.5,
+                max_latitude: 2.25,
+                center_zoom: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_s2_entries_merge() {
+        let a = S2Entries {
+            face_0: Directory::new(vec![Entry::new(3, 0, 10, 1), Entry::new(1, 10, 10, 1)]),
+            face_3: Directory::new(vec![Entry::new(5, 20, 10, 1)]),
+            ..Default::default()
+        };
+        let b = S2Entries {
+            face_0: Directory::new(vec![Entry::new(2, 30, 10, 1), Entry::new(3, 40, 10, 1)]),
+            face_3: Directory::new(vec![Entry::new(5, 50, 10, 1), Entry::new(6, 60, 10, 1)]),
+            face_5: Directory::new(vec![Entry::new(0, 70, 10, 1)]),
+            ..Default::default()
+        };
+
+        // unsorted, duplicates and empty entries are reported per face
+        let mut c = a.clone();
+        c.face_3.insert(Entry::new(5, 80, 0, 1));
+        assert_eq!(
+            c.validate_all(),
+            Err(vec![
+                (Face::Face0, vec![DirectoryError::Unsorted { tile_id: 1 }]),
+                (
+                    Face::Face3,
+                    vec![
+                        DirectoryError::EmptyEntry { tile_id: 5 },
+                        DirectoryError::DuplicateTileId { tile_id: 5 }
+                    ]
+                ),
+            ])
+        );
+        c.sort_all();
+        assert_eq!(
+            c.face_0.entries,
+            vec![Entry::new(1, 10, 10, 1), Entry::new(3, 0, 10, 1)]
+        );
+
+        let mut merged = a.clone();
+        merged.merge_mut(b.clone());
+        assert_eq!(merged, a.merge(b));
+        assert_eq!(merged.validate_all(), Ok(()));
+        // the entries of the second S2Entries win on conflicts
+        assert_eq!(
+            merged.face_0.entries,
+            vec![
+                Entry::new(1, 10, 10, 1),
+                Entry::new(2, 30, 10, 1),
+                Entry::new(3, 40, 10, 1)
+            ]
+        );
+        assert_eq!(
+            merged.face_3.entries,
+            vec![Entry::new(5, 50, 10, 1), Entry::new(6, 60, 10, 1)]
+        );
+        assert!(merged.face_1.is_empty());
+        assert_eq!(merged.face_5.entries, vec![Entry::new(0, 70, 10, 1)]);
+    }
+
     #[test]
     fn test_s2_entries() {
         let mut s2entries = S2Entries {
@@ -460,8 +1020,10 @@ mod tests {
                 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
             ]
         );
+        assert_eq!(bytes.len(), S2_HEADER_SIZE_BYTES);
         let from_bytes = S2Header::from_bytes(&mut Buffer::from(bytes.as_slice()));
         assert_eq!(default_header, from_bytes);
+        assert_eq!(S2Header::default().to_bytes().len(), S2_HEADER_SIZE_BYTES);
 
         // set a complex header:
         let header = S2Header {
@@ -513,6 +1075,7 @@ mod tests {
             leaf_directory_length5: 36,
         };
         let mut bytes = header.to_bytes();
+        assert_eq!(bytes.len(), S2_HEADER_SIZE_BYTES);
         let from_bytes = S2Header::from_bytes(&mut bytes);
         assert_eq!(header, from_bytes);
 
@@ -532,4 +1095,104 @@ mod tests {
         assert_eq!(header.get_root_length(4.into()), 25);
         assert_eq!(header.get_root_length(5.into()), 26);
     }
+
+    #[test]
+    fn test_header_from_str() {
+        let header = S2Header {
+            is_s2: true,
+            version: 1,
+            root_directory_offset: 262,
+            root_directory_length: 98,
+            root_directory_offset4: 360,
+            root_directory_length4: 7,
+            metadata_offset: 367,
+            metadata_length: 12,
+            leaf_directory_offset3: 1_000_000,
+            leaf_directory_length3: 4_096,
+            data_offset: 98_304,
+            data_length: 901_696,
+            n_addressed_tiles: 5,
+            tile_compression: Compression::Zstd,
+            tile_type: TileType::Webp,
+            min_zoom: 1,
+            max_zoom: 9,
+            center_longitude: 0.1,
+            ..Default::default()
+        };
+        let text = format!("{header:#}");
+        assert!(text.starts_with("is_s2=true\nversion=1\nroot_directory_offset=262\n"));
+        assert!(text.contains("\ntile_compression=zstd\ntile_type=webp\n"));
+        assert!(text.ends_with("\nleaf_directory_offset5=0\nleaf_directory_length5=0"));
+        assert_eq!(text.parse::<S2Header>(), Ok(header));
+
+        assert_eq!(
+            "is_s2 = true\n# face 3\nleaf_directory_length3 = 7\n".parse(),
+            Ok(S2Header {
+                is_s2: true,
+                leaf_directory_length3: 7,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            "is_s2=yes".parse::<S2Header>(),
+            Err(HeaderParseError::InvalidValue { line: 1 })
+        );
+        assert_eq!(
+            "version=1\nleaf_directory_offset6=0".parse::<S2Header>(),
+            Err(HeaderParseError::UnknownKey { line: 2 })
+        );
+    }
+
+    #[test]
+    fn test_header_display() {
+        let header = S2Header {
+            is_s2: true,
+            version: 1,
+            root_directory_length: 5,
+            root_directory_length3: 5,
+            metadata_length: 417,
+            leaf_directory_length2: 1_024,
+            data_length: 1_572_864,
+            n_addressed_tiles: 2,
+            n_tile_contents: 1,
+            tile_type: TileType::Png,
+            max_zoom: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            header.to_string(),
+            "S2PMTiles v1\n\
+             tile type: png, tile compression: none, internal compression: none\n\
+             zooms: z0..z2\n\
+             root directories: 10 B, metadata: 417 B, leaf directories: 1.0 KB, tile data: 1.5 \
+             MB\n\
+             face | root offset | root length | leaf offset | leaf length\n   \
+                0 |           0 |           5 |           0 |           0\n   \
+                1 |           0 |           0 |           0 |           0\n   \
+                2 |           0 |           0 |           0 |        1024\n   \
+                3 |           0 |           5 |           0 |           0\n   \
+                4 |           0 |           0 |           0 |           0\n   \
+                5 |           0 |           0 |           0 |           0\n\
+             tiles: 2 addressed, 0 entries, 1 contents, clustered: false"
+        );
+        assert_eq!(header.summary(), "S2PMTiles v1 png z0..z2 2 tiles 1.5 MB");
+        assert_eq!(format!("{header:#}").parse(), Ok(header));
+
+        let header = S2Header {
+            version: 3,
+            min_longitude: -1.5,
+            max_latitude: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            header.to_string(),
+            "PMTiles v3\n\
+             tile type: pbf, tile compression: none, internal compression: none\n\
+             zooms: z0..z0\n\
+             bounds: [-1.5000000, 0.0000000, 0.0000000, 2.0000000]\n\
+             center: [0.0000000, 0.0000000] z0\n\
+             root directories: 0 B, metadata: 0 B, leaf directories: 0 B, tile data: 0 B\n\
+             tiles: 0 addressed, 0 entries, 0 contents, clustered: false"
+        );
+    }
 }
@@ -0,0 +1,748 @@
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use crate::reader::{DataManager, PMTilesReader, ReadError};
+use crate::writer::{CommitResult, LocalWriter, PMTilesWriter, WriteError, WriterWarning};
+use crate::{Compression, Entry, Tile};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+use s2_tilejson::{Face, Metadata};
+
+/// Every place an archive can store tiles: index 0 is the WM directory, 1-6 are the S2 faces
+const STORES: [Option<Face>; 7] = [
+    None,
+    Some(Face::Face0),
+    Some(Face::Face1),
+    Some(Face::Face2),
+    Some(Face::Face3),
+    Some(Face::Face4),
+    Some(Face::Face5),
+];
+
+/// How to resolve a tile that exists in both archives being merged
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum ConflictPolicy {
+    /// keep the tile from the first archive
+    #[default]
+    TakeFirst,
+    /// keep the tile from the second archive
+    TakeSecond,
+    /// stop the merge and report the duplicate tile
+    Error,
+}
+
+/// Why a merge failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// the tile ID found in both archives when using `ConflictPolicy::Error`
+    Conflict(u64),
+    /// the writer refused a tile, e.g. it enforces clustering and already holds later tiles
+    Write(WriteError),
+    /// the writer refused to commit, e.g. both archives were empty
+    Commit(Vec<WriterWarning>),
+    /// a zoom split that is 0, above 30 or not above the previous split
+    InvalidSplit(u8),
+    /// an archive could not be read, e.g. its directories are nested too deep
+    Read(ReadError),
+    /// one archive is S2 and the other is WM, their tiles can not share an archive
+    MixedArchives,
+    /// an archive's tile compression differs from the writer's, tiles are copied as is
+    CompressionMismatch {
+        /// the tile compression of the writer
+        expected: Compression,
+        /// the tile compression of the archive
+        got: Compression,
+    },
+}
+
+/// Merge two archives into a writer and commit it.
+/// Tiles found in both archives are resolved using the `conflict` policy. If the policy is
+/// `ConflictPolicy::Error`, nothing is written and the first duplicate tile ID is returned.
+/// Both archives must be WM or both S2, and their tile compression must be the writer's since
+/// tile data is copied without recompressing it; otherwise nothing is written.
+/// The metadata of `reader1` is used unless `metadata` is provided.
+/// On success the result of committing the writer is returned.
+pub fn merge_pmtiles<A: DataManager, B: DataManager>(
+    reader1: &mut PMTilesReader<A>,
+    reader2: &mut PMTilesReader<B>,
+    writer: &mut PMTilesWriter,
+    conflict: ConflictPolicy,
+    metadata: Option<&Metadata>,
+) -> Result<CommitResult, MergeError> {
+    let (header1, header2) = (reader1.get_header(), reader2.get_header());
+    if header1.is_s2 != header2.is_s2 {
+        return Err(MergeError::MixedArchives);
+    }
+    let expected = writer.compression();
+    for got in [header1.tile_compression, header2.tile_compression] {
+        if got != expected {
+            return Err(MergeError::CompressionMismatch { expected, got });
+        }
+    }
+
+    // (store, is from reader2, run) in tile order, runs cut where the other archive wins
+    let mut runs: Vec<(usize, bool, Entry)> = Vec::new();
+    for store in 0..STORES.len() {
+        let first = store_entries(reader1, store).map_err(MergeError::Read)?;
+        let second = store_entries(reader2, store).map_err(MergeError::Read)?;
+        let (pieces, overlap) = match conflict {
+            ConflictPolicy::TakeSecond => overlay(first, second),
+            _ => {
+                let (pieces, overlap) = overlay(second, first);
+                let pieces = pieces.into_iter().map(|(top, e)| (!top, e)).collect();
+                (pieces, overlap)
+            }
+        };
+        if let (ConflictPolicy::Error, Some(tile_id)) = (conflict, overlap) {
+            return Err(MergeError::Conflict(tile_id));
+        }
+        runs.extend(pieces.into_iter().map(|(second, e)| (store, second, e)));
+    }
+
+    // write in tile order so the output stays clustered, reading the data of each entry once
+    // (the pieces of a cut run follow each other)
+    let (mut first_data, mut second_data) = (None, None);
+    for (store, second, entry) in runs {
+        let data = if second {
+            entry_data(reader2, &entry, &mut second_data)
+        } else {
+            entry_data(reader1, &entry, &mut first_data)
+        };
+        writer
+            .write_tile_run(entry.tile_id, entry.run_length, data, STORES[store])
+            .map_err(MergeError::Write)?;
+    }
+
+    match metadata {
+        Some(metadata) => writer.commit(metadata),
+        None => writer.commit(reader1.get_metadata()),
+    }
+    .map_err(MergeError::Commit)
+}
+
+/// Split an archive into one archive per zoom range.
+/// `splits` is a sorted list of zoom breakpoints, each being the first zoom of a new range, so
+/// `[9, 16]` creates the ranges 0-8, 9-15 and 16 to the max zoom of the archive. Splits that are
+/// 0, above 30, unsorted or duplicated are refused with `MergeError::InvalidSplit`.
+/// Each output is committed with the original metadata and returned with its min and max zoom.
+/// Ranges without any tiles can not be committed and are returned as is.
+pub fn split_pmtiles_by_zoom<M: DataManager>(
+    reader: &mut PMTilesReader<M>,
+    splits: &[u8],
+) -> Result<Vec<(PMTilesWriter, u8, u8)>, MergeError> {
+    // build the zoom ranges
+    let mut ranges: Vec<(u8, u8)> = Vec::new();
+    let mut min_zoom = 0;
+    for split in splits {
+        if *split <= min_zoom || *split > 30 {
+            return Err(MergeError::InvalidSplit(*split));
+        }
+        ranges.push((min_zoom, split - 1));
+        min_zoom = *split;
+    }
+    let compression = reader.get_header().tile_compression;
+    let entries = all_entries(reader).map_err(MergeError::Read)?;
+    let max_zoom = entries
+        .iter()
+        .map(|(_, e)| Tile::from_id(e.tile_id + e.run_length as u64 - 1).zoom)
+        .max()
+        .unwrap_or(0);
+    ranges.push((min_zoom, max_zoom.max(min_zoom)));
+
+    let mut data = None;
+    let mut writers = Vec::with_capacity(ranges.len());
+    for (min_zoom, max_zoom) in ranges {
+        // zooms are stored one after the other, so a zoom range is a tile ID range
+        let ids =
+            Tile::global_tile_id_range(min_zoom).start..Tile::global_tile_id_range(max_zoom).end;
+        let mut writer = PMTilesWriter::new(compression, Box::new(LocalWriter::new()));
+        let mut has_tiles = false;
+        for (store, entry) in &entries {
+            let start = entry.tile_id.max(ids.start);
+            let end = (entry.tile_id + entry.run_length as u64).min(ids.end);
+            if start >= end {
+                continue;
+            }
+            let data = entry_data(reader, entry, &mut data);
+            writer
+                .write_tile_run(start, (end - start) as u32, data, STORES[*store])
+                .map_err(MergeError::Write)?;
+            has_tiles = true;
+        }
+        if has_tiles {
+            writer
+                .commit(reader.get_metadata())
+                .map_err(MergeError::Commit)?;
+        }
+        writers.push((writer, min_zoom, max_zoom));
+    }
+
+    Ok(writers)
+}
+
+/// Extract a single S2 face into a standard WM PMTiles archive.
+/// The tiles of `face` are written to `writer` using their WM tile IDs and committed with the
+/// metadata of the original archive. An empty face is not committed.
+/// Fails if `writer` refuses a tile (e.g. its validator rejects it) or refuses to commit.
+pub fn extract_face<M: DataManager>(
+    reader: &mut PMTilesReader<M>,
+    face: Face,
+    writer: &mut PMTilesWriter,
+) -> Result<CommitResult, MergeError> {
+    for entry in reader.get_entries(Some(face)).map_err(MergeError::Read)? {
+        let data = reader.get_entry_data(&entry);
+        writer
+            .write_tile_run(entry.tile_id, entry.run_length, &data, None)
+            .map_err(MergeError::Write)?;
+    }
+    writer
+        .commit(reader.get_metadata())
+        .map_err(MergeError::Commit)
+}
+
+/// The tile changes found between two archives
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffResult {
+    /// tiles only found in the `after` archive
+    pub new_tiles: Vec<u64>,
+    /// tiles only found in the `before` archive
+    pub removed_tiles: Vec<u64>,
+    /// tiles found in both archives but with different content
+    pub modified_tiles: Vec<u64>,
+}
+
+/// Compare two archives and report which tiles were added, removed or modified.
+/// Tiles are compared using their raw (still tile compressed) bytes. S2 archives are compared
+/// face by face, but the result only stores tile IDs. Runs are compared as a whole and the data
+/// of each pair of entries is read and compared once.
+/// Fails if the directories of either archive are nested deeper than the max depth of its reader
+pub fn diff_pmtiles<A: DataManager, B: DataManager>(
+    before: &mut PMTilesReader<A>,
+    after: &mut PMTilesReader<B>,
+) -> Result<DiffResult, ReadError> {
+    let end = |e: &Entry| e.tile_id + e.run_length as u64;
+    let mut result = DiffResult::default();
+    // ((before offset, after offset), length) -> whether the data differs
+    let mut compared: BTreeMap<((u64, u64), u32), bool> = BTreeMap::new();
+    let mut before_data: Option<(u64, u32, Vec<u8>)> = None;
+    let mut after_data: Option<(u64, u32, Vec<u8>)> = None;
+
+    for store in 0..STORES.len() {
+        let before_runs = store_entries(before, store)?;
+        let after_runs = store_entries(after, store)?;
+        // walk both run lists at once, `pos` being the first tile ID not looked at yet
+        let (mut i, mut j, mut pos) = (0, 0, 0);
+        while i < before_runs.len() || j < after_runs.len() {
+            let b = before_runs.get(i).map(|e| (e.tile_id.max(pos), e));
+            let a = after_runs.get(j).map(|e| (e.tile_id.max(pos), e));
+            pos = match (b, a) {
+                (Some((b_start, b)), Some((a_start, _))) if b_start < a_start => {
+                    let stop = end(b).min(a_start);
+                    result.removed_tiles.extend(b_start..stop);
+                    stop
+                }
+                (Some((b_start, _)), Some((a_start, a))) if a_start < b_start => {
+                    let stop = end(a).min(b_start);
+                    result.new_tiles.extend(a_start..stop);
+                    stop
+                }
+                (Some((start, b)), Some((_, a))) => {
+                    let stop = end(b).min(end(a));
+                    let modified = b.length != a.length
+                        || *compared
+                            .entry(((b.offset, a.offset), b.length))
+                            .or_insert_with(|| {
+                                entry_data(before, b, &mut before_data)
+                                    != entry_data(after, a, &mut after_data)
+                            });
+                    if modified {
+                        result.modified_tiles.extend(start..stop);
+                    }
+                    stop
+                }
+                (Some((start, b)), None) => {
+                    result.removed_tiles.extend(start..end(b));
+                    end(b)
+                }
+                (None, Some((start, a))) => {
+                    result.new_tiles.extend(start..end(a));
+                    end(a)
+                }
+                (None, None) => break,
+            };
+            if before_runs.get(i).is_some_and(|e| end(e) <= pos) {
+                i += 1;
+            }
+            if after_runs.get(j).is_some_and(|e| end(e) <= pos) {
+                j += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// The data of an entry, only read if `last` holds the data of another entry
+fn entry_data<'a, M: DataManager>(
+    reader: &mut PMTilesReader<M>,
+    entry: &Entry,
+    last: &'a mut Option<(u64, u32, Vec<u8>)>,
+) -> &'a [u8] {
+    if !matches!(last, Some((o, l, _)) if (*o, *l) == (entry.offset, entry.length)) {
+        *last = Some((entry.offset, entry.length, reader.get_entry_data(entry)));
+    }
+
+    last.as_ref()
+        .map(|(_, _, data)| data.as_slice())
+        .unwrap_or_default()
+}
+
+/// The entries of one of the `STORES` of an archive, empty if the archive has no such store
+fn store_entries<M: DataManager>(
+    reader: &mut PMTilesReader<M>,
+    store: usize,
+) -> Result<Vec<Entry>, ReadError> {
+    if stores(reader).contains(&store) {
+        reader.get_entries(STORES[store])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Lay the `top` runs over the `base` runs (both sorted by tile ID): base runs are split with
+/// `Entry::split_run` to drop the tiles a top run covers. Returns every run in tile order,
+/// flagged true if it is a top run, and the first tile ID found in both
+fn overlay(base: Vec<Entry>, top: Vec<Entry>) -> (Vec<(bool, Entry)>, Option<u64>) {
+    let end = |e: &Entry| e.tile_id + e.run_length as u64;
+    let mut runs: Vec<(bool, Entry)> = top.iter().map(|e| (true, *e)).collect();
+    let mut overlap = None;
+    let mut next_top = 0;
+    for entry in base {
+        let mut rest = Some(entry);
+        while let Some(mut run) = rest {
+            while next_top < top.len() && end(&top[next_top]) <= run.tile_id {
+                next_top += 1;
+            }
+            let covering = match top.get(next_top) {
+                Some(t) if t.tile_id < end(&run) => *t,
+                _ => {
+                    runs.push((false, run));
+                    break;
+                }
+            };
+            overlap.get_or_insert(run.tile_id.max(covering.tile_id));
+            // keep the tiles before the top run, drop the ones it covers
+            if let Some((head, tail)) = run.split_run(covering.tile_id) {
+                runs.push((false, head));
+                run = tail;
+            }
+            rest = run.split_run(end(&covering)).map(|(_, tail)| tail);
+        }
+    }
+    runs.sort_by_key(|(_, e)| e.tile_id);
+
+    (runs, overlap)
+}
+
+/// Every tile entry of an archive alongside the `STORES` index it was found in
+fn all_entries<M: DataManager>(
+    reader: &mut PMTilesReader<M>,
+) -> Result<Vec<(usize, Entry)>, ReadError> {
+    let mut entries = Vec::new();
+    for store in stores(reader) {
+        for entry in reader.get_entries(STORES[store])? {
+            entries.push((store, entry));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// The range of `STORES` indexes a reader has tiles in
+fn stores<M: DataManager>(reader: &mut PMTilesReader<M>) -> Range<usize> {
+    if reader.get_header().is_s2 {
+        1..7
+    } else {
+        0..1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::LocalManager;
+
+    fn build_archive(tiles: &[(u8, u64, u64, &str)]) -> PMTilesReader<LocalManager> {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for (zoom, x, y, data) in tiles {
+            writer
+                .write_tile_xyz(*zoom, *x, *y, data.as_bytes())
+                .unwrap();
+        }
+        writer.commit(&Metadata::default()).unwrap();
+
+        PMTilesReader::new(LocalManager::new(writer.take()), None)
+    }
+
+    /// counts the range requests made to a local manager
+    #[derive(Debug)]
+    struct CountingManager(LocalManager, usize);
+    impl DataManager for CountingManager {
+        fn get_range(&mut self, offset: u64, length: u64) -> Vec<u8> {
+            self.1 += 1;
+            self.0.get_range(offset, length)
+        }
+    }
+
+    /// a WM archive of (tile ID, run length, data) runs, with its range requests counted
+    /// from after the header and root are read
+    fn run_archive(runs: &[(u64, u32, &str)]) -> PMTilesReader<CountingManager> {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for (tile_id, run_length, data) in runs {
+            writer
+                .write_tile_run(*tile_id, *run_length, data.as_bytes(), None)
+                .unwrap();
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader =
+            PMTilesReader::new(CountingManager(LocalManager::new(writer.take()), 0), None);
+        reader.get_header();
+        reader.inner_manager_mut().1 = 0;
+
+        reader
+    }
+
+    fn merge(
+        conflict: ConflictPolicy,
+    ) -> (
+        Result<CommitResult, MergeError>,
+        PMTilesReader<LocalManager>,
+    ) {
+        let mut reader1 = build_archive(&[(0, 0, 0, "a-0"), (1, 0, 0, "a-1"), (1, 1, 0, "a-2")]);
+        let mut reader2 = build_archive(&[(1, 1, 0, "b-2"), (2, 0, 0, "b-3")]);
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        let res = merge_pmtiles(&mut reader1, &mut reader2, &mut writer, conflict, None);
+        let reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+
+        (res, reader)
+    }
+
+    #[test]
+    fn test_merge_take_first() {
+        let (res, mut reader) = merge(ConflictPolicy::TakeFirst);
+        assert_eq!(res.unwrap().tiles_written, 4);
+        assert_eq!(reader.get_header().n_addressed_tiles, 4);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"a-0");
+        assert_eq!(reader.get_tile_zxy(1, 0, 0).unwrap(), b"a-1");
+        assert_eq!(reader.get_tile_zxy(1, 1, 0).unwrap(), b"a-2");
+        assert_eq!(reader.get_tile_zxy(2, 0, 0).unwrap(), b"b-3");
+    }
+
+    #[test]
+    fn test_merge_take_second() {
+        let (res, mut reader) = merge(ConflictPolicy::TakeSecond);
+        assert_eq!(res.unwrap().tiles_written, 4);
+        assert_eq!(reader.get_header().n_addressed_tiles, 4);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"a-0");
+        assert_eq!(reader.get_tile_zxy(1, 0, 0).unwrap(), b"a-1");
+        assert_eq!(reader.get_tile_zxy(1, 1, 0).unwrap(), b"b-2");
+        assert_eq!(reader.get_tile_zxy(2, 0, 0).unwrap(), b"b-3");
+    }
+
+    #[test]
+    fn test_merge_error() {
+        let (res, _) = merge(ConflictPolicy::Error);
+        assert_eq!(res, Err(MergeError::Conflict(Tile::new(1, 1, 0).to_id())));
+    }
+
+    #[test]
+    fn test_merge_incompatible() {
+        let mut wm = build_archive(&[(0, 0, 0, "wm")]);
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_s2(Face::Face2, 0, 0, 0, b"s2").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut s2 = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let mut writer = PMTilesWriter::new(Compression::Gzip, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(1, 0, 0, b"gzip").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut gzip = PMTilesReader::new(LocalManager::new(writer.take()), None);
+
+        // WM and S2 tiles can not share an archive
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert_eq!(
+            merge_pmtiles(
+                &mut wm,
+                &mut s2,
+                &mut writer,
+                ConflictPolicy::TakeFirst,
+                None
+            ),
+            Err(MergeError::MixedArchives)
+        );
+        // tiles are not recompressed
+        assert_eq!(
+            merge_pmtiles(
+                &mut wm,
+                &mut gzip,
+                &mut writer,
+                ConflictPolicy::TakeFirst,
+                None
+            ),
+            Err(MergeError::CompressionMismatch {
+                expected: Compression::None,
+                got: Compression::Gzip
+            })
+        );
+        let mut writer = PMTilesWriter::new(Compression::Gzip, Box::new(LocalWriter::new()));
+        assert_eq!(
+            merge_pmtiles(
+                &mut wm,
+                &mut gzip,
+                &mut writer,
+                ConflictPolicy::TakeFirst,
+                None
+            ),
+            Err(MergeError::CompressionMismatch {
+                expected: Compression::Gzip,
+                got: Compression::None
+            })
+        );
+        // nothing was written
+        assert_eq!(
+            writer.validate_before_commit().unwrap_err()[0],
+            WriterWarning::NoTilesWritten
+        );
+    }
+
+    #[test]
+    fn test_merge_long_run() {
+        // a run of a million ocean tiles, with land cutting through it
+        let mut ocean = run_archive(&[(0, 1_000_000, "ocean")]);
+        let mut land = run_archive(&[
+            (10, 5, "land"),
+            (500_000, 1, "island"),
+            (999_999, 3, "coast"),
+        ]);
+
+        for (conflict, land_reads) in [
+            (ConflictPolicy::TakeFirst, 1),
+            (ConflictPolicy::TakeSecond, 3),
+        ] {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            let result = merge_pmtiles(&mut ocean, &mut land, &mut writer, conflict, None).unwrap();
+            assert_eq!(result.tiles_written, 1_000_002);
+            // each entry that is written was read once
+            assert_eq!(ocean.inner_manager().1, 1);
+            assert_eq!(land.inner_manager().1, land_reads);
+            ocean.inner_manager_mut().1 = 0;
+            land.inner_manager_mut().1 = 0;
+
+            let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+            let mut tile = |tile_id: u64| {
+                let tile = Tile::from_id(tile_id);
+                reader.get_tile_zxy(tile.zoom, tile.x, tile.y)
+            };
+            let second = |data: &str| match conflict {
+                ConflictPolicy::TakeSecond => Some(data.as_bytes().to_vec()),
+                _ => Some(b"ocean".to_vec()),
+            };
+            assert_eq!(tile(9), Some(b"ocean".to_vec()));
+            assert_eq!(tile(10), second("land"));
+            assert_eq!(tile(14), second("land"));
+            assert_eq!(tile(15), Some(b"ocean".to_vec()));
+            assert_eq!(tile(500_000), second("island"));
+            assert_eq!(tile(999_999), second("coast"));
+            assert_eq!(tile(1_000_000), Some(b"coast".to_vec()));
+            assert_eq!(tile(1_000_001), Some(b"coast".to_vec()));
+            assert_eq!(tile(1_000_002), None);
+        }
+        let mut entries = |conflict| {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            merge_pmtiles(&mut ocean, &mut land, &mut writer, conflict, None).unwrap();
+            let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+            reader.get_entries(None).unwrap()
+        };
+        // runs are kept whole or cut in pieces, never expanded
+        assert_eq!(entries(ConflictPolicy::TakeFirst).len(), 2);
+        assert_eq!(entries(ConflictPolicy::TakeSecond).len(), 6);
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert_eq!(
+            merge_pmtiles(
+                &mut ocean,
+                &mut land,
+                &mut writer,
+                ConflictPolicy::Error,
+                None
+            ),
+            Err(MergeError::Conflict(10))
+        );
+    }
+
+    #[test]
+    fn test_split_by_zoom() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for zoom in 0..8 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let tmp_str = format!("{}-{}-{}", zoom, x, y);
+                    writer
+                        .write_tile_xyz(zoom, x, y, tmp_str.as_bytes())
+                        .unwrap();
+                }
+            }
+        }
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+
+        // duplicated, unsorted, empty first range, past the max zoom
+        for (splits, invalid) in [([4, 4], 4), ([8, 4], 4), ([0, 4], 0), ([4, 31], 31)] {
+            let res = split_pmtiles_by_zoom(&mut reader, &splits);
+            assert_eq!(res.err(), Some(MergeError::InvalidSplit(invalid)));
+        }
+        let mut splits = split_pmtiles_by_zoom(&mut reader, &[4, 8]).unwrap();
+        let ranges: Vec<(u8, u8)> = splits.iter().map(|(_, min, max)| (*min, *max)).collect();
+        assert_eq!(ranges, vec![(0, 3), (4, 7), (8, 8)]);
+        // zoom 8+ has no tiles so it was never committed
+        let (empty, _, _) = splits.pop().unwrap();
+        let warnings = empty.validate_before_commit().unwrap_err();
+        assert!(warnings.contains(&WriterWarning::NoTilesWritten));
+
+        let mut readers: Vec<PMTilesReader<LocalManager>> = splits
+            .into_iter()
+            .map(|(mut w, _, _)| PMTilesReader::new(LocalManager::new(w.take()), None))
+            .collect();
+
+        // zooms 0-3
+        let header = readers[0].get_header();
+        assert_eq!(header.n_addressed_tiles, 1 + 4 + 16 + 64);
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 3));
+        assert_eq!(readers[0].get_tile_zxy(3, 2, 5).unwrap(), b"3-2-5");
+        assert_eq!(readers[0].get_tile_zxy(4, 2, 5), None);
+        // zooms 4-7
+        let header = readers[1].get_header();
+        assert_eq!(header.n_addressed_tiles, 256 + 1_024 + 4_096 + 16_384);
+        assert_eq!((header.min_zoom, header.max_zoom), (4, 7));
+        assert_eq!(readers[1].get_tile_zxy(5, 12, 30).unwrap(), b"5-12-30");
+        assert_eq!(readers[1].get_tile_zxy(3, 2, 5), None);
+    }
+
+    #[test]
+    fn test_extract_face() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer
+            .write_tile_s2(Face::Face0, 0, 0, 0, b"face-0")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 0, 0, 0, b"face-3-0")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 1, 1, 0, b"face-3-1")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 2, 1, 1, b"face-3-2")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face5, 1, 1, 1, b"face-5")
+            .unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+
+        // empty faces are not committed
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert!(matches!(
+            extract_face(&mut reader, Face::Face1, &mut writer),
+            Err(MergeError::Commit(_))
+        ));
+        // a tile refused by the writer is reported
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_validator(Box::new(|_: u64, data: &[u8]| match data {
+                b"face-3-1" => Err("bad tile".to_string()),
+                _ => Ok(()),
+            }));
+        assert_eq!(
+            extract_face(&mut reader, Face::Face3, &mut writer).err(),
+            Some(MergeError::Write(WriteError::ValidationFailed(
+                "bad tile".to_string()
+            )))
+        );
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert_eq!(
+            extract_face(&mut reader, Face::Face3, &mut writer)
+                .unwrap()
+                .tiles_written,
+            3
+        );
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+
+        let header = reader.get_header();
+        assert!(!header.is_s2);
+        assert_eq!(header.n_addressed_tiles, 3);
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 2));
+        assert_eq!(*reader.get_metadata(), Metadata::default());
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"face-3-0");
+        assert_eq!(reader.get_tile_zxy(1, 1, 0).unwrap(), b"face-3-1");
+        assert_eq!(reader.get_tile_zxy(2, 1, 1).unwrap(), b"face-3-2");
+        assert_eq!(reader.get_tile_zxy(1, 1, 1), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        let tiles: Vec<(u8, u64, u64, String)> = (0..16)
+            .map(|pos| {
+                let tile = Tile::from_zoom_pos(2, pos);
+                (2, tile.x, tile.y, format!("tile-{}", pos))
+            })
+            .collect();
+        // archive A has tiles 0-9
+        let before_tiles: Vec<(u8, u64, u64, &str)> = tiles[0..10]
+            .iter()
+            .map(|(z, x, y, d)| (*z, *x, *y, d.as_str()))
+            .collect();
+        // archive B removes tile 0, modifies tiles 1-3 and adds tiles 10-11
+        let mut after_tiles: Vec<(u8, u64, u64, &str)> = tiles[1..12]
+            .iter()
+            .map(|(z, x, y, d)| (*z, *x, *y, d.as_str()))
+            .collect();
+        after_tiles[0].3 = "modified-1";
+        after_tiles[1].3 = "modified-2";
+        after_tiles[2].3 = "modified-3";
+
+        let mut before = build_archive(&before_tiles);
+        let mut after = build_archive(&after_tiles);
+        let diff = diff_pmtiles(&mut before, &mut after).unwrap();
+
+        let id = |pos: u64| Tile::from_zoom_pos(2, pos).to_id();
+        assert_eq!(diff.new_tiles, vec![id(10), id(11)]);
+        assert_eq!(diff.removed_tiles, vec![id(0)]);
+        assert_eq!(diff.modified_tiles, vec![id(1), id(2), id(3)]);
+
+        // no changes
+        let diff = diff_pmtiles(&mut before, &mut build_archive(&before_tiles)).unwrap();
+        assert_eq!(diff, DiffResult::default());
+    }
+
+    #[test]
+    fn test_diff_long_run() {
+        let mut before = run_archive(&[(0, 1_000_000, "ocean"), (1_000_000, 10, "land")]);
+        let mut after = run_archive(&[
+            (5, 999_990, "ocean"),
+            (999_995, 5, "cliffs"),
+            (1_000_000, 10, "land"),
+            (1_000_010, 2, "land"),
+        ]);
+        let diff = diff_pmtiles(&mut before, &mut after).unwrap();
+        assert_eq!(diff.removed_tiles, vec![0, 1, 2, 3, 4]);
+        assert_eq!(diff.new_tiles, vec![1_000_010, 1_000_011]);
+        assert_eq!(
+            diff.modified_tiles,
+            (999_995..1_000_000).collect::<Vec<_>>()
+        );
+        // "cliffs" differs in length from "ocean" so is never read, the other pairs are read once
+        assert_eq!(before.inner_manager().1, 2);
+        assert_eq!(after.inner_manager().1, 2);
+    }
+}
@@ -5,14 +5,29 @@ extern crate alloc;
 use std::fs::{File, OpenOptions};
 #[cfg(feature = "std")]
 use std::io::{self, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex, MutexGuard};
 
+#[cfg(feature = "std")]
+use crate::reader::{PMTilesReader, ReadError};
 use crate::{
-    Compression, Directory, Entry, Header, S2Entries, S2Header, Tile, TileType, ROOT_SIZE,
+    pmtiles::entries_byte_size,
+    reader::{DataManager, LocalManager},
+    Buffer, Compression, Directory, Entry, Header, S2Entries, S2Header, Tile, TileType, ROOT_SIZE,
     S2_HEADER_SIZE_BYTES, S2_ROOT_SIZE,
 };
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 use s2_tilejson::{Face, Metadata};
+#[cfg(all(feature = "sha2", not(feature = "fast-hash")))]
 use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::xxh3_128;
 
 /// The result of an optimized directory computation
 #[derive(Debug, Clone, Default)]
@@ -52,18 +67,43 @@ impl OptimizedDirectory {
 
     /// Build the root and leaf directories
     pub fn build_root_leaves(directory: &Directory, leaf_size: usize) -> OptimizedDirectory {
+        OptimizedDirectory::build_root_leaves_with_overlap(directory, leaf_size, f32::INFINITY)
+    }
+
+    /// Build the root and leaf directories, splitting long runs that cross a leaf boundary.
+    ///
+    /// When the last entry of a leaf has a run_length larger than `overlap_threshold` percent of
+    /// `leaf_size`, the run is split in two and its tail becomes the first entry of the next leaf.
+    /// The root then points tiles in the tail straight at the leaf that follows them instead of
+    /// funneling the whole run through one leaf. The cost is one extra entry per split, so the
+    /// directories grow slightly. An infinite threshold never splits.
+    pub fn build_root_leaves_with_overlap(
+        directory: &Directory,
+        leaf_size: usize,
+        overlap_threshold: f32,
+    ) -> OptimizedDirectory {
         let mut root_entries = Directory::default();
         let mut leaves_bytes = Vec::<u8>::new();
         let mut num_leaves = 0;
+        let max_run = leaf_size as f32 * overlap_threshold / 100.;
 
         let mut i = 0;
-        let entries = &directory.entries;
+        let mut entries = directory.entries.clone();
         while i < entries.len() {
             num_leaves += 1;
             let mut end = i + leaf_size;
             if i + leaf_size > entries.len() {
                 end = entries.len();
             }
+            let last = entries[end - 1];
+            if end < entries.len() && last.run_length as f32 > max_run {
+                if let Some((head, tail)) =
+                    last.split_run(last.tile_id + last.run_length as u64 / 2)
+                {
+                    entries[end - 1] = head;
+                    entries.insert(end, tail);
+                }
+            }
             let new_dir_slice = Directory::new(entries[i..end].to_vec());
             let serialized = new_dir_slice.serialize();
             let entry = Entry {
@@ -83,6 +123,67 @@ impl OptimizedDirectory {
             num_leaves,
         }
     }
+
+    /// Summarize how the entries are distributed across the leaf directories
+    pub fn stats(&self) -> DirStats {
+        let mut stats = DirStats {
+            num_leaves: self.num_leaves,
+            root_size_bytes: self.root_bytes.len(),
+            total_leaf_size_bytes: self.leaves_bytes.len(),
+            ..Default::default()
+        };
+        if self.num_leaves == 0 {
+            return stats;
+        }
+
+        let root = Directory::from_buffer(&mut self.root_bytes.as_slice().into());
+        let mut total_entries = 0;
+        stats.min_entries_per_leaf = usize::MAX;
+        for leaf in &root.entries {
+            let start = leaf.offset as usize;
+            let end = start + leaf.length as usize;
+            // the first varint of a serialized directory is its number of entries
+            let mut buffer: Buffer = self.leaves_bytes[start..end].into();
+            let num_entries = buffer.read_varint::<usize>();
+            stats.max_entries_per_leaf = stats.max_entries_per_leaf.max(num_entries);
+            stats.min_entries_per_leaf = stats.min_entries_per_leaf.min(num_entries);
+            total_entries += num_entries;
+        }
+        stats.avg_entries_per_leaf = total_entries as f64 / root.len() as f64;
+
+        stats
+    }
+}
+
+/// A summary of the leaf structure of an `OptimizedDirectory`
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct DirStats {
+    /// The number of leaf directories
+    pub num_leaves: u64,
+    /// The most entries found in a single leaf
+    pub max_entries_per_leaf: usize,
+    /// The fewest entries found in a single leaf
+    pub min_entries_per_leaf: usize,
+    /// The average number of entries per leaf
+    pub avg_entries_per_leaf: f64,
+    /// The size of the root directory
+    pub root_size_bytes: usize,
+    /// The combined size of all leaf directories
+    pub total_leaf_size_bytes: usize,
+}
+impl DirStats {
+    /// A human readable summary of the directory stats
+    pub fn print_summary(&self) -> String {
+        format!(
+            "root: {} B, leaves: {} ({} B), entries per leaf: min {}, max {}, avg {:.1}",
+            self.root_size_bytes,
+            self.num_leaves,
+            self.total_leaf_size_bytes,
+            self.min_entries_per_leaf,
+            self.max_entries_per_leaf,
+            self.avg_entries_per_leaf
+        )
+    }
 }
 
 /// The data writer
@@ -93,6 +194,18 @@ pub trait DataWriter: core::fmt::Debug {
     fn append_data(&mut self, data: &[u8]);
     /// Assuming local writer, take ownership of the data when finished writing it
     fn take(&self) -> Vec<u8>;
+    /// The number of bytes written so far
+    fn current_size(&self) -> u64;
+    /// Discard everything written so far. Writers that can not do so keep the default, which
+    /// returns an `Unsupported` error
+    #[cfg(feature = "std")]
+    fn truncate(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+    /// A data manager to read back what was written so far, if the storage can be read back
+    fn data_manager(&self) -> Option<Box<dyn DataManager>> {
+        None
+    }
 }
 
 /// If `std` is enabled use the `FileWriter`
@@ -106,6 +219,7 @@ impl FileWriter {
     /// Create a new `FileWriter`
     pub fn create(path: &str) -> Result<Self, io::Error> {
         let file = OpenOptions::new()
+            .read(true)
             .write(true)
             .create(true)
             .truncate(true)
@@ -130,6 +244,19 @@ impl DataWriter for FileWriter {
     fn take(&self) -> Vec<u8> {
         vec![]
     }
+
+    fn current_size(&self) -> u64 {
+        self.file.metadata().map(|m| m.len()).unwrap_or(0)
+    }
+
+    fn truncate(&mut self) -> io::Result<()> {
+        self.file.set_len(0)
+    }
+
+    fn data_manager(&self) -> Option<Box<dyn DataManager>> {
+        let file = self.file.try_clone().ok()?;
+        Some(Box::new(file))
+    }
 }
 
 /// The local writer is when not using `std` and stores everything to a `Vec<u8>`
@@ -168,6 +295,267 @@ impl DataWriter for LocalWriter {
     fn take(&self) -> Vec<u8> {
         self.data.clone()
     }
+
+    fn current_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    #[cfg(feature = "std")]
+    fn truncate(&mut self) -> io::Result<()> {
+        self.data.clear();
+        Ok(())
+    }
+
+    fn data_manager(&self) -> Option<Box<dyn DataManager>> {
+        Some(Box::new(LocalManager::new(self.data.clone())))
+    }
+}
+
+/// The result of committing an archive
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CommitResult {
+    /// the combined size of the root directories
+    pub root_size_bytes: u64,
+    /// the combined size of the leaf directories
+    pub leaf_size_bytes: u64,
+    /// the size of the JSON metadata
+    pub metadata_size_bytes: u64,
+    /// the size of the tile data
+    pub data_size_bytes: u64,
+    /// the size of the whole archive
+    pub total_bytes: u64,
+    /// the number of tiles written (addressed tiles)
+    pub tiles_written: u64,
+    /// the number of unique tile contents stored
+    pub unique_contents: u64,
+    /// the number of tiles written per unique tile content (1.0 means nothing was deduplicated)
+    pub dedup_ratio: f64,
+    /// the fraction of tiles written after a lower tile ID of the same directory, see
+    /// `PMTilesWriter::clustering_score`
+    pub clustering_score: f64,
+}
+
+/// Everything a commit writes, computed ahead of time by `PMTilesWriter::prepare_commit`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WritePlan {
+    /// the header, root directories and metadata, written at the start of the archive
+    pub head: Vec<u8>,
+    /// the leaf directories, appended after the tile data
+    pub leaves: Vec<u8>,
+    /// the result of the commit once the plan is applied
+    pub result: CommitResult,
+}
+impl WritePlan {
+    /// Perform the writes: append the leaf directories, then write the head at offset 0
+    pub fn apply(&self, writer: &mut dyn DataWriter) {
+        writer.append_data(&self.leaves);
+        writer.write_data(&self.head, 0);
+    }
+}
+
+/// A common mistake found before committing an archive
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WriterWarning {
+    /// no tiles were written, committing would produce an empty archive
+    NoTilesWritten,
+    /// the tile type was never set
+    TileTypeUnknown,
+    /// the bounds were never set (all zero)
+    MissingBounds,
+    /// the written tiles have no zoom range (min and max zoom are both zero)
+    MissingZoomRange,
+    /// the same tile id was written more than once, either as its own entry or inside the run
+    /// of another entry. The archive would be ambiguous, so `commit` refuses it
+    DuplicateTileId(u64),
+    /// the tile compression is not part of the PMTiles v3 spec (zlib or lz4). These are only
+    /// supported for reading, so `commit` refuses them
+    NonStandardCompression(Compression),
+    /// the writer was aborted, nothing can be committed
+    Aborted,
+    /// `commit_incremental` already freed the entries, the archive can not be committed again
+    EntriesFreed,
+    /// a tile sent by a `FaceWriter` was refused when collected, `receive_face_tiles` says why
+    FaceTileRefused,
+}
+
+/// How the writer handles the clustered flag of the archive
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum ClusteringMode {
+    /// the archive is clustered unless tiles are written out of order
+    #[default]
+    AutoDetect,
+    /// writing a tile out of order is an error, so the archive is always clustered
+    Enforce,
+    /// the archive is never marked as clustered
+    Disable,
+}
+
+/// The number of tiles `commit_and_verify` reads back and compares
+pub const VERIFY_SAMPLE_SIZE: usize = 16;
+
+/// The errors found when reading back a committed archive
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// the archive could not be committed
+    Commit(Vec<WriterWarning>),
+    /// the data writer can not read back what was written
+    Unreadable,
+    /// the header read back does not describe what was written
+    HeaderMismatch,
+    /// the tile read back has different contents than what was written
+    TileDataMismatch {
+        /// the tile ID
+        tile_id: u64,
+    },
+    /// the tile written could not be found when reading back
+    MissingTile {
+        /// the tile ID
+        tile_id: u64,
+    },
+}
+
+/// The errors that can occur when committing a `WriteTransaction`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError {
+    /// the archive could not be committed, the temporary file was deleted
+    Commit(Vec<WriterWarning>),
+    /// the temporary file could not be renamed to the final path
+    Rename(io::ErrorKind),
+}
+
+/// The errors that can occur when writing a tile
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteError {
+    /// a tile was written before the last written tile while enforcing clustering
+    OutOfOrder {
+        /// the smallest tile ID that could have been written
+        expected: u64,
+        /// the tile ID that was written
+        got: u64,
+    },
+    /// the writer was aborted
+    Aborted,
+    /// no tile was written with this tile ID
+    TileNotFound {
+        /// the tile ID
+        tile_id: u64,
+    },
+    /// the tile data was refused by the writer's `TileValidator`
+    ValidationFailed(String),
+    /// an entry given to `write_tile_raw` has a run length of 0 (which reads as a leaf
+    /// directory), a length that does not match its data, or points past the data written
+    InvalidRawEntry {
+        /// the tile ID
+        tile_id: u64,
+    },
+    /// the archive can not be committed, see `validate_before_commit`
+    Commit(Vec<WriterWarning>),
+}
+
+/// Hashes tile data for deduplication, tiles with the same hash are only stored once.
+/// Any `FnMut(&[u8]) -> [u8; 32]` closure is a `TileHasher`.
+pub trait TileHasher {
+    /// Hash the tile data
+    fn hash(&mut self, data: &[u8]) -> [u8; 32];
+}
+impl<F: FnMut(&[u8]) -> [u8; 32]> TileHasher for F {
+    fn hash(&mut self, data: &[u8]) -> [u8; 32] {
+        self(data)
+    }
+}
+impl core::fmt::Debug for dyn TileHasher + Send {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("TileHasher")
+    }
+}
+
+/// Checks tile data before it is written, see `PMTilesWriter::with_validator`.
+/// The data is checked as written, so compressed tiles are seen compressed.
+/// Any `Fn(u64, &[u8]) -> Result<(), String>` closure is a `TileValidator`.
+pub trait TileValidator {
+    /// Check the data of a tile, returning why it is invalid if so
+    fn validate(&self, tile_id: u64, data: &[u8]) -> Result<(), String>;
+}
+impl<F: Fn(u64, &[u8]) -> Result<(), String>> TileValidator for F {
+    fn validate(&self, tile_id: u64, data: &[u8]) -> Result<(), String> {
+        self(tile_id, data)
+    }
+}
+impl core::fmt::Debug for dyn TileValidator + Send {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("TileValidator")
+    }
+}
+
+/// Accepts tiles starting with a valid protobuf field tag (a field number above 0 and a wire
+/// type below 6), like uncompressed vector tiles. Empty tiles are valid empty messages
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PbfMagicValidator;
+impl TileValidator for PbfMagicValidator {
+    fn validate(&self, tile_id: u64, data: &[u8]) -> Result<(), String> {
+        match data.first() {
+            Some(tag) if tag & 0x07 >= 6 || tag >> 3 == 0 => Err(format!(
+                "tile {tile_id} does not start with a protobuf field tag (0x{tag:02x})"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The 8 bytes every PNG file starts with
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Accepts tiles starting with the PNG signature
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PngMagicValidator;
+impl TileValidator for PngMagicValidator {
+    fn validate(&self, tile_id: u64, data: &[u8]) -> Result<(), String> {
+        if data.starts_with(&PNG_SIGNATURE) {
+            Ok(())
+        } else {
+            Err(format!(
+                "tile {tile_id} does not start with the PNG signature"
+            ))
+        }
+    }
+}
+
+/// Reports the writer's progress as `(tiles_written, bytes_written)`, see
+/// `PMTilesWriter::set_progress_callback`.
+/// Any `Fn(u64, u64)` closure is a `ProgressCallback`.
+pub trait ProgressCallback {
+    /// Called with the number of tiles addressed and the size of the archive so far
+    fn progress(&self, tiles_written: u64, bytes_written: u64);
+}
+impl<F: Fn(u64, u64)> ProgressCallback for F {
+    fn progress(&self, tiles_written: u64, bytes_written: u64) {
+        self(tiles_written, bytes_written)
+    }
+}
+impl core::fmt::Debug for dyn ProgressCallback + Send {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// The default hasher using SHA-256, or XXH3 like `FastHasher` if the `sha2` feature is
+/// disabled or `fast-hash` is enabled
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DefaultHasher;
+impl TileHasher for DefaultHasher {
+    fn hash(&mut self, data: &[u8]) -> [u8; 32] {
+        hash_data(data)
+    }
+}
+
+/// A faster (non cryptographic) hasher using the 128 bit XXH3, zero padded to 32 bytes
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FastHasher;
+impl TileHasher for FastHasher {
+    fn hash(&mut self, data: &[u8]) -> [u8; 32] {
+        fast_hash_data(data)
+    }
 }
 
 /// The File reader is to be used by the local filesystem.
@@ -176,176 +564,1098 @@ pub struct PMTilesWriter {
     tile_entries: Directory,
     s2tile_entries: S2Entries,
     offset: u64,
-    hash_to_offset: std::collections::HashMap<[u8; 32], u64>,
+    hash_to_offset: BTreeMap<[u8; 32], u64>,
+    tile_contents: u64,
+    dedup: bool,
     addressed_tiles: u64,
     clustered: bool,
+    clustering: ClusteringMode,
+    /// tiles written after another tile of the same directory, and how many of them had a
+    /// higher tile ID, see `clustering_score`
+    entry_pairs: u64,
+    ordered_pairs: u64,
     compression: Compression,
-    data_writer: Box<dyn DataWriter>,
+    tile_type: TileType,
+    detect_tile_type: bool,
+    bounds: [f32; 4],
+    /// fields merged into the serialized `Metadata` at commit time
+    extra_metadata: serde_json::Map<String, serde_json::Value>,
+    aborted: bool,
+    /// set once `commit_incremental` freed the WM entries
+    entries_freed: bool,
+    hasher: Box<dyn TileHasher + Send>,
+    progress: Option<Box<dyn ProgressCallback + Send>>,
+    validator: Option<Box<dyn TileValidator + Send>>,
+    /// tiles sent by the `FaceWriter`s of `split_by_face`, waiting to be written
+    #[cfg(feature = "std")]
+    face_channel: Option<(Sender<FaceTile>, Receiver<FaceTile>)>,
+    /// boxed as `Send`, like the hasher, validator and progress callback, so the writer can be
+    /// moved to another thread or shared with `PMTilesWriterShared`
+    data_writer: Box<dyn DataWriter + Send>,
 }
 impl PMTilesWriter {
     /// given a compression scheme and a data writer, create an instance to start storing tiles
     /// and metadata.
     /// Compression will only describle how tiles are stored, nothing more.
-    pub fn new(compression: Compression, data_writer: Box<dyn DataWriter>) -> Self {
+    pub fn new(compression: Compression, data_writer: Box<dyn DataWriter + Send>) -> Self {
+        PMTilesWriter::new_with_hasher(compression, data_writer, DefaultHasher)
+    }
+
+    /// Same as `new`, but reserving room for `estimated_unique_tiles` entries in the WM
+    /// directory up front, since every unique tile needs at least one entry.
+    /// The deduplication index is a `BTreeMap` (it has no capacity to reserve) and the S2 face
+    /// directories still grow as tiles are written.
+    pub fn new_with_capacity(
+        compression: Compression,
+        data_writer: Box<dyn DataWriter + Send>,
+        estimated_unique_tiles: usize,
+    ) -> Self {
+        let mut writer = PMTilesWriter::new(compression, data_writer);
+        writer.tile_entries.entries.reserve(estimated_unique_tiles);
+        writer
+    }
+
+    /// Start writing an archive to a temporary file next to `path` ("{path}.tmp"), which only
+    /// replaces `path` once the transaction is committed. Dropping the transaction without
+    /// committing deletes the temporary file, so a crash never leaves a partial archive at `path`
+    #[cfg(feature = "std")]
+    pub fn begin_transaction(
+        path: &str,
+        compression: Compression,
+    ) -> Result<WriteTransaction, io::Error> {
+        let temp_path = format!("{path}.tmp");
+        let data_writer = FileWriter::create(&temp_path)?;
+        Ok(WriteTransaction {
+            writer: Some(PMTilesWriter::new(compression, Box::new(data_writer))),
+            path: path.into(),
+            temp_path,
+        })
+    }
+
+    /// Same as `new`, but deduplicating tiles with `FastHasher` whatever the features enabled
+    pub fn with_fast_hash(
+        compression: Compression,
+        data_writer: Box<dyn DataWriter + Send>,
+    ) -> Self {
+        PMTilesWriter::new_with_hasher(compression, data_writer, FastHasher)
+    }
+
+    /// Same as `new`, but deduplicating tiles with a custom hasher, like `FastHasher`
+    pub fn new_with_hasher<H: TileHasher + Send + 'static>(
+        compression: Compression,
+        data_writer: Box<dyn DataWriter + Send>,
+        hasher: H,
+    ) -> Self {
         let root_data = vec![0u8; S2_ROOT_SIZE];
         let mut writer = PMTilesWriter {
             tile_entries: Directory::default(),
             s2tile_entries: S2Entries::default(),
-            hash_to_offset: std::collections::HashMap::new(),
+            hash_to_offset: BTreeMap::new(),
+            tile_contents: 0,
+            dedup: true,
             offset: 0,
             addressed_tiles: 0,
-            clustered: false,
+            clustered: true,
+            clustering: ClusteringMode::AutoDetect,
+            entry_pairs: 0,
+            ordered_pairs: 0,
             compression,
+            tile_type: TileType::Unknown,
+            detect_tile_type: false,
+            bounds: [0.0; 4],
+            extra_metadata: serde_json::Map::new(),
+            aborted: false,
+            entries_freed: false,
+            hasher: Box::new(hasher),
+            progress: None,
+            validator: None,
+            #[cfg(feature = "std")]
+            face_channel: None,
             data_writer,
         };
         writer.data_writer.append_data(&root_data);
         writer
     }
 
+    /// Reopen an existing archive to add more tiles to it. The existing tile data is copied
+    /// into `data_writer` (which should start empty, the archive is rewritten rather than
+    /// edited in place) and hashed so new tiles deduplicate against it. Directories, tile type,
+    /// compression, bounds and the clustered flag are taken from the archive; the metadata is
+    /// not, pass it to `commit` again.
+    #[cfg(feature = "std")]
+    pub fn open_existing<M: DataManager>(
+        data_manager: M,
+        data_writer: Box<dyn DataWriter + Send>,
+    ) -> Result<PMTilesWriter, ReadError> {
+        let mut reader = PMTilesReader::new(data_manager, None);
+        let header = reader.try_get_header()?;
+        let mut writer = PMTilesWriter::new(header.tile_compression, data_writer);
+        writer.tile_type = header.tile_type;
+        writer.bounds = [
+            header.min_longitude,
+            header.min_latitude,
+            header.max_longitude,
+            header.max_latitude,
+        ];
+        writer.clustered = header.clustered;
+        if header.is_s2 {
+            for face in STORES.into_iter().flatten() {
+                *writer.s2tile_entries.get_mut(face) =
+                    Directory::new(reader.get_entries(Some(face))?);
+            }
+        } else {
+            writer.tile_entries = Directory::new(reader.get_entries(None)?);
+        }
+
+        // copy the data as is so the entries keep their offsets, hashing every content
+        let entries: Vec<Entry> = writer
+            .directories()
+            .iter()
+            .flat_map(|directory| directory.entries.iter().copied())
+            .collect();
+        writer.addressed_tiles = entries.iter().map(|e| e.run_length as u64).sum();
+        let mut contents: Vec<(u64, u64)> = entries
+            .iter()
+            .map(|e| (e.offset, e.length as u64))
+            .collect();
+        contents.sort_unstable();
+        contents.dedup();
+        let data_manager = reader.inner_manager_mut();
+        for (offset, length) in &contents {
+            let (offset, end) = (*offset, offset + length);
+            let start = offset.min(writer.offset);
+            let data = data_manager.get_range(header.data_offset + start, end - start);
+            if end > writer.offset {
+                writer
+                    .data_writer
+                    .append_data(&data[(writer.offset - start) as usize..]);
+                writer.offset = end;
+            }
+            let hsh = writer.hasher.hash(&data[(offset - start) as usize..]);
+            writer.hash_to_offset.entry(hsh).or_insert(offset);
+        }
+        if header.data_length > writer.offset {
+            let rest = data_manager.get_range(
+                header.data_offset + writer.offset,
+                header.data_length - writer.offset,
+            );
+            writer.data_writer.append_data(&rest);
+            writer.offset = header.data_length;
+        }
+        writer.tile_contents = contents.len() as u64;
+
+        Ok(writer)
+    }
+
+    /// Same as `new`, but every tile is stored as is, skipping the hashing used to deduplicate
+    /// tiles. Writes faster when duplicates are rare, at the cost of a larger archive otherwise
+    pub fn with_no_dedup(
+        compression: Compression,
+        data_writer: Box<dyn DataWriter + Send>,
+    ) -> Self {
+        let mut writer = PMTilesWriter::new(compression, data_writer);
+        writer.dedup = false;
+        writer
+    }
+
+    /// Check every tile with `validator` before writing it. A refused tile is not written and
+    /// `write_tile` (or `replace_tile`) returns `WriteError::ValidationFailed`
+    pub fn with_validator(mut self, validator: Box<dyn TileValidator + Send>) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Guess the tile type from the first tile written with `TileType::from_file_bytes`, if it
+    /// was not set with `set_tile_type` before. Tiles are seen as written, so vector tiles are
+    /// only recognized when they are not compressed
+    pub fn with_tile_type_detection(mut self) -> Self {
+        self.detect_tile_type = true;
+        self
+    }
+
+    /// Set how the clustered flag is handled, see `ClusteringMode`
+    pub fn with_clustering_enforcement(mut self, enforcement: ClusteringMode) -> Self {
+        self.clustering = enforcement;
+        self.clustered = enforcement != ClusteringMode::Disable;
+        self
+    }
+
+    /// Sort every directory by tile ID, for when tiles could not be written in order.
+    /// The tile data is not moved, entries keep pointing at the data where it was written and
+    /// consecutive tiles sharing the same data are merged into runs. The archive is marked as
+    /// clustered only if the sorted entries reference the data in the order it was appended.
+    /// The content hashes are unaffected since the data never moves.
+    pub fn sort_and_recluster(&mut self) {
+        let mut clustered = self.clustering != ClusteringMode::Disable;
+        let mut next_offset = 0;
+        for face in STORES {
+            let directory = match face {
+                None => &mut self.tile_entries,
+                Some(f) => self.s2tile_entries.get_mut(f),
+            };
+            directory.sort();
+            directory.merge_runs();
+            // new data must follow the previous data, older offsets are deduplicated tiles
+            for entry in &directory.entries {
+                match entry.offset.cmp(&next_offset) {
+                    core::cmp::Ordering::Equal => next_offset += entry.length as u64,
+                    core::cmp::Ordering::Greater => clustered = false,
+                    core::cmp::Ordering::Less => {}
+                }
+            }
+        }
+        self.clustered = clustered;
+        // every entry now follows a lower tile ID
+        self.ordered_pairs = self.entry_pairs;
+    }
+
+    /// The tile compression the archive is written with
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// The fraction of adjacent entry pairs whose tile IDs are in ascending order: 1.0 when
+    /// every tile was written after the previous tile of its directory (or when nothing was
+    /// written), down to 0.0 when every tile went backwards. Sorting with `sort_and_recluster`
+    /// brings it back to 1.0. The archive is only marked as clustered at 1.0
+    pub fn clustering_score(&self) -> f64 {
+        if self.entry_pairs == 0 {
+            1.0
+        } else {
+            self.ordered_pairs as f64 / self.entry_pairs as f64
+        }
+    }
+
+    /// The clustered flag written to the header
+    fn is_clustered(&self) -> bool {
+        self.clustered && self.ordered_pairs == self.entry_pairs
+    }
+
+    /// Call `callback(tiles_written, bytes_written)` after every tile written and after the
+    /// commit, to follow a long ingestion. `tiles_written` counts addressed tiles (deduplicated
+    /// ones included) and `bytes_written` is the size of the archive so far.
+    pub fn set_progress_callback<F: Fn(u64, u64) + Send + 'static>(&mut self, callback: F) {
+        self.progress = Some(Box::new(callback));
+    }
+
+    /// Set the type of tiles stored in the archive
+    pub fn set_tile_type(&mut self, tile_type: TileType) {
+        self.tile_type = tile_type;
+    }
+
+    /// Set the bounds of the archive in degrees
+    pub fn set_bounds(
+        &mut self,
+        min_longitude: f32,
+        min_latitude: f32,
+        max_longitude: f32,
+        max_latitude: f32,
+    ) {
+        self.bounds = [min_longitude, min_latitude, max_longitude, max_latitude];
+    }
+
+    /// Add a field to the metadata JSON written at commit time, on top of the fields of the
+    /// `Metadata` passed to `commit`. Returns false (and adds nothing) if `key` is the name of
+    /// a `Metadata` field, which would make the metadata unreadable as `Metadata`
+    pub fn set_extra_metadata(&mut self, key: &str, value: serde_json::Value) -> bool {
+        let fields = serde_json::to_value(Metadata::default()).unwrap();
+        if fields.get(key).is_some() {
+            return false;
+        }
+        self.extra_metadata.insert(key.into(), value);
+
+        true
+    }
+
+    /// Check for common mistakes before committing.
+    /// `WriterWarning::NoTilesWritten`, `DuplicateTileId` and `NonStandardCompression` stop a
+    /// commit, the rest are worth logging.
+    pub fn validate_before_commit(&self) -> Result<(), Vec<WriterWarning>> {
+        let mut warnings = Vec::new();
+        if self.addressed_tiles == 0 {
+            warnings.push(WriterWarning::NoTilesWritten);
+        }
+        if self.tile_type == TileType::Unknown {
+            warnings.push(WriterWarning::TileTypeUnknown);
+        }
+        if self.bounds == [0.0; 4] {
+            warnings.push(WriterWarning::MissingBounds);
+        }
+        if matches!(self.compression, Compression::Zlib | Compression::Lz4) {
+            warnings.push(WriterWarning::NonStandardCompression(self.compression));
+        }
+        let mut min_zoom = u8::MAX;
+        let mut max_zoom = 0;
+        for directory in self.directories() {
+            // a tile is a duplicate if an earlier entry (or its run) already addresses it
+            let mut ranges: Vec<(u64, u64)> = directory
+                .entries
+                .iter()
+                .map(|e| (e.tile_id, e.tile_id + e.run_length.max(1) as u64))
+                .collect();
+            ranges.sort_unstable();
+            let mut addressed_until = 0;
+            for (i, (start, end)) in ranges.into_iter().enumerate() {
+                let duplicate = WriterWarning::DuplicateTileId(start);
+                if i > 0 && start < addressed_until && !warnings.contains(&duplicate) {
+                    warnings.push(duplicate);
+                }
+                addressed_until = addressed_until.max(end);
+            }
+            for e in &directory.entries {
+                let last_id = e.tile_id + e.run_length.max(1) as u64 - 1;
+                min_zoom = min_zoom.min(Tile::from_id(e.tile_id).zoom);
+                max_zoom = max_zoom.max(Tile::from_id(last_id).zoom);
+            }
+        }
+        if min_zoom == u8::MAX || (min_zoom == 0 && max_zoom == 0) {
+            warnings.push(WriterWarning::MissingZoomRange);
+        }
+
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(warnings)
+        }
+    }
+
+    /// Estimate the size of the archive if it were committed now.
+    /// The root directories and metadata live inside the fixed `S2_ROOT_SIZE` block, so the
+    /// estimate is what the data writer holds so far (that block and the tile data), and the
+    /// leaf directories needed by any directory too large to fit in the root.
+    pub fn estimate_output_size(&self) -> u64 {
+        let root_budget = (ROOT_SIZE - S2_HEADER_SIZE_BYTES - ESTIMATED_METADATA_SIZE) as u64;
+        let leaves_size: u64 = self
+            .directories()
+            .into_iter()
+            .map(|directory| directory.approximate_byte_size() as u64)
+            .filter(|size| *size >= root_budget)
+            .sum();
+
+        self.data_writer.current_size() + leaves_size
+    }
+
+    /// The WM directory followed by the six S2 face directories
+    fn directories(&self) -> [&Directory; 7] {
+        [
+            &self.tile_entries,
+            &self.s2tile_entries.face_0,
+            &self.s2tile_entries.face_1,
+            &self.s2tile_entries.face_2,
+            &self.s2tile_entries.face_3,
+            &self.s2tile_entries.face_4,
+            &self.s2tile_entries.face_5,
+        ]
+    }
+
     /// take ownership of writer data (if local this actually has content)
     pub fn take(&mut self) -> Vec<u8> {
         self.data_writer.take()
     }
 
     /// Write a tile to the PMTiles file given its (face, zoom, x, y) coordinates.
-    pub fn write_tile_xyz(&mut self, zoom: u8, x: u64, y: u64, data: &[u8]) {
+    pub fn write_tile_xyz(
+        &mut self,
+        zoom: u8,
+        x: u64,
+        y: u64,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
         let tile_id = Tile::new(zoom, x, y).to_id();
-        self.write_tile(tile_id, data, None);
+        self.write_tile(tile_id, data, None)
     }
 
     /// Write a tile to the PMTiles file given its (face, zoom, x, y) coordinates.
-    pub fn write_tile_s2(&mut self, face: Face, zoom: u8, x: u64, y: u64, data: &[u8]) {
+    pub fn write_tile_s2(
+        &mut self,
+        face: Face,
+        zoom: u8,
+        x: u64,
+        y: u64,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
         let tile_id = Tile::new(zoom, x, y).to_id();
-        self.write_tile(tile_id, data, Some(face));
+        self.write_tile(tile_id, data, Some(face))
     }
 
-    /// Write a tile to the PMTiles file given its tile ID.
-    pub fn write_tile(&mut self, tile_id: u64, data: &[u8], face: Option<Face>) {
-        let length = data.len();
-        let tile_entries = match face {
-            None => &mut self.tile_entries,
-            Some(f) => self.s2tile_entries.get_mut(f),
+    /// Split off one `FaceWriter` per S2 face (Face0 to Face5), so each face can be written from
+    /// its own thread without locking the writer. The tiles they send are queued (in memory)
+    /// until `receive_face_tiles` writes them, which `commit` does first. Every face writer
+    /// must be done sending by then, tiles sent later are left out
+    #[cfg(feature = "std")]
+    pub fn split_by_face(&mut self) -> [FaceWriter; 6] {
+        let (sender, _) = self.face_channel.get_or_insert_with(mpsc::channel);
+        [
+            Face::Face0,
+            Face::Face1,
+            Face::Face2,
+            Face::Face3,
+            Face::Face4,
+            Face::Face5,
+        ]
+        .map(|face| FaceWriter {
+            face,
+            sender: sender.clone(),
+        })
+    }
+
+    /// Write the tiles sent by the `FaceWriter`s so far, in the order each face sent them.
+    /// Stops at the first tile refused (out of order while enforcing clustering, or by the
+    /// validator), the tiles sent after it stay queued
+    #[cfg(feature = "std")]
+    pub fn receive_face_tiles(&mut self) -> Result<(), WriteError> {
+        let Some(channel) = self.face_channel.take() else {
+            return Ok(());
         };
-        if !tile_entries.is_empty() && tile_id < tile_entries.last().unwrap().tile_id {
-            self.clustered = false;
-        }
-
-        let hsh = hash_data(data);
-        match self.hash_to_offset.get(&hsh) {
-            Some(offset) => {
-                let mut add_new_entry = true;
-                if let Some(last) = tile_entries.last_mut() {
-                    if tile_id == last.tile_id + last.run_length as u64 && last.offset == *offset {
-                        last.run_length += 1;
-                        add_new_entry = false; // Update within existing entry, no need to add a new one
+        let mut result = Ok(());
+        while let Ok((face, tile_id, data)) = channel.1.try_recv() {
+            result = self.write_tile(tile_id, &data, Some(face));
+            if result.is_err() {
+                break;
+            }
+        }
+        self.face_channel = Some(channel);
+
+        result
+    }
+
+    /// Write many tiles of one S2 face given their (zoom, x, y, data). The batch is sorted by
+    /// tile ID before writing, so its order never unclusters the archive; only a batch starting
+    /// before the last written tile does (or fails, see `ClusteringMode::Enforce`).
+    /// Identical tiles within the batch are deduplicated like any other write
+    pub fn write_tile_s2_batch(
+        &mut self,
+        face: Face,
+        tiles: &[(u8, u64, u64, &[u8])],
+    ) -> Result<(), WriteError> {
+        let mut sorted: Vec<(u64, &[u8])> = tiles
+            .iter()
+            .map(|(zoom, x, y, data)| (Tile::new(*zoom, *x, *y).to_id(), *data))
+            .collect();
+        sorted.sort_by_key(|(tile_id, _)| *tile_id);
+        for (tile_id, data) in sorted {
+            self.write_tile(tile_id, data, Some(face))?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a tile to the PMTiles file given its tile ID.
+    /// Only fails if the tile is out of order while using `ClusteringMode::Enforce`.
+    /// Every call addresses one tile, including the ones folded into the run of the previous
+    /// entry. Writing the same tile ID twice is not checked here (that would mean tracking
+    /// every ID written); it is reported by `validate_before_commit` and refused by `commit`.
+    pub fn write_tile(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        face: Option<Face>,
+    ) -> Result<(), WriteError> {
+        self.append_tile(tile_id, 1, data, face)?;
+        self.report_progress();
+
+        Ok(())
+    }
+
+    /// Same as `write_tile` for `run_length` consecutive tiles starting at `tile_id` that share
+    /// the same data, which is validated and hashed once. A run length of 0 writes nothing
+    pub fn write_tile_run(
+        &mut self,
+        tile_id: u64,
+        run_length: u32,
+        data: &[u8],
+        face: Option<Face>,
+    ) -> Result<(), WriteError> {
+        if run_length == 0 {
+            return Ok(());
+        }
+        self.append_tile(tile_id, run_length, data, face)?;
+        self.report_progress();
+
+        Ok(())
+    }
+
+    /// Store the data of a run of tiles (unless deduplicated) and address it in its directory
+    fn append_tile(
+        &mut self,
+        tile_id: u64,
+        run_length: u32,
+        data: &[u8],
+        face: Option<Face>,
+    ) -> Result<(), WriteError> {
+        if self.aborted {
+            return Err(WriteError::Aborted);
+        }
+        self.validate_tile(tile_id, data)?;
+        if self.detect_tile_type && self.addressed_tiles == 0 && self.tile_type == TileType::Unknown
+        {
+            self.tile_type = TileType::from_file_bytes(data);
+        }
+        if let Some(last) = self.directory_mut(face).last() {
+            let last_id = last.tile_id + last.run_length as u64 - 1;
+            if tile_id < last_id {
+                match self.clustering {
+                    ClusteringMode::Enforce => {
+                        return Err(WriteError::OutOfOrder {
+                            expected: last_id,
+                            got: tile_id,
+                        });
                     }
+                    _ => self.clustered = false,
                 }
-                if add_new_entry {
-                    tile_entries.insert(Entry {
-                        tile_id,
-                        offset: *offset,
-                        length: length as u32,
-                        run_length: 1,
-                    });
-                }
             }
-            None => {
-                let offset = self.offset;
-                self.data_writer.append_data(data);
-                tile_entries.insert(Entry {
-                    tile_id,
-                    offset,
-                    length: length as u32,
-                    run_length: 1,
-                });
-                self.hash_to_offset.insert(hsh, offset);
-                self.offset += length as u64;
+            self.entry_pairs += 1;
+            if tile_id > last_id {
+                self.ordered_pairs += 1;
+            }
+        }
+        // the tiles within the run follow each other
+        self.entry_pairs += run_length as u64 - 1;
+        self.ordered_pairs += run_length as u64 - 1;
+
+        let offset = self.store_data(data);
+        let tile_entries = self.directory_mut(face);
+        let merged = match tile_entries.last_mut() {
+            // the same data as the previous tile, extend its run
+            Some(last)
+                if tile_id == last.tile_id + last.run_length as u64 && last.offset == offset =>
+            {
+                last.run_length
+                    .checked_add(run_length)
+                    .map(|run| last.run_length = run)
+                    .is_some()
             }
+            _ => false,
+        };
+        if !merged {
+            tile_entries.insert(Entry::new(tile_id, offset, data.len() as u32, run_length));
         }
+        self.addressed_tiles += run_length as u64;
 
-        self.addressed_tiles += 1;
+        Ok(())
     }
 
-    /// Finish writing by building the header with root and leaf directories
-    pub fn commit(&mut self, metadata: &Metadata) {
-        if !self.tile_entries.is_empty() {
-            self.commit_wm(metadata);
-        } else {
-            self.commit_s2(metadata);
+    /// Write a tile at the offset of its entry (relative to the start of the tile data), for
+    /// callers laying out and deduplicating the data themselves. With `data`, it is written at
+    /// `entry.offset` and the caller guarantees it overlaps nothing else; without, the entry
+    /// points at data already written. The writer's own layout is bypassed: nothing is hashed
+    /// or deduplicated, and the clustered flag is cleared whatever the clustering mode (call
+    /// `sort_and_recluster` once done to recompute it from the final layout)
+    pub fn write_tile_raw(
+        &mut self,
+        entry: Entry,
+        data: Option<&[u8]>,
+        face: Option<Face>,
+    ) -> Result<(), WriteError> {
+        if self.aborted {
+            return Err(WriteError::Aborted);
         }
+        let invalid = WriteError::InvalidRawEntry {
+            tile_id: entry.tile_id,
+        };
+        let end = entry.offset + entry.length as u64;
+        match data {
+            _ if entry.run_length == 0 => return Err(invalid),
+            Some(data) if data.len() != entry.length as usize => return Err(invalid),
+            None if end > self.offset => return Err(invalid),
+            _ => {}
+        }
+        if let Some(data) = data {
+            self.validate_tile(entry.tile_id, data)?;
+            self.data_writer
+                .write_data(data, S2_ROOT_SIZE as u64 + entry.offset);
+            self.offset = self.offset.max(end);
+            self.tile_contents += 1;
+        }
+        if let Some(last) = self.directory_mut(face).last() {
+            let ordered = entry.tile_id >= last.tile_id + last.run_length as u64;
+            self.entry_pairs += 1;
+            self.ordered_pairs += ordered as u64;
+        }
+        self.directory_mut(face).insert(entry);
+        self.addressed_tiles += entry.run_length as u64;
+        self.clustered = false;
+        self.report_progress();
+
+        Ok(())
     }
 
-    /// Finish writing by building the header with root and leaf directories
-    pub fn commit_wm(&mut self, metadata: &Metadata) {
-        // build metadata
-        let meta_buffer = serde_json::to_vec(metadata).unwrap();
+    /// Replace the data of a tile already written, before committing. The new data is appended
+    /// (or deduplicated like any other write) and the tile's entry points to it, splitting the
+    /// run it was part of if needed. The old data stays in the archive, and since the entry no
+    /// longer follows the data order the archive is not marked as clustered anymore.
+    pub fn replace_tile(
+        &mut self,
+        tile_id: u64,
+        new_data: &[u8],
+        face: Option<Face>,
+    ) -> Result<(), WriteError> {
+        if self.aborted {
+            return Err(WriteError::Aborted);
+        }
+        let Some(index) = self.directory_mut(face).entries.iter().position(|e| {
+            e.run_length > 0 && (e.tile_id..e.tile_id + e.run_length as u64).contains(&tile_id)
+        }) else {
+            return Err(WriteError::TileNotFound { tile_id });
+        };
+        self.validate_tile(tile_id, new_data)?;
 
-        // optimize directories
-        let od: OptimizedDirectory = OptimizedDirectory::optimize_directories(
-            &mut self.tile_entries,
-            ROOT_SIZE - S2_HEADER_SIZE_BYTES - meta_buffer.len(),
-        );
-        let OptimizedDirectory {
-            root_bytes,
-            leaves_bytes,
-            ..
-        } = od;
+        let offset = self.store_data(new_data);
+        let entries = &mut self.directory_mut(face).entries;
+        let entry = entries[index];
+        let mut replacement = Vec::with_capacity(3);
+        if let Some((head, _)) = entry.split_run(tile_id) {
+            replacement.push(head);
+        }
+        replacement.push(Entry::new(tile_id, offset, new_data.len() as u32, 1));
+        if let Some((_, tail)) = entry.split_run(tile_id + 1) {
+            replacement.push(tail);
+        }
+        entries.splice(index..=index, replacement);
+        self.clustered = false;
 
-        // build header data
-        let root_directory_offset = S2_HEADER_SIZE_BYTES as u64;
-        let root_directory_length = root_bytes.len() as u64;
-        let metadata_offset = root_directory_offset + root_directory_length;
-        let metadata_length = meta_buffer.len() as u64;
-        let leaf_directory_offset = self.offset + S2_ROOT_SIZE as u64;
-        let leaf_directory_length = leaves_bytes.len() as u64;
-        self.offset += leaves_bytes.len() as u64;
+        Ok(())
+    }
 
-        // write data
-        self.data_writer.append_data(&leaves_bytes);
-        // to make writing fasters
-        let min_zoom = Tile::from_id(self.tile_entries.first().unwrap().tile_id).zoom;
-        let max_zoom = Tile::from_id(self.tile_entries.last().unwrap().tile_id).zoom;
+    /// Run the validator, if any
+    fn validate_tile(&self, tile_id: u64, data: &[u8]) -> Result<(), WriteError> {
+        match &self.validator {
+            Some(validator) => validator
+                .validate(tile_id, data)
+                .map_err(WriteError::ValidationFailed),
+            None => Ok(()),
+        }
+    }
 
-        // build header
-        let header = Header {
-            version: 3,
-            root_directory_offset,
-            root_directory_length,
-            metadata_offset,
-            metadata_length,
-            leaf_directory_offset,
-            leaf_directory_length,
-            data_offset: S2_ROOT_SIZE as u64,
-            data_length: self.offset,
-            n_addressed_tiles: self.addressed_tiles,
-            n_tile_entries: self.tile_entries.len() as u64,
-            n_tile_contents: self.hash_to_offset.len() as u64,
-            clustered: self.clustered,
-            internal_compression: Compression::None,
-            tile_compression: self.compression,
-            tile_type: TileType::Unknown,
-            min_zoom,
-            max_zoom,
-            ..Default::default()
+    /// Append the data unless the same data was already stored, returning its offset
+    fn store_data(&mut self, data: &[u8]) -> u64 {
+        let hsh = if self.dedup {
+            let hsh = self.hasher.hash(data);
+            if let Some(offset) = self.hash_to_offset.get(&hsh) {
+                return *offset;
+            }
+            Some(hsh)
+        } else {
+            None
         };
-        let serialized_header = header.to_bytes().take();
+        let offset = self.offset;
+        self.data_writer.append_data(data);
+        if let Some(hsh) = hsh {
+            self.hash_to_offset.insert(hsh, offset);
+        }
+        self.offset += data.len() as u64;
+        self.tile_contents += 1;
 
-        // write header
-        self.data_writer.write_data(&serialized_header, 0);
-        self.data_writer
-            .write_data(&root_bytes, root_directory_offset);
-        self.data_writer.write_data(&meta_buffer, metadata_offset);
+        offset
     }
 
-    /// Finish writing by building the header with root and leaf directories
-    pub fn commit_s2(&mut self, metadata: &Metadata) {
-        // build metadata
-        let meta_buffer = serde_json::to_vec(metadata).unwrap();
+    /// The directory of a face (None for WM)
+    fn directory_mut(&mut self, face: Option<Face>) -> &mut Directory {
+        match face {
+            None => &mut self.tile_entries,
+            Some(f) => self.s2tile_entries.get_mut(f),
+        }
+    }
 
-        // optimize directories
-        let od = OptimizedDirectory::optimize_directories(
-            self.s2tile_entries.get_mut(Face::Face0),
-            ROOT_SIZE - S2_HEADER_SIZE_BYTES - meta_buffer.len(),
+    /// Tell the progress callback, if any, where the writer stands
+    fn report_progress(&self) {
+        if let Some(progress) = &self.progress {
+            // the root block is reserved up front, tile data and leaves are appended after it
+            progress.progress(self.addressed_tiles, S2_ROOT_SIZE as u64 + self.offset);
+        }
+    }
+
+    /// Discard everything written so far, so a failed write never leaves a partial archive
+    /// behind. A file is truncated to 0 bytes and closed, local data is cleared.
+    /// Any further `write_tile` or `commit` returns an error. The writer is aborted even when
+    /// the data could not be truncated, the returned error says why.
+    #[cfg(feature = "std")]
+    pub fn abort(&mut self) -> io::Result<()> {
+        let truncated = self.data_writer.truncate();
+        self.discard();
+        truncated
+    }
+
+    /// Discard everything written so far, local data is dropped.
+    /// Any further `write_tile` or `commit` returns an error.
+    #[cfg(not(feature = "std"))]
+    pub fn abort(&mut self) {
+        self.discard();
+    }
+
+    /// Reset the writer to an empty, aborted state
+    fn discard(&mut self) {
+        // dropping the data writer closes the file
+        self.data_writer = Box::new(LocalWriter::new());
+        self.tile_entries = Directory::default();
+        self.s2tile_entries = S2Entries::default();
+        self.hash_to_offset.clear();
+        self.tile_contents = 0;
+        self.offset = 0;
+        self.addressed_tiles = 0;
+        self.entry_pairs = 0;
+        self.ordered_pairs = 0;
+        self.aborted = true;
+        #[cfg(feature = "std")]
+        {
+            // any tile still sent by a `FaceWriter` now fails
+            self.face_channel = None;
+        }
+    }
+
+    /// Finish writing by building the header with root and leaf directories.
+    /// Nothing is written if no tiles were written, a tile was written more than once or the
+    /// compression is zlib or lz4 (only supported for reading), see `validate_before_commit`.
+    pub fn commit(&mut self, metadata: &Metadata) -> Result<CommitResult, Vec<WriterWarning>> {
+        self.check_commit()?;
+        if !self.tile_entries.is_empty() {
+            Ok(self.commit_wm(metadata))
+        } else {
+            Ok(self.commit_s2(metadata))
+        }
+    }
+
+    /// Same as `commit`, but each leaf directory of a WM archive is written as soon as it is
+    /// built rather than buffering all of them, so only one leaf of `chunk_size` entries is in
+    /// memory at a time. `chunk_size` is doubled until the root directory fits, like `commit`
+    /// does starting from 4096 entries (with which the output is identical).
+    /// The entries are freed as their leaves are written, only the root directory is kept, so
+    /// any later commit fails with `WriterWarning::EntriesFreed`. S2 archives and archives
+    /// without leaf directories are committed as usual.
+    pub fn commit_incremental(
+        &mut self,
+        metadata: &Metadata,
+        chunk_size: usize,
+    ) -> Result<CommitResult, WriteError> {
+        self.check_commit().map_err(WriteError::Commit)?;
+        if self.tile_entries.is_empty() {
+            return Ok(self.commit_s2(metadata));
+        }
+        let meta_buffer = self.metadata_buffer(metadata);
+        let target_root_length = ROOT_SIZE - S2_HEADER_SIZE_BYTES - meta_buffer.len();
+        self.tile_entries.sort();
+        if self.tile_entries.approximate_byte_size() < target_root_length {
+            return Ok(self.commit_wm(metadata));
+        }
+
+        // size the root first, so no leaf is written for a root that would not fit
+        let mut leaf_size = chunk_size.max(1);
+        let root = loop {
+            let mut root = Directory::default();
+            let mut offset = 0;
+            for chunk in self.tile_entries.entries.chunks(leaf_size) {
+                let length = entries_byte_size(chunk) as u32;
+                root.insert(Entry::new(chunk[0].tile_id, offset, length, 0));
+                offset += length as u64;
+            }
+            if root.approximate_byte_size() < target_root_length {
+                break root;
+            }
+            leaf_size *= 2;
+        };
+        let leaves_length = root.entries.iter().map(|e| e.length as u64).sum();
+        let (head, result) = self.wm_head(root.serialize(), leaves_length, meta_buffer);
+
+        // reversed, so each leaf is split off the end and its memory given back as we go
+        let mut entries = core::mem::take(&mut self.tile_entries.entries);
+        self.entries_freed = true;
+        entries.reverse();
+        while !entries.is_empty() {
+            let mut chunk = entries.split_off(entries.len().saturating_sub(leaf_size));
+            chunk.reverse();
+            self.data_writer
+                .append_data(&Directory::new(chunk).serialize());
+            if entries.len() < entries.capacity() / 2 {
+                entries.shrink_to_fit();
+            }
+        }
+        self.offset += leaves_length;
+        self.data_writer.write_data(&head, 0);
+        self.report_progress();
+
+        Ok(result)
+    }
+
+    /// Collect the tiles of the `FaceWriter`s, then refuse to commit an aborted writer, one
+    /// whose entries `commit_incremental` freed, or one without tiles, with a tile written twice
+    /// or with a non-standard compression
+    fn check_commit(&mut self) -> Result<(), Vec<WriterWarning>> {
+        if self.aborted {
+            return Err(vec![WriterWarning::Aborted]);
+        }
+        if self.entries_freed {
+            return Err(vec![WriterWarning::EntriesFreed]);
+        }
+        #[cfg(feature = "std")]
+        if self.receive_face_tiles().is_err() {
+            return Err(vec![WriterWarning::FaceTileRefused]);
+        }
+        if let Err(warnings) = self.validate_before_commit() {
+            let fatal = warnings.iter().any(|w| {
+                matches!(
+                    w,
+                    WriterWarning::NoTilesWritten
+                        | WriterWarning::DuplicateTileId(_)
+                        | WriterWarning::NonStandardCompression(_)
+                )
+            });
+            if fatal {
+                return Err(warnings);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Commit the archive, then read it back to check the header and a pseudo-random sample of
+    /// `VERIFY_SAMPLE_SIZE` tiles against what was written
+    #[cfg(feature = "std")]
+    pub fn commit_and_verify(&mut self, metadata: &Metadata) -> Result<CommitResult, VerifyError> {
+        let result = self.commit(metadata).map_err(VerifyError::Commit)?;
+        self.verify(&result)?;
+
+        Ok(result)
+    }
+
+    /// Read back a committed archive and compare it to what was written
+    #[cfg(feature = "std")]
+    fn verify(&mut self, result: &CommitResult) -> Result<(), VerifyError> {
+        let data = self
+            .data_writer
+            .data_manager()
+            .ok_or(VerifyError::Unreadable)?;
+        let mut reader = PMTilesReader::new(data, None);
+
+        let header = reader.get_header();
+        if header.is_s2 == !self.tile_entries.is_empty()
+            || header.n_addressed_tiles != self.addressed_tiles
+            || header.n_tile_contents != self.tile_contents
+            || header.metadata_length != result.metadata_size_bytes
+            || header.data_offset != S2_ROOT_SIZE as u64
+            || header.clustered != self.is_clustered()
+            || header.tile_compression != self.compression
+            || header.tile_type != self.tile_type
+        {
+            return Err(VerifyError::HeaderMismatch);
+        }
+
+        let offset_to_hash: BTreeMap<u64, [u8; 32]> =
+            self.hash_to_offset.iter().map(|(h, o)| (*o, *h)).collect();
+        let entries: Vec<(Option<Face>, Entry)> = STORES
+            .iter()
+            .zip(self.directories())
+            .flat_map(|(face, dir)| dir.entries.iter().map(|e| (*face, *e)))
+            .filter(|(_, e)| e.run_length > 0)
+            .collect();
+        // xorshift, so the same archive always checks the same tiles
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..VERIFY_SAMPLE_SIZE.min(self.addressed_tiles as usize) {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let (face, entry) = entries[(seed % entries.len() as u64) as usize];
+            let tile_id = entry.tile_id + (seed >> 32) % entry.run_length as u64;
+            let Tile { zoom, x, y } = Tile::from_id(tile_id);
+            let data = reader
+                .get_tile(face, zoom, x, y)
+                .ok_or(VerifyError::MissingTile { tile_id })?;
+            // tiles written without dedup or with `write_tile_raw` have no hash to compare
+            // against, so only their stored length is checked
+            let matches = match offset_to_hash.get(&entry.offset) {
+                Some(hash) => *hash == self.hasher.hash(&data),
+                None => data.len() == entry.length as usize,
+            };
+            if !matches {
+                return Err(VerifyError::TileDataMismatch { tile_id });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finish writing an archive that only stores metadata, like a sidecar file of layer schemas
+    /// and attribution. The result is a valid archive with zero tiles and an empty root directory.
+    pub fn write_metadata_only(
+        &mut self,
+        metadata: &Metadata,
+    ) -> Result<CommitResult, Vec<WriterWarning>> {
+        if self.aborted {
+            return Err(vec![WriterWarning::Aborted]);
+        }
+        Ok(self.commit_wm(metadata))
+    }
+
+    /// Compute everything a commit would write (header, root and leaf directories, metadata)
+    /// without writing anything, so sizes can be inspected first. Apply it with `WritePlan::apply`.
+    pub fn prepare_commit(&mut self, metadata: &Metadata) -> WritePlan {
+        if !self.tile_entries.is_empty() {
+            self.prepare_wm(metadata)
+        } else {
+            self.prepare_s2(metadata)
+        }
+    }
+
+    /// Write the whole archive (tile data, leaf directories, then the header, root directories
+    /// and metadata) to `writer`, which should start empty, rather than to this writer's own
+    /// data writer. Nothing is written to the latter, so the same tiles can be committed to
+    /// several destinations, this writer included. Like `commit_wm`, nothing is validated.
+    /// Returns `None`, having written nothing, if this writer's tile data can not be read back
+    pub fn commit_to_writer(
+        &mut self,
+        writer: &mut dyn DataWriter,
+        metadata: &Metadata,
+    ) -> Option<CommitResult> {
+        let mut data = self.data_writer.data_manager()?;
+        let plan = self.prepare_commit(metadata);
+        // the tile data follows the space left for the head, copied in chunks
+        writer.append_data(&vec![0u8; S2_ROOT_SIZE]);
+        let end = S2_ROOT_SIZE as u64 + self.offset;
+        let mut start = S2_ROOT_SIZE as u64;
+        while start < end {
+            let length = COPY_CHUNK_SIZE.min(end - start);
+            writer.append_data(&data.get_range(start, length));
+            start += length;
+        }
+        plan.apply(writer);
+
+        Some(plan.result)
+    }
+
+    /// Finish writing by building the header with root and leaf directories
+    pub fn commit_wm(&mut self, metadata: &Metadata) -> CommitResult {
+        let plan = self.prepare_wm(metadata);
+        self.apply_plan(plan)
+    }
+
+    /// Finish writing by building the header with root and leaf directories
+    pub fn commit_s2(&mut self, metadata: &Metadata) -> CommitResult {
+        let plan = self.prepare_s2(metadata);
+        self.apply_plan(plan)
+    }
+
+    /// Write a plan to the data writer
+    fn apply_plan(&mut self, plan: WritePlan) -> CommitResult {
+        self.offset += plan.leaves.len() as u64;
+        plan.apply(self.data_writer.as_mut());
+        self.report_progress();
+        plan.result
+    }
+
+    /// Serialize the metadata along with the fields of `set_extra_metadata`
+    fn metadata_buffer(&self, metadata: &Metadata) -> Vec<u8> {
+        if self.extra_metadata.is_empty() {
+            return serde_json::to_vec(metadata).unwrap();
+        }
+        let mut value = serde_json::to_value(metadata).unwrap();
+        if let serde_json::Value::Object(fields) = &mut value {
+            fields.extend(self.extra_metadata.clone());
+        }
+        serde_json::to_vec(&value).unwrap()
+    }
+
+    /// Plan the commit of a WM archive
+    fn prepare_wm(&mut self, metadata: &Metadata) -> WritePlan {
+        // build metadata
+        let meta_buffer = self.metadata_buffer(metadata);
+
+        // optimize directories
+        let od: OptimizedDirectory = OptimizedDirectory::optimize_directories(
+            &mut self.tile_entries,
+            ROOT_SIZE - S2_HEADER_SIZE_BYTES - meta_buffer.len(),
+        );
+        let OptimizedDirectory {
+            root_bytes,
+            leaves_bytes,
+            ..
+        } = od;
+        let (head, result) = self.wm_head(root_bytes, leaves_bytes.len() as u64, meta_buffer);
+
+        WritePlan {
+            head,
+            leaves: leaves_bytes,
+            result,
+        }
+    }
+
+    /// Build the header, root directory and metadata of a WM archive whose leaf directories
+    /// are appended after the tile data written so far
+    fn wm_head(
+        &self,
+        root_bytes: Vec<u8>,
+        leaf_directory_length: u64,
+        meta_buffer: Vec<u8>,
+    ) -> (Vec<u8>, CommitResult) {
+        // build header data
+        let root_directory_offset = S2_HEADER_SIZE_BYTES as u64;
+        let root_directory_length = root_bytes.len() as u64;
+        let metadata_offset = root_directory_offset + root_directory_length;
+        let metadata_length = meta_buffer.len() as u64;
+        let leaf_directory_offset = self.offset + S2_ROOT_SIZE as u64;
+        let data_length = self.offset;
+        let offset = self.offset + leaf_directory_length;
+
+        // to make writing fasters
+        let min_zoom = self
+            .tile_entries
+            .first()
+            .map_or(0, |e| Tile::from_id(e.tile_id).zoom);
+        let max_zoom = self
+            .tile_entries
+            .last()
+            .map_or(0, |e| Tile::from_id(e.tile_id).zoom);
+
+        // build header
+        let header = Header {
+            version: 3,
+            root_directory_offset,
+            root_directory_length,
+            metadata_offset,
+            metadata_length,
+            leaf_directory_offset,
+            leaf_directory_length,
+            data_offset: S2_ROOT_SIZE as u64,
+            data_length: offset,
+            n_addressed_tiles: self.addressed_tiles,
+            n_tile_entries: self.tile_entries.len() as u64,
+            n_tile_contents: self.tile_contents,
+            clustered: self.is_clustered(),
+            internal_compression: Compression::None,
+            tile_compression: self.compression,
+            tile_type: self.tile_type,
+            min_longitude: self.bounds[0],
+            min_latitude: self.bounds[1],
+            max_longitude: self.bounds[2],
+            max_latitude: self.bounds[3],
+            min_zoom,
+            max_zoom,
+            ..Default::default()
+        };
+        let serialized_header = header.to_bytes().take();
+
+        // header, root directory and metadata
+        let mut head = serialized_header;
+        head.resize(root_directory_offset as usize, 0);
+        head.extend(root_bytes);
+        head.extend(meta_buffer);
+
+        let result = self.commit_result(
+            root_directory_length,
+            leaf_directory_length,
+            metadata_length,
+            data_length,
+        );
+        (head, result)
+    }
+
+    /// Plan the commit of a S2 archive
+    fn prepare_s2(&mut self, metadata: &Metadata) -> WritePlan {
+        // build metadata
+        let meta_buffer = self.metadata_buffer(metadata);
+
+        // optimize directories
+        let od = OptimizedDirectory::optimize_directories(
+            self.s2tile_entries.get_mut(Face::Face0),
+            ROOT_SIZE - S2_HEADER_SIZE_BYTES - meta_buffer.len(),
         );
         let OptimizedDirectory {
             root_bytes,
@@ -416,33 +1726,34 @@ impl PMTilesWriter {
         let metadata_offset = root_directory_offset5 + root_directory_length5;
         let metadata_length = meta_buffer.len() as u64;
         // leafs
-        let leaf_directory_offset = self.offset + S2_ROOT_SIZE as u64;
+        let data_length = self.offset;
+        let mut offset = self.offset;
+        let mut leaves = Vec::new();
+        let leaf_directory_offset = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length = leaves_bytes.len() as u64;
-        self.offset += leaf_directory_length;
-        self.data_writer.append_data(&leaves_bytes);
-        let leaf_directory_offset1 = self.offset + S2_ROOT_SIZE as u64;
+        offset += leaf_directory_length;
+        leaves.extend(leaves_bytes);
+        let leaf_directory_offset1 = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length1 = leaves_bytes1.len() as u64;
-        self.offset += leaf_directory_length1;
-        self.data_writer.append_data(&leaves_bytes1);
-        let leaf_directory_offset2 = self.offset + S2_ROOT_SIZE as u64;
+        offset += leaf_directory_length1;
+        leaves.extend(leaves_bytes1);
+        let leaf_directory_offset2 = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length2 = leaves_bytes2.len() as u64;
-        self.offset += leaf_directory_length2;
-        self.data_writer.append_data(&leaves_bytes2);
-        let leaf_directory_offset3 = self.offset + S2_ROOT_SIZE as u64;
+        offset += leaf_directory_length2;
+        leaves.extend(leaves_bytes2);
+        let leaf_directory_offset3 = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length3 = leaves_bytes3.len() as u64;
-        self.offset += leaf_directory_length3;
-        self.data_writer.append_data(&leaves_bytes3);
-        let leaf_directory_offset4 = self.offset + S2_ROOT_SIZE as u64;
+        offset += leaf_directory_length3;
+        leaves.extend(leaves_bytes3);
+        let leaf_directory_offset4 = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length4 = leaves_bytes4.len() as u64;
-        self.offset += leaf_directory_length4;
-        self.data_writer.append_data(&leaves_bytes4);
-        let leaf_directory_offset5 = self.offset + S2_ROOT_SIZE as u64;
+        offset += leaf_directory_length4;
+        leaves.extend(leaves_bytes4);
+        let leaf_directory_offset5 = offset + S2_ROOT_SIZE as u64;
         let leaf_directory_length5 = leaves_bytes5.len() as u64;
-        self.offset += leaf_directory_length5;
-        self.data_writer.append_data(&leaves_bytes5);
+        offset += leaf_directory_length5;
+        leaves.extend(leaves_bytes5);
 
-        // write data
-        self.data_writer.append_data(&leaves_bytes);
         // build header
         let header = S2Header {
             is_s2: true,
@@ -474,49 +1785,401 @@ impl PMTilesWriter {
             leaf_directory_offset5,
             leaf_directory_length5,
             data_offset: S2_ROOT_SIZE as u64,
-            data_length: self.offset,
+            data_length: offset,
             n_addressed_tiles: self.addressed_tiles,
             n_tile_entries: self.tile_entries.len() as u64,
-            n_tile_contents: self.hash_to_offset.len() as u64,
-            clustered: self.clustered,
+            n_tile_contents: self.tile_contents,
+            clustered: self.is_clustered(),
             internal_compression: Compression::None,
             tile_compression: self.compression,
-            tile_type: TileType::Unknown,
+            tile_type: self.tile_type,
+            min_longitude: self.bounds[0],
+            min_latitude: self.bounds[1],
+            max_longitude: self.bounds[2],
+            max_latitude: self.bounds[3],
             ..Default::default()
         };
         let serialized_header = header.to_bytes().take();
 
-        // write header
-        self.data_writer.write_data(&serialized_header, 0);
-        self.data_writer
-            .write_data(&root_bytes, root_directory_offset);
-        self.data_writer
-            .write_data(&root_bytes1, root_directory_offset1);
-        self.data_writer
-            .write_data(&root_bytes2, root_directory_offset2);
-        self.data_writer
-            .write_data(&root_bytes3, root_directory_offset3);
-        self.data_writer
-            .write_data(&root_bytes4, root_directory_offset4);
-        self.data_writer
-            .write_data(&root_bytes5, root_directory_offset5);
-        self.data_writer.write_data(&meta_buffer, metadata_offset);
+        // header, root directories and metadata
+        let mut head = serialized_header;
+        head.resize(root_directory_offset as usize, 0);
+        head.extend(root_bytes);
+        head.extend(root_bytes1);
+        head.extend(root_bytes2);
+        head.extend(root_bytes3);
+        head.extend(root_bytes4);
+        head.extend(root_bytes5);
+        head.extend(meta_buffer);
+
+        let result = self.commit_result(
+            root_directory_length
+                + root_directory_length1
+                + root_directory_length2
+                + root_directory_length3
+                + root_directory_length4
+                + root_directory_length5,
+            leaf_directory_length
+                + leaf_directory_length1
+                + leaf_directory_length2
+                + leaf_directory_length3
+                + leaf_directory_length4
+                + leaf_directory_length5,
+            metadata_length,
+            data_length,
+        );
+        WritePlan {
+            head,
+            leaves,
+            result,
+        }
+    }
+
+    /// Build the result of a commit given the byte sizes of each section
+    fn commit_result(
+        &self,
+        root_size_bytes: u64,
+        leaf_size_bytes: u64,
+        metadata_size_bytes: u64,
+        data_size_bytes: u64,
+    ) -> CommitResult {
+        let unique_contents = self.tile_contents;
+        CommitResult {
+            root_size_bytes,
+            leaf_size_bytes,
+            metadata_size_bytes,
+            data_size_bytes,
+            total_bytes: S2_ROOT_SIZE as u64 + data_size_bytes + leaf_size_bytes,
+            tiles_written: self.addressed_tiles,
+            unique_contents,
+            dedup_ratio: if unique_contents == 0 {
+                0.0
+            } else {
+                self.addressed_tiles as f64 / unique_contents as f64
+            },
+            clustering_score: self.clustering_score(),
+        }
+    }
+}
+
+/// A tile sent by a `FaceWriter`: its face, tile ID and data
+#[cfg(feature = "std")]
+type FaceTile = (Face, u64, Vec<u8>);
+
+/// Writes the tiles of one S2 face from any thread, see `PMTilesWriter::split_by_face`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct FaceWriter {
+    face: Face,
+    sender: Sender<FaceTile>,
+}
+#[cfg(feature = "std")]
+impl FaceWriter {
+    /// The face this writer writes to
+    pub fn face(&self) -> Face {
+        self.face
+    }
+
+    /// Send a tile to the writer, it is written when the writer collects it.
+    /// Only fails if the writer was aborted or dropped
+    pub fn write_tile_xyz(&self, zoom: u8, x: u64, y: u64, data: &[u8]) -> Result<(), WriteError> {
+        let tile_id = Tile::new(zoom, x, y).to_id();
+        self.sender
+            .send((self.face, tile_id, data.to_vec()))
+            .map_err(|_| WriteError::Aborted)
+    }
+}
+
+/// A `PMTilesWriter` shared between threads. Every call locks the writer while it runs, so
+/// tiles are written in whichever order the threads get to it. For S2 archives,
+/// `PMTilesWriter::split_by_face` avoids waiting on the lock
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct PMTilesWriterShared(pub Arc<Mutex<PMTilesWriter>>);
+#[cfg(feature = "std")]
+impl PMTilesWriterShared {
+    /// Share a writer
+    pub fn new(writer: PMTilesWriter) -> Self {
+        PMTilesWriterShared(Arc::new(Mutex::new(writer)))
+    }
+
+    /// Lock the writer for anything else than writing tiles or committing.
+    /// Panics if a thread panicked while holding the lock
+    pub fn lock(&self) -> MutexGuard<'_, PMTilesWriter> {
+        self.0
+            .lock()
+            .expect("a thread panicked while using the writer")
+    }
+
+    /// Write a WM tile, see `PMTilesWriter::write_tile_xyz`
+    pub fn write_tile_xyz(&self, zoom: u8, x: u64, y: u64, data: &[u8]) -> Result<(), WriteError> {
+        self.lock().write_tile_xyz(zoom, x, y, data)
+    }
+
+    /// Write an S2 tile, see `PMTilesWriter::write_tile_s2`
+    pub fn write_tile_s2(
+        &self,
+        face: Face,
+        zoom: u8,
+        x: u64,
+        y: u64,
+        data: &[u8],
+    ) -> Result<(), WriteError> {
+        self.lock().write_tile_s2(face, zoom, x, y, data)
+    }
+
+    /// Commit the archive, see `PMTilesWriter::commit`
+    pub fn commit(&self, metadata: &Metadata) -> Result<CommitResult, Vec<WriterWarning>> {
+        self.lock().commit(metadata)
+    }
+
+    /// Get the writer back, None if it is still shared with another clone
+    pub fn into_inner(self) -> Option<PMTilesWriter> {
+        Arc::into_inner(self.0).map(|writer| {
+            writer
+                .into_inner()
+                .expect("a thread panicked while using the writer")
+        })
+    }
+}
+
+/// A `PMTilesWriter` writing to a temporary file, moved to its final path on `commit`.
+/// Use it like the writer it wraps, see `PMTilesWriter::begin_transaction`
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct WriteTransaction {
+    /// only None once committed
+    writer: Option<PMTilesWriter>,
+    path: String,
+    temp_path: String,
+}
+#[cfg(feature = "std")]
+impl WriteTransaction {
+    /// The path the archive is written to until committed
+    pub fn temp_path(&self) -> &str {
+        &self.temp_path
+    }
+
+    /// Commit the archive (see `PMTilesWriter::commit`) and atomically rename the temporary
+    /// file to the final path. On failure the temporary file is deleted
+    pub fn commit(mut self, metadata: &Metadata) -> Result<CommitResult, TransactionError> {
+        let mut writer = self
+            .writer
+            .take()
+            .expect("the transaction is not committed");
+        let result = writer.commit(metadata);
+        // close the file before moving it
+        drop(writer);
+        let result = result.map_err(TransactionError::Commit)?;
+        std::fs::rename(&self.temp_path, &self.path)
+            .map_err(|e| TransactionError::Rename(e.kind()))?;
+        Ok(result)
+    }
+}
+#[cfg(feature = "std")]
+impl core::ops::Deref for WriteTransaction {
+    type Target = PMTilesWriter;
+
+    fn deref(&self) -> &PMTilesWriter {
+        self.writer
+            .as_ref()
+            .expect("the transaction is not committed")
+    }
+}
+#[cfg(feature = "std")]
+impl core::ops::DerefMut for WriteTransaction {
+    fn deref_mut(&mut self) -> &mut PMTilesWriter {
+        self.writer
+            .as_mut()
+            .expect("the transaction is not committed")
+    }
+}
+#[cfg(feature = "std")]
+impl Drop for WriteTransaction {
+    fn drop(&mut self) {
+        // close the file, then delete it unless it was renamed by `commit`
+        self.writer = None;
+        let _ = std::fs::remove_file(&self.temp_path);
     }
 }
 
+/// The WM directory followed by the six S2 faces
+const STORES: [Option<Face>; 7] = [
+    None,
+    Some(Face::Face0),
+    Some(Face::Face1),
+    Some(Face::Face2),
+    Some(Face::Face3),
+    Some(Face::Face4),
+    Some(Face::Face5),
+];
+
+/// A rough guess of the metadata size used when estimating the output size
+const ESTIMATED_METADATA_SIZE: usize = 1_024;
+
+/// The most tile data read at once by `PMTilesWriter::commit_to_writer`
+const COPY_CHUNK_SIZE: u64 = 1 << 20;
+
+#[cfg(all(feature = "sha2", not(feature = "fast-hash")))]
 fn hash_data(data: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(data);
     hasher.finalize().into()
 }
 
+#[cfg(not(all(feature = "sha2", not(feature = "fast-hash"))))]
+fn hash_data(data: &[u8]) -> [u8; 32] {
+    fast_hash_data(data)
+}
+
+/// The 128 bit XXH3 of the data, zero padded to 32 bytes
+fn fast_hash_data(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash[..16].copy_from_slice(&xxh3_128(data).to_le_bytes());
+    hash
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::reader::{FileManager, LocalManager, PMTilesReader};
+    use crate::reader::{FileManager, LocalManager, PMTilesReader, ReadError};
     use s2_tilejson::Metadata;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_build_root_leaves_with_overlap() {
+        let leaf_size = 4;
+        // every entry is a run of leaf_size / 2 tiles, so each leaf ends mid-way into a long run
+        let directory = Directory::new(
+            (0..12)
+                .map(|i| Entry::new(i * 2, i * 10, 10, leaf_size as u32 / 2))
+                .collect(),
+        );
+        let find = |od: &OptimizedDirectory, tile_id: u64| -> Option<Entry> {
+            let root = Directory::from_buffer(&mut od.root_bytes.as_slice().into());
+            let leaf = crate::find_tile(&root.entries, tile_id)?;
+            let start = leaf.offset as usize;
+            let end = start + leaf.length as usize;
+            let leaf_dir = Directory::from_buffer(&mut od.leaves_bytes[start..end].into());
+            crate::find_tile(&leaf_dir.entries, tile_id)
+        };
+
+        let plain = OptimizedDirectory::build_root_leaves(&directory, leaf_size);
+        let split = OptimizedDirectory::build_root_leaves_with_overlap(&directory, leaf_size, 25.);
+        assert_eq!(plain.num_leaves, 3);
+        // each split adds an entry, which pushes the last one into a fourth leaf
+        assert_eq!(split.num_leaves, 4);
+        assert!(split.root_bytes.len() > plain.root_bytes.len());
+
+        // both layouts resolve every tile to the same data
+        for tile_id in 0..24 {
+            let expected = directory.entries[tile_id as usize / 2];
+            let plain_entry = find(&plain, tile_id).unwrap();
+            let split_entry = find(&split, tile_id).unwrap();
+            assert_eq!(plain_entry, expected);
+            assert_eq!(split_entry.offset, expected.offset);
+            assert_eq!(split_entry.length, expected.length);
+        }
+        assert_eq!(find(&plain, 24), None);
+        assert_eq!(find(&split, 24), None);
+
+        // the tail of the run ending the first leaf is now the first entry of the second leaf
+        let root = Directory::from_buffer(&mut split.root_bytes.as_slice().into());
+        assert_eq!(root.entries[1].tile_id, 7);
+        assert_eq!(find(&split, 7).unwrap(), Entry::new(7, 30, 10, 1));
+
+        // a threshold above the run length leaves the layout untouched
+        let unsplit =
+            OptimizedDirectory::build_root_leaves_with_overlap(&directory, leaf_size, 75.);
+        assert_eq!(unsplit.root_bytes, plain.root_bytes);
+        assert_eq!(unsplit.leaves_bytes, plain.leaves_bytes);
+    }
+
+    #[test]
+    fn test_dir_stats() {
+        let directory = Directory::new((0..10_000).map(|i| Entry::new(i, i * 10, 10, 1)).collect());
+        let od = OptimizedDirectory::build_root_leaves(&directory, 4_096);
+        let stats = od.stats();
+        assert_eq!(
+            stats,
+            DirStats {
+                num_leaves: 3,
+                max_entries_per_leaf: 4_096,
+                min_entries_per_leaf: 1_808,
+                avg_entries_per_leaf: 10_000.0 / 3.0,
+                root_size_bytes: od.root_bytes.len(),
+                total_leaf_size_bytes: od.leaves_bytes.len(),
+            }
+        );
+        assert_eq!(
+            stats.print_summary(),
+            format!(
+                "root: {} B, leaves: 3 ({} B), entries per leaf: min 1808, max 4096, avg 3333.3",
+                od.root_bytes.len(),
+                od.leaves_bytes.len()
+            )
+        );
+
+        // no leaves
+        let mut directory = Directory::new(vec![Entry::new(0, 0, 10, 1)]);
+        let od = OptimizedDirectory::optimize_directories(&mut directory, 16_384);
+        assert_eq!(
+            od.stats(),
+            DirStats {
+                root_size_bytes: 5,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.pmtiles");
+        let path = path.to_string_lossy().into_owned();
+        let metadata = Metadata {
+            name: "transaction".into(),
+            ..Default::default()
+        };
+
+        let mut transaction = PMTilesWriter::begin_transaction(&path, Compression::None).unwrap();
+        assert_eq!(transaction.temp_path(), format!("{path}.tmp"));
+        transaction.set_tile_type(TileType::Pbf);
+        transaction.set_bounds(-180.0, -85.0, 180.0, 85.0);
+        transaction.write_tile_xyz(0, 0, 0, b"zero").unwrap();
+        transaction.write_tile_xyz(1, 1, 0, b"one").unwrap();
+        // nothing appears at the final path until committed
+        assert!(std::path::Path::new(&format!("{path}.tmp")).exists());
+        assert!(!std::path::Path::new(&path).exists());
+        let result = transaction.commit(&metadata).unwrap();
+        assert_eq!(result.tiles_written, 2);
+        assert!(!std::path::Path::new(&format!("{path}.tmp")).exists());
+
+        let mut reader = PMTilesReader::new(FileManager::new(&path).unwrap(), None);
+        assert_eq!(reader.get_metadata(), &metadata);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), Some(b"zero".to_vec()));
+        assert_eq!(reader.get_tile_zxy(1, 1, 0), Some(b"one".to_vec()));
+
+        // dropping without committing deletes the temporary file and keeps the committed one
+        let mut transaction = PMTilesWriter::begin_transaction(&path, Compression::None).unwrap();
+        transaction.write_tile_xyz(0, 0, 0, b"replaced").unwrap();
+        drop(transaction);
+        assert!(!std::path::Path::new(&format!("{path}.tmp")).exists());
+        let mut reader = PMTilesReader::new(FileManager::new(&path).unwrap(), None);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), Some(b"zero".to_vec()));
+
+        // a failed commit cleans up too
+        let other = dir.path().join("empty.pmtiles");
+        let other = other.to_string_lossy().into_owned();
+        let transaction = PMTilesWriter::begin_transaction(&other, Compression::None).unwrap();
+        assert!(matches!(
+            transaction.commit(&metadata),
+            Err(TransactionError::Commit(_))
+        ));
+        assert!(!std::path::Path::new(&format!("{other}.tmp")).exists());
+        assert!(!std::path::Path::new(&other).exists());
+    }
+
     #[test]
     fn test_file_writer_wm() {
         let temp_file = NamedTempFile::new().expect("Failed to create temporary file");
@@ -528,11 +2191,28 @@ mod tests {
         // setup data
         let tmp_str = "hello world";
         // write data in tile
-        pmtiles_writer.write_tile_xyz(0, 0, 0, tmp_str.as_bytes());
+        pmtiles_writer
+            .write_tile_xyz(0, 0, 0, tmp_str.as_bytes())
+            .unwrap();
+        assert_eq!(pmtiles_writer.estimate_output_size(), 98_315);
         // finish
-        pmtiles_writer.commit(&Metadata::default());
+        let result = pmtiles_writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(
+            result,
+            CommitResult {
+                root_size_bytes: 5,
+                leaf_size_bytes: 0,
+                metadata_size_bytes: 417,
+                data_size_bytes: 11,
+                total_bytes: 98_315,
+                tiles_written: 1,
+                unique_contents: 1,
+                dedup_ratio: 1.0,
+                clustering_score: 1.0,
+            }
+        );
 
-        let mut reader = PMTilesReader::new(Box::new(FileManager::new(&file_path).unwrap()), None);
+        let mut reader = PMTilesReader::new(FileManager::new(&file_path).unwrap(), None);
 
         let header = reader.get_header();
         assert_eq!(
@@ -551,6 +2231,7 @@ mod tests {
                 n_addressed_tiles: 1,
                 n_tile_entries: 1,
                 n_tile_contents: 1,
+                clustered: true,
                 tile_type: TileType::Unknown,
                 ..Default::default()
             }
@@ -573,14 +2254,32 @@ mod tests {
         // setup data
         let tmp_str = "hello world";
         // write data in tile
-        pmtiles_writer.write_tile_s2(Face::Face0, 0, 0, 0, tmp_str.as_bytes());
-        pmtiles_writer.write_tile_s2(Face::Face3, 2, 1, 1, tmp_str.as_bytes());
+        pmtiles_writer
+            .write_tile_s2(Face::Face0, 0, 0, 0, tmp_str.as_bytes())
+            .unwrap();
+        pmtiles_writer
+            .write_tile_s2(Face::Face3, 2, 1, 1, tmp_str.as_bytes())
+            .unwrap();
         // finish
-        pmtiles_writer.commit(&Metadata::default());
+        let result = pmtiles_writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(
+            result,
+            CommitResult {
+                root_size_bytes: 14,
+                leaf_size_bytes: 0,
+                metadata_size_bytes: 417,
+                data_size_bytes: 11,
+                total_bytes: 98_315,
+                tiles_written: 2,
+                unique_contents: 1,
+                dedup_ratio: 2.0,
+                clustering_score: 1.0,
+            }
+        );
 
         let pmtiles_data = pmtiles_writer.take();
 
-        let mut reader = PMTilesReader::new(Box::new(LocalManager::new(pmtiles_data)), None);
+        let mut reader = PMTilesReader::new(LocalManager::new(pmtiles_data), None);
 
         let header = reader.get_header();
         assert_eq!(
@@ -599,7 +2298,7 @@ mod tests {
                 n_addressed_tiles: 2,
                 n_tile_entries: 0,
                 n_tile_contents: 1,
-                clustered: false,
+                clustered: true,
                 min_zoom: 0,
                 max_zoom: 0,
                 min_longitude: 0.0,
@@ -654,16 +2353,29 @@ mod tests {
             for x in 0..(1 << zoom) {
                 for y in 0..(1 << zoom) {
                     let tmp_str = format!("{}-{}-{}", zoom, x, y);
-                    pmtiles_writer.write_tile_xyz(zoom, x, y, tmp_str.as_bytes());
+                    pmtiles_writer
+                        .write_tile_xyz(zoom, x, y, tmp_str.as_bytes())
+                        .unwrap();
                 }
             }
         }
         // finish
-        pmtiles_writer.commit(&Metadata::default());
+        let estimate = pmtiles_writer.estimate_output_size();
+        let result = pmtiles_writer.commit(&Metadata::default()).unwrap();
+        assert!(estimate.abs_diff(result.total_bytes) * 10 < result.total_bytes);
+        assert_eq!(result.tiles_written, 21_845);
+        assert_eq!(result.unique_contents, 21_845);
+        assert_eq!(result.dedup_ratio, 1.0);
+        assert!(result.leaf_size_bytes > 0);
+        assert_eq!(
+            result.total_bytes,
+            S2_ROOT_SIZE as u64 + result.data_size_bytes + result.leaf_size_bytes
+        );
 
         let pmtiles_data = pmtiles_writer.take();
+        assert_eq!(pmtiles_data.len() as u64, result.total_bytes);
 
-        let mut reader = PMTilesReader::new(Box::new(LocalManager::new(pmtiles_data)), None);
+        let mut reader = PMTilesReader::new(LocalManager::new(pmtiles_data), None);
 
         let zoom = 5;
         let x = 12;
@@ -673,4 +2385,1451 @@ mod tests {
         let tmp_str = format!("{}-{}-{}", zoom, x, y);
         assert_eq!(tile, tmp_str.as_bytes());
     }
+
+    #[test]
+    fn test_write_tile_s2_batch() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_clustering_enforcement(ClusteringMode::Enforce);
+        writer.write_tile_s2(Face::Face2, 0, 0, 0, b"root").unwrap();
+        // every zoom 4 tile, shuffled, with every fourth tile sharing the same data
+        let mut tiles: Vec<(u8, u64, u64, Vec<u8>)> = (0..256u64)
+            .map(|i| {
+                let (x, y) = (i % 16, i / 16);
+                let data = if i % 4 == 0 {
+                    b"ocean".to_vec()
+                } else {
+                    vec![x as u8, y as u8]
+                };
+                (4, x, y, data)
+            })
+            .collect();
+        let mut seed = 42u64;
+        for i in (1..tiles.len()).rev() {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            tiles.swap(i, (seed % (i as u64 + 1)) as usize);
+        }
+        let batch: Vec<(u8, u64, u64, &[u8])> = tiles
+            .iter()
+            .map(|(z, x, y, data)| (*z, *x, *y, data.as_slice()))
+            .collect();
+        writer.write_tile_s2_batch(Face::Face2, &batch).unwrap();
+        assert!(writer.clustered);
+        assert_eq!(writer.addressed_tiles, 257);
+        assert_eq!(writer.tile_contents, 1 + 192 + 1);
+
+        // a batch starting before the last written tile is out of order
+        assert_eq!(
+            writer.write_tile_s2_batch(Face::Face2, &[(1, 0, 0, b"late")]),
+            Err(WriteError::OutOfOrder {
+                expected: crate::TileRange::for_zoom(4).max_tile_id,
+                got: Tile::new(1, 0, 0).to_id(),
+            })
+        );
+        writer.commit(&Metadata::default()).unwrap();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(reader.get_header().clustered);
+        assert_eq!(
+            reader.get_tile_s2(Face::Face2, 0, 0, 0),
+            Some(b"root".to_vec())
+        );
+        for (zoom, x, y, data) in tiles {
+            assert_eq!(reader.get_tile_s2(Face::Face2, zoom, x, y), Some(data));
+        }
+    }
+
+    #[test]
+    fn test_validate_before_commit() {
+        // empty writer
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert_eq!(
+            writer.validate_before_commit(),
+            Err(vec![
+                WriterWarning::NoTilesWritten,
+                WriterWarning::TileTypeUnknown,
+                WriterWarning::MissingBounds,
+                WriterWarning::MissingZoomRange,
+            ])
+        );
+        assert!(writer.commit(&Metadata::default()).is_err());
+        assert_eq!(writer.take().len(), S2_ROOT_SIZE);
+
+        // a single zoom 0 tile written twice
+        writer.write_tile_xyz(0, 0, 0, b"a").unwrap();
+        writer.write_tile_xyz(0, 0, 0, b"b").unwrap();
+        assert_eq!(
+            writer.validate_before_commit(),
+            Err(vec![
+                WriterWarning::TileTypeUnknown,
+                WriterWarning::MissingBounds,
+                WriterWarning::DuplicateTileId(0),
+                WriterWarning::MissingZoomRange,
+            ])
+        );
+        assert!(writer.commit(&Metadata::default()).is_err());
+
+        // a tile written again inside an earlier run
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Png);
+        writer.set_bounds(-180.0, -85.0, 180.0, 85.0);
+        for tile_id in 1..5 {
+            writer.write_tile(tile_id, b"ocean", None).unwrap();
+        }
+        writer.write_tile(0, b"land", None).unwrap();
+        assert_eq!(writer.tile_entries.len(), 2);
+        assert_eq!(writer.addressed_tiles, 5);
+        assert_eq!(writer.validate_before_commit(), Ok(()));
+        writer.write_tile(3, b"land", None).unwrap();
+        assert_eq!(
+            writer.validate_before_commit(),
+            Err(vec![WriterWarning::DuplicateTileId(3)])
+        );
+        assert_eq!(
+            writer.commit(&Metadata::default()),
+            Err(vec![WriterWarning::DuplicateTileId(3)])
+        );
+
+        // a valid archive
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Png);
+        writer.set_bounds(-180.0, -85.0, 180.0, 85.0);
+        writer.write_tile_xyz(0, 0, 0, b"a").unwrap();
+        writer.write_tile_xyz(1, 0, 0, b"b").unwrap();
+        assert_eq!(writer.validate_before_commit(), Ok(()));
+        writer.commit(&Metadata::default()).unwrap();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert_eq!(header.tile_type, TileType::Png);
+        assert_eq!(header.min_longitude, -180.0);
+        assert_eq!(header.max_latitude, 85.0);
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 1));
+
+        // non-standard compression
+        let mut writer = PMTilesWriter::new(Compression::Lz4, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Png);
+        writer.set_bounds(-180.0, -85.0, 180.0, 85.0);
+        writer.write_tile_xyz(1, 0, 0, b"a").unwrap();
+        assert_eq!(
+            writer.validate_before_commit(),
+            Err(vec![WriterWarning::NonStandardCompression(
+                Compression::Lz4
+            )])
+        );
+        // which is only supported for reading
+        assert_eq!(
+            writer.commit(&Metadata::default()),
+            Err(vec![WriterWarning::NonStandardCompression(
+                Compression::Lz4
+            )])
+        );
+    }
+
+    #[test]
+    fn test_clustering_mode() {
+        let build = |mode: ClusteringMode, ids: &[u64]| {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+                .with_clustering_enforcement(mode);
+            let results: Vec<Result<(), WriteError>> = ids
+                .iter()
+                .map(|id| writer.write_tile(*id, format!("{}", id).as_bytes(), None))
+                .collect();
+            writer.commit(&Metadata::default()).unwrap();
+            let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+            (results, reader.get_header().clustered)
+        };
+
+        // auto detect
+        assert_eq!(
+            build(ClusteringMode::AutoDetect, &[0, 1, 2]),
+            (vec![Ok(()); 3], true)
+        );
+        assert_eq!(
+            build(ClusteringMode::AutoDetect, &[0, 2, 1]),
+            (vec![Ok(()); 3], false)
+        );
+        // enforce
+        assert_eq!(
+            build(ClusteringMode::Enforce, &[0, 1, 2]),
+            (vec![Ok(()); 3], true)
+        );
+        assert_eq!(
+            build(ClusteringMode::Enforce, &[0, 2, 1, 3]),
+            (
+                vec![
+                    Ok(()),
+                    Ok(()),
+                    Err(WriteError::OutOfOrder {
+                        expected: 2,
+                        got: 1
+                    }),
+                    Ok(())
+                ],
+                true
+            )
+        );
+        // disable
+        assert_eq!(
+            build(ClusteringMode::Disable, &[0, 1, 2]),
+            (vec![Ok(()); 3], false)
+        );
+    }
+
+    #[test]
+    fn test_sort_and_recluster() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        for zoom in (0..4).rev() {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let tmp_str = format!("{}-{}-{}", zoom, x, y);
+                    writer
+                        .write_tile_xyz(zoom, x, y, tmp_str.as_bytes())
+                        .unwrap();
+                }
+            }
+        }
+        // repeated data merges into a run once sorted
+        let (first, second) = (Tile::from_zoom_pos(4, 0), Tile::from_zoom_pos(4, 1));
+        writer.write_tile(second.to_id(), b"same", None).unwrap();
+        writer.write_tile(first.to_id(), b"same", None).unwrap();
+        writer.sort_and_recluster();
+        assert_eq!(writer.tile_entries.len(), 85 + 1);
+        writer.commit(&Metadata::default()).unwrap();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 4));
+        assert!(!header.clustered);
+        for zoom in 0..4 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let tmp_str = format!("{}-{}-{}", zoom, x, y);
+                    assert_eq!(reader.get_tile_zxy(zoom, x, y).unwrap(), tmp_str.as_bytes());
+                }
+            }
+        }
+        assert_eq!(reader.get_tile_zxy(4, first.x, first.y).unwrap(), b"same");
+        assert_eq!(reader.get_tile_zxy(4, second.x, second.y).unwrap(), b"same");
+
+        // out of order tiles only pointing at earlier data are still clustered
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile(5, b"a", None).unwrap();
+        writer.write_tile(3, b"a", None).unwrap();
+        assert!(!writer.clustered);
+        writer.sort_and_recluster();
+        assert!(writer.clustered);
+    }
+
+    #[test]
+    fn test_clustering_score() {
+        let write = |tile_ids: &[u64]| {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            writer.set_tile_type(TileType::Pbf);
+            for tile_id in tile_ids {
+                writer
+                    .write_tile(*tile_id, format!("{tile_id}").as_bytes(), None)
+                    .unwrap();
+            }
+            writer
+        };
+
+        // in order
+        let mut writer = write(&(0..100).collect::<Vec<_>>());
+        assert_eq!(writer.clustering_score(), 1.0);
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.clustering_score, 1.0);
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(reader.get_header().clustered);
+
+        // reversed
+        let mut writer = write(&(0..100).rev().collect::<Vec<_>>());
+        assert_eq!(writer.clustering_score(), 0.0);
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.clustering_score, 0.0);
+
+        // 19 of 20 pairs in order
+        let mut tile_ids: Vec<u64> = (0..21).collect();
+        tile_ids.swap(19, 20);
+        let mut writer = write(&tile_ids);
+        assert_eq!(writer.clustering_score(), 19.0 / 20.0);
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.clustering_score, 0.95);
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(!reader.get_header().clustered);
+
+        // alternating
+        let writer = write(&[10, 0, 11, 1, 12, 2, 13]);
+        assert_eq!(writer.clustering_score(), 0.5);
+
+        // nothing or a single tile to compare
+        assert_eq!(write(&[]).clustering_score(), 1.0);
+        assert_eq!(write(&[7]).clustering_score(), 1.0);
+
+        // pairs are only counted within a directory
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_s2(Face::Face1, 1, 1, 1, b"a").unwrap();
+        writer.write_tile_s2(Face::Face0, 1, 0, 0, b"b").unwrap();
+        writer.write_tile_s2(Face::Face1, 1, 0, 0, b"c").unwrap();
+        assert_eq!(writer.clustering_score(), 0.0);
+
+        // rewriting the same tile is not ascending
+        let writer = write(&[0, 1, 1]);
+        assert_eq!(writer.clustering_score(), 0.5);
+        assert!(!writer.is_clustered());
+
+        // sorting orders every pair, but the data still follows the write order
+        let mut writer = write(&[2, 1, 0]);
+        writer.sort_and_recluster();
+        assert_eq!(writer.clustering_score(), 1.0);
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(!reader.get_header().clustered);
+    }
+
+    #[test]
+    fn test_current_size() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let file_path = temp_file.path().to_string_lossy().into_owned();
+        let writers: [Box<dyn DataWriter + Send>; 2] = [
+            Box::new(LocalWriter::new()),
+            Box::new(FileWriter::create(&file_path).unwrap()),
+        ];
+        for data_writer in writers {
+            assert_eq!(data_writer.current_size(), 0);
+            let mut writer = PMTilesWriter::new(Compression::None, data_writer);
+            assert_eq!(writer.data_writer.current_size(), S2_ROOT_SIZE as u64);
+            writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+            assert_eq!(writer.data_writer.current_size(), S2_ROOT_SIZE as u64 + 5);
+            writer.write_tile_xyz(1, 0, 0, b"world!").unwrap();
+            assert_eq!(writer.data_writer.current_size(), S2_ROOT_SIZE as u64 + 11);
+            // deduplicated tiles are not written again
+            writer.write_tile_xyz(1, 1, 0, b"hello").unwrap();
+            assert_eq!(writer.data_writer.current_size(), S2_ROOT_SIZE as u64 + 11);
+        }
+    }
+
+    #[test]
+    fn test_write_metadata_only() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        let metadata = Metadata {
+            name: "sidecar".into(),
+            description: "layer schemas and attribution".into(),
+            ..Default::default()
+        };
+        // a regular commit refuses to write an empty archive
+        assert_eq!(
+            writer.commit(&metadata).map(|_| ()),
+            Err(vec![
+                WriterWarning::NoTilesWritten,
+                WriterWarning::TileTypeUnknown,
+                WriterWarning::MissingBounds,
+                WriterWarning::MissingZoomRange,
+            ])
+        );
+        let result = writer.write_metadata_only(&metadata).unwrap();
+        assert_eq!(result.tiles_written, 0);
+        assert_eq!(result.unique_contents, 0);
+        assert_eq!(result.data_size_bytes, 0);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert!(!header.is_s2);
+        assert_eq!(header.n_addressed_tiles, 0);
+        assert_eq!(header.n_tile_entries, 0);
+        assert_eq!(header.n_tile_contents, 0);
+        assert_eq!(header.leaf_directory_length, 0);
+        assert_eq!(reader.get_metadata(), &metadata);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), None);
+        assert_eq!(reader.get_tile_zxy(5, 3, 7), None);
+        assert_eq!(reader.get_tile_s2(Face::Face2, 0, 0, 0), None);
+    }
+
+    #[test]
+    fn test_extra_metadata() {
+        let metadata = Metadata {
+            name: "roads".into(),
+            description: "before".into(),
+            ..Default::default()
+        };
+        for s2 in [false, true] {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            if s2 {
+                writer.write_tile_s2(Face::Face3, 0, 0, 0, b"tile").unwrap();
+            } else {
+                writer.write_tile_xyz(0, 0, 0, b"tile").unwrap();
+            }
+            assert!(writer.set_extra_metadata("generator", serde_json::json!("tiler 2.1")));
+            let counts = serde_json::json!({ "water": 3, "roads": 9 });
+            assert!(writer.set_extra_metadata("feature_counts", counts));
+            // fields of `Metadata` can not be overridden
+            let layers = serde_json::json!({ "water": 3, "roads": 9 });
+            assert!(!writer.set_extra_metadata("layers", layers));
+            assert!(!writer.set_extra_metadata("description", serde_json::json!("after")));
+            let result = if s2 {
+                writer.commit_s2(&metadata)
+            } else {
+                writer.commit_wm(&metadata)
+            };
+
+            let data = writer.take();
+            let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+            assert_eq!(
+                reader.get_header().metadata_length,
+                result.metadata_size_bytes
+            );
+            let fields = reader.get_all_metadata_fields().unwrap();
+            assert_eq!(fields["name"], "roads");
+            assert_eq!(fields["generator"], "tiler 2.1");
+            assert_eq!(fields["feature_counts"]["roads"], 9);
+            assert_eq!(fields["description"], "before");
+            // the metadata still reads back as `Metadata`
+            assert_eq!(*reader.get_metadata(), metadata);
+            assert_eq!(
+                reader.get_tile(s2.then_some(Face::Face3), 0, 0, 0),
+                Some(b"tile".to_vec())
+            );
+        }
+
+        // without extra fields the metadata is serialized as is
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"tile").unwrap();
+        writer.commit_wm(&metadata);
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(
+            reader.get_metadata_raw(),
+            serde_json::to_vec(&metadata).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_commit_to_writer() {
+        let metadata = Metadata {
+            name: "copies".into(),
+            ..Default::default()
+        };
+        let wm = |writer: &mut PMTilesWriter| {
+            // enough tiles for leaf directories
+            for id in 0..Tile::global_tile_id_range(7).end {
+                let data = format!("tile {}", id % 1_000);
+                writer.write_tile(id, data.as_bytes(), None).unwrap();
+            }
+        };
+        let s2 = |writer: &mut PMTilesWriter| {
+            writer.write_tile_s2(Face::Face0, 0, 0, 0, b"zero").unwrap();
+            writer.write_tile_s2(Face::Face4, 2, 1, 3, b"four").unwrap();
+        };
+        for write in [&wm as &dyn Fn(&mut PMTilesWriter), &s2] {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            write(&mut writer);
+            let mut first = LocalWriter::new();
+            let first_result = writer.commit_to_writer(&mut first, &metadata);
+            let mut second = LocalWriter::new();
+            let second_result = writer.commit_to_writer(&mut second, &metadata);
+            // the writer's own data writer is untouched
+            assert_eq!(writer.take().len(), S2_ROOT_SIZE + writer.offset as usize);
+            let result = writer.commit(&metadata).unwrap();
+
+            let data = writer.take();
+            assert_eq!(first.take(), data);
+            assert_eq!(second.take(), data);
+            assert_eq!(first_result, Some(result));
+            assert_eq!(second_result, Some(result));
+        }
+
+        // the tile data of a writer that can not be read back is not copied
+        let mut writer =
+            PMTilesWriter::new(Compression::None, Box::new(AppendOnlyWriter::default()));
+        wm(&mut writer);
+        let mut copy = LocalWriter::new();
+        assert_eq!(writer.commit_to_writer(&mut copy, &metadata), None);
+        assert!(copy.take().is_empty());
+
+        // to a file
+        let temp_file = NamedTempFile::new().unwrap();
+        let file_path = temp_file.path().to_string_lossy().into_owned();
+        let mut file_writer = FileWriter::create(&file_path).unwrap();
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        wm(&mut writer);
+        writer
+            .commit_to_writer(&mut file_writer, &metadata)
+            .unwrap();
+        let mut reader = PMTilesReader::new(FileManager::new(&file_path).unwrap(), None);
+        assert_eq!(reader.get_metadata(), &metadata);
+        assert!(reader.get_header().leaf_directory_length > 0);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0), Some(b"tile 0".to_vec()));
+        let tile = Tile::from_id(5_000);
+        assert_eq!(
+            reader.get_tile_zxy(tile.zoom, tile.x, tile.y),
+            Some(b"tile 0".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_prepare_commit() {
+        let metadata = Metadata {
+            name: "plan".into(),
+            ..Default::default()
+        };
+        let build = |s2: bool| {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            writer.set_tile_type(TileType::Pbf);
+            for x in 0..4 {
+                for y in 0..4 {
+                    let data = [x as u8, y as u8];
+                    if s2 {
+                        writer.write_tile_s2(Face::Face0, 2, x, y, &data).unwrap();
+                        writer.write_tile_s2(Face::Face4, 2, x, y, &data).unwrap();
+                    } else {
+                        writer.write_tile_xyz(2, x, y, &data).unwrap();
+                    }
+                }
+            }
+            writer
+        };
+
+        for s2 in [false, true] {
+            let mut committed = build(s2);
+            let expected = committed.commit(&metadata).unwrap();
+
+            let mut planned = build(s2);
+            let size_before = planned.data_writer.current_size();
+            let plan = planned.prepare_commit(&metadata);
+            // nothing is written until the plan is applied
+            assert_eq!(planned.data_writer.current_size(), size_before);
+            assert_eq!(plan.result, expected);
+            assert_eq!(
+                plan.head.len() as u64,
+                S2_HEADER_SIZE_BYTES as u64
+                    + expected.root_size_bytes
+                    + expected.metadata_size_bytes
+            );
+            assert_eq!(plan.leaves.len() as u64, expected.leaf_size_bytes);
+
+            plan.apply(planned.data_writer.as_mut());
+            let data = planned.take();
+            assert_eq!(data, committed.take());
+
+            let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+            assert_eq!(reader.get_header().is_s2, s2);
+            assert_eq!(reader.get_metadata(), &metadata);
+            if s2 {
+                assert_eq!(reader.get_tile_s2(Face::Face4, 2, 3, 1), Some(vec![3, 1]));
+            } else {
+                assert_eq!(reader.get_tile_zxy(2, 3, 1), Some(vec![3, 1]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_commit_and_verify() {
+        // local wm
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Pbf);
+        for zoom in 0..4 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let data = format!("{zoom}/{x}/{y}");
+                    writer.write_tile_xyz(zoom, x, y, data.as_bytes()).unwrap();
+                }
+            }
+        }
+        // duplicated contents
+        writer.write_tile_xyz(4, 0, 0, b"same").unwrap();
+        writer.write_tile_xyz(4, 0, 1, b"same").unwrap();
+        let result = writer.commit_and_verify(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 87);
+
+        // file s2
+        let temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let file_path = temp_file.path().to_string_lossy().into_owned();
+        let file_writer = FileWriter::create(&file_path).unwrap();
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(file_writer));
+        writer
+            .write_tile_s2(Face::Face0, 0, 0, 0, b"face 0")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face3, 1, 1, 0, b"face 3")
+            .unwrap();
+        writer
+            .write_tile_s2(Face::Face5, 2, 3, 3, b"face 5")
+            .unwrap();
+        let result = writer.commit_and_verify(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 3);
+
+        // corrupted tile data is caught
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(writer.verify(&result), Ok(()));
+        writer.data_writer.write_data(b"j", S2_ROOT_SIZE as u64);
+        assert_eq!(
+            writer.verify(&result),
+            Err(VerifyError::TileDataMismatch { tile_id: 0 })
+        );
+        // so is an emptied root directory
+        let empty_root = Directory::default().serialize();
+        writer
+            .data_writer
+            .write_data(&empty_root, S2_HEADER_SIZE_BYTES as u64);
+        assert_eq!(
+            writer.verify(&result),
+            Err(VerifyError::MissingTile { tile_id: 0 })
+        );
+        // and a header that does not match
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        writer.set_tile_type(TileType::Png);
+        assert_eq!(writer.verify(&result), Err(VerifyError::HeaderMismatch));
+        // nothing to read back from
+        let mut writer =
+            PMTilesWriter::new(Compression::None, Box::new(AppendOnlyWriter::default()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        assert_eq!(
+            writer.commit_and_verify(&Metadata::default()),
+            Err(VerifyError::Unreadable)
+        );
+
+        // raw tiles are not hashed even with dedup on
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile(0, b"hashed", None).unwrap();
+        writer
+            .write_tile_raw(Entry::new(1, 6, 3, 2), Some(b"raw"), None)
+            .unwrap();
+        writer
+            .write_tile_raw(Entry::new(3, 0, 6, 1), None, None)
+            .unwrap();
+        let result = writer.commit_and_verify(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 4);
+        // but a raw tile of the wrong length is still caught
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer
+            .write_tile_raw(Entry::new(0, 0, 3, 1), Some(b"raw"), None)
+            .unwrap();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(writer.verify(&result), Ok(()));
+        let shorter_root = Directory::new(vec![Entry::new(0, 0, 2, 1)]).serialize();
+        writer
+            .data_writer
+            .write_data(&shorter_root, S2_HEADER_SIZE_BYTES as u64);
+        assert_eq!(
+            writer.verify(&result),
+            Err(VerifyError::TileDataMismatch { tile_id: 0 })
+        );
+
+        // nothing to commit
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert!(matches!(
+            writer.commit_and_verify(&Metadata::default()),
+            Err(VerifyError::Commit(_))
+        ));
+    }
+
+    #[test]
+    fn test_wm_magic_bytes() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let data = writer.take();
+        assert_eq!(&data[0..2], b"PM");
+        assert_eq!(data[7], 3);
+        let mut reader = PMTilesReader::new(LocalManager::new(data), None);
+        assert_eq!(reader.is_s2(), Ok(false));
+        assert!(!reader.get_header().is_s2);
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer
+            .write_tile_s2(Face::Face1, 0, 0, 0, b"hello")
+            .unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(&writer.take()[0..2], b"S2");
+    }
+
+    #[test]
+    fn test_hashers() {
+        let write = |mut writer: PMTilesWriter| {
+            for zoom in 0..3 {
+                for x in 0..(1 << zoom) {
+                    for y in 0..(1 << zoom) {
+                        let data = format!("tile {}", (x + y) % 3);
+                        writer.write_tile_xyz(zoom, x, y, data.as_bytes()).unwrap();
+                    }
+                }
+            }
+            let result = writer.commit(&Metadata::default()).unwrap();
+            (result, writer.take())
+        };
+        let (default_result, default_data) = write(PMTilesWriter::new_with_hasher(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+            DefaultHasher,
+        ));
+        let (fast_result, fast_data) = write(PMTilesWriter::new_with_hasher(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+            FastHasher,
+        ));
+        let (convenience_result, convenience_data) = write(PMTilesWriter::with_fast_hash(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+        ));
+        assert_eq!(default_result.unique_contents, 3);
+        assert_eq!(fast_result.unique_contents, 3);
+        assert_eq!(convenience_result, fast_result);
+        // the hash only decides what is deduplicated, the archives are the same
+        assert!(default_data == fast_data);
+        assert!(convenience_data == fast_data);
+
+        let mut default_reader = PMTilesReader::new(LocalManager::new(default_data), None);
+        let mut fast_reader = PMTilesReader::new(LocalManager::new(fast_data), None);
+        for zoom in 0..3 {
+            for x in 0..(1 << zoom) {
+                for y in 0..(1 << zoom) {
+                    let expected = format!("tile {}", (x + y) % 3).into_bytes();
+                    assert_eq!(default_reader.get_tile_zxy(zoom, x, y).unwrap(), expected);
+                    assert_eq!(fast_reader.get_tile_zxy(zoom, x, y).unwrap(), expected);
+                }
+            }
+        }
+
+        // closures are hashers too, this one dedups tiles of the same length
+        let length_hasher = |data: &[u8]| {
+            let mut hash = [0u8; 32];
+            hash[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            hash
+        };
+        let (result, _) = write(PMTilesWriter::new_with_hasher(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+            length_hasher,
+        ));
+        assert_eq!(result.tiles_written, 21);
+        assert_eq!(result.unique_contents, 1);
+    }
+
+    #[test]
+    fn test_new_with_capacity() {
+        let write = |mut writer: PMTilesWriter| {
+            for tile in (0..=4).flat_map(|z| crate::TileRange::for_zoom(z).iter()) {
+                let data = [(tile % 7) as u8; 3];
+                writer.write_tile(tile, &data, None).unwrap();
+            }
+            writer.commit(&Metadata::default()).unwrap();
+            (writer.tile_entries.entries.capacity(), writer.take())
+        };
+        let tiles = Tile::zoom_tile_count(4) as usize * 4 / 3 + 1;
+        let (_, expected) = write(PMTilesWriter::new(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+        ));
+        let (capacity, data) = write(PMTilesWriter::new_with_capacity(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+            tiles,
+        ));
+        assert_eq!(data, expected);
+        // never reallocated
+        assert_eq!(capacity, tiles);
+    }
+
+    #[test]
+    fn test_no_dedup() {
+        let mut writer =
+            PMTilesWriter::with_no_dedup(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"same").unwrap();
+        writer.write_tile_xyz(1, 0, 0, b"same").unwrap();
+        writer.write_tile_xyz(1, 0, 1, b"same").unwrap();
+        writer.write_tile_xyz(1, 1, 1, b"other").unwrap();
+        writer.write_tile_xyz(1, 1, 0, b"same").unwrap();
+        assert!(writer.hash_to_offset.is_empty());
+        let result = writer.commit_and_verify(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 5);
+        assert_eq!(result.unique_contents, 5);
+        assert_eq!(result.data_size_bytes, 21);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert_eq!(header.n_tile_contents, header.n_tile_entries);
+        assert_eq!(header.n_tile_entries, 5);
+        assert_eq!(reader.get_tile_zxy(0, 0, 0).unwrap(), b"same");
+        assert_eq!(reader.get_tile_zxy(1, 0, 1).unwrap(), b"same");
+        assert_eq!(reader.get_tile_zxy(1, 1, 1).unwrap(), b"other");
+        assert_eq!(reader.get_tile_zxy(1, 1, 0).unwrap(), b"same");
+
+        // the same tiles with dedup
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"same").unwrap();
+        writer.write_tile_xyz(1, 0, 0, b"same").unwrap();
+        writer.write_tile_xyz(1, 1, 1, b"other").unwrap();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.unique_contents, 2);
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        let calls: Arc<Mutex<Vec<(u64, u64)>>> = Arc::default();
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        let captured = calls.clone();
+        writer.set_progress_callback(move |tiles, bytes| {
+            captured.lock().unwrap().push((tiles, bytes))
+        });
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        writer.write_tile_xyz(1, 0, 0, b"hello").unwrap();
+        writer.write_tile_xyz(1, 0, 1, b"world!").unwrap();
+        writer
+            .write_tile_s2_batch(Face::Face0, &[(2, 0, 0, b"a"), (1, 1, 0, b"b")])
+            .unwrap();
+        // a failed write reports nothing
+        let mut enforced = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_clustering_enforcement(ClusteringMode::Enforce);
+        enforced.write_tile_xyz(1, 0, 0, b"x").unwrap();
+        enforced.set_progress_callback(|_, _| panic!("nothing was written"));
+        enforced.write_tile_xyz(0, 0, 0, b"x").unwrap_err();
+        let result = writer.commit(&Metadata::default()).unwrap();
+
+        let root = S2_ROOT_SIZE as u64;
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            calls[..5],
+            [
+                (1, root + 5),
+                (2, root + 5),
+                (3, root + 11),
+                (4, root + 12),
+                (5, root + 13)
+            ]
+        );
+        assert_eq!(calls.len(), 6);
+        assert!(calls
+            .windows(2)
+            .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+        // after the commit the size is the whole archive
+        assert_eq!(calls[5], (result.tiles_written, writer.take().len() as u64));
+    }
+
+    #[test]
+    fn test_replace_tile() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        // one run of 4 tiles
+        for tile_id in 1..5 {
+            writer.write_tile(tile_id, b"sea", None).unwrap();
+        }
+        writer.write_tile(5, b"land", None).unwrap();
+        assert_eq!(writer.tile_entries.len(), 2);
+
+        writer.replace_tile(2, b"island", None).unwrap();
+        assert_eq!(
+            writer.tile_entries.entries,
+            vec![
+                Entry::new(1, 0, 3, 1),
+                Entry::new(2, 7, 6, 1),
+                Entry::new(3, 0, 3, 2),
+                Entry::new(5, 3, 4, 1),
+            ]
+        );
+        // the same data as another tile is deduplicated
+        writer.replace_tile(4, b"land", None).unwrap();
+        assert_eq!(writer.tile_entries.entries[3], Entry::new(4, 3, 4, 1));
+        assert_eq!(
+            writer.replace_tile(0, b"nothing here", None),
+            Err(WriteError::TileNotFound { tile_id: 0 })
+        );
+        assert_eq!(
+            writer.replace_tile(6, b"nothing here", None),
+            Err(WriteError::TileNotFound { tile_id: 6 })
+        );
+
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 5);
+        assert_eq!(result.unique_contents, 3);
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert!(!header.clustered);
+        let tiles: Vec<Vec<u8>> = (1..6)
+            .map(|tile_id| {
+                let Tile { zoom, x, y } = Tile::from_id(tile_id);
+                reader.get_tile_zxy(zoom, x, y).unwrap()
+            })
+            .collect();
+        assert_eq!(tiles, [&b"sea"[..], b"island", b"sea", b"land", b"land"]);
+
+        // S2
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_s2(Face::Face1, 0, 0, 0, b"s2").unwrap();
+        assert_eq!(
+            writer.replace_tile(0, b"s2!", Some(Face::Face2)),
+            Err(WriteError::TileNotFound { tile_id: 0 })
+        );
+        writer.replace_tile(0, b"s2!", Some(Face::Face1)).unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.get_tile_s2(Face::Face1, 0, 0, 0).unwrap(), b"s2!");
+    }
+
+    #[test]
+    fn test_open_existing() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Png);
+        writer.set_bounds(-10.0, -5.0, 10.0, 5.0);
+        for tile_id in 0..5 {
+            writer.write_tile(tile_id, b"old", None).unwrap();
+        }
+        writer.write_tile(5, b"older", None).unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let existing = writer.take();
+
+        let mut writer = PMTilesWriter::open_existing(
+            LocalManager::new(existing.clone()),
+            Box::new(LocalWriter::new()),
+        )
+        .unwrap();
+        assert_eq!(writer.offset, 8);
+        assert_eq!(writer.tile_contents, 2);
+        writer.write_tile(6, b"new", None).unwrap();
+        // deduplicated against the existing data
+        writer.write_tile(7, b"older", None).unwrap();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 8);
+        assert_eq!(result.unique_contents, 3);
+        assert_eq!(result.data_size_bytes, 11);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert_eq!(header.tile_type, TileType::Png);
+        assert_eq!(header.max_longitude, 10.0);
+        assert!(header.clustered);
+        let expected = [
+            b"old",
+            b"old",
+            b"old",
+            b"old",
+            b"old",
+            &b"older"[..],
+            b"new",
+            b"older",
+        ];
+        for (tile_id, data) in expected.iter().enumerate() {
+            let Tile { zoom, x, y } = Tile::from_id(tile_id as u64);
+            assert_eq!(reader.get_tile_zxy(zoom, x, y).unwrap(), *data);
+        }
+
+        // an archive from another writer, with its data after the metadata
+        let writer = PMTilesWriter::open_existing(
+            FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap(),
+            Box::new(LocalWriter::new()),
+        );
+        let mut writer = writer.unwrap();
+        assert_eq!(writer.compression, Compression::Gzip);
+        writer.write_tile_xyz(1, 0, 0, b"not really gzip").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut fixture = PMTilesReader::new(
+            FileManager::new("./test/fixtures/test_fixture_1.pmtiles").unwrap(),
+            None,
+        );
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.get_header().n_addressed_tiles, 2);
+        // the stored (gzipped) bytes are copied as is
+        let (old, new) = (
+            fixture.get_entries(None).unwrap()[0],
+            reader.get_entries(None).unwrap()[0],
+        );
+        assert_eq!(new.tile_id, 0);
+        assert_eq!(reader.get_entry_data(&new), fixture.get_entry_data(&old));
+
+        assert_eq!(
+            PMTilesWriter::open_existing(
+                LocalManager::new(vec![0; 127]),
+                Box::new(LocalWriter::new())
+            )
+            .unwrap_err(),
+            ReadError::InvalidMagicBytes
+        );
+    }
+
+    #[test]
+    fn test_open_existing_s2() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        // enough tiles for a leaf directory
+        for tile_id in 0..20_000u64 {
+            writer
+                .write_tile(tile_id, &tile_id.to_le_bytes(), Some(Face::Face2))
+                .unwrap();
+        }
+        writer.write_tile_s2(Face::Face4, 0, 0, 0, b"four").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let existing = writer.take();
+
+        let mut writer =
+            PMTilesWriter::open_existing(LocalManager::new(existing), Box::new(LocalWriter::new()))
+                .unwrap();
+        writer.write_tile_s2(Face::Face4, 1, 1, 1, b"more").unwrap();
+        writer.write_tile_s2(Face::Face5, 0, 0, 0, b"five").unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(reader.get_header().is_s2);
+        assert_eq!(
+            reader.face_tile_count().unwrap(),
+            Some([0, 0, 20_000, 0, 2, 1])
+        );
+        let tile = Tile::from_id(12_345);
+        assert_eq!(
+            reader
+                .get_tile_s2(Face::Face2, tile.zoom, tile.x, tile.y)
+                .unwrap(),
+            12_345u64.to_le_bytes()
+        );
+        assert_eq!(reader.get_tile_s2(Face::Face4, 0, 0, 0).unwrap(), b"four");
+        assert_eq!(reader.get_tile_s2(Face::Face4, 1, 1, 1).unwrap(), b"more");
+        assert_eq!(reader.get_tile_s2(Face::Face5, 0, 0, 0).unwrap(), b"five");
+    }
+
+    #[test]
+    fn test_commit_incremental() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let build = || {
+            let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+            for tile_id in 0..30_000u64 {
+                writer
+                    .write_tile(tile_id, &tile_id.to_le_bytes(), None)
+                    .unwrap();
+            }
+            writer
+        };
+        let mut writer = build();
+        let expected = writer.commit(&Metadata::default()).unwrap();
+        assert!(expected.leaf_size_bytes > 0);
+        let expected_data = writer.take();
+
+        let mut writer = build();
+        let progress = Arc::new(AtomicU64::new(0));
+        let captured = progress.clone();
+        writer.set_progress_callback(move |_, bytes| captured.store(bytes, Ordering::Relaxed));
+        let result = writer
+            .commit_incremental(&Metadata::default(), 4096)
+            .unwrap();
+        assert_eq!(result, expected);
+        // only the root directory was kept, so the archive can not be committed again
+        assert!(writer.tile_entries.is_empty());
+        assert_eq!(
+            writer.commit(&Metadata::default()),
+            Err(vec![WriterWarning::EntriesFreed])
+        );
+        assert_eq!(
+            writer.commit_incremental(&Metadata::default(), 4096),
+            Err(WriteError::Commit(vec![WriterWarning::EntriesFreed]))
+        );
+        let data = writer.take();
+        assert!(data == expected_data);
+        assert_eq!(progress.load(Ordering::Relaxed), data.len() as u64);
+
+        // smaller leaves, or doubled until the root fits (8 entries per leaf here)
+        for (chunk_size, leaves) in [(100, 300), (1, 30_000 / 8)] {
+            let mut writer = build();
+            let result = writer
+                .commit_incremental(&Metadata::default(), chunk_size)
+                .unwrap();
+            assert_eq!(result.tiles_written, 30_000);
+            let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+            assert_eq!(reader.leaf_directory_count().unwrap(), leaves);
+            for tile_id in [0, 99, 100, 12_345, 29_999] {
+                let Tile { zoom, x, y } = Tile::from_id(tile_id);
+                assert_eq!(
+                    reader.get_tile_zxy(zoom, x, y).unwrap(),
+                    tile_id.to_le_bytes()
+                );
+            }
+        }
+
+        // no leaves needed
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        let result = writer
+            .commit_incremental(&Metadata::default(), 4096)
+            .unwrap();
+        assert_eq!(result.leaf_size_bytes, 0);
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        assert_eq!(
+            writer.commit_incremental(&Metadata::default(), 4096),
+            Err(WriteError::Commit(vec![
+                WriterWarning::NoTilesWritten,
+                WriterWarning::TileTypeUnknown,
+                WriterWarning::MissingBounds,
+                WriterWarning::MissingZoomRange
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_validator() {
+        let png = [&PNG_SIGNATURE[..], b"\0\0\0\rIHDR"].concat();
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_validator(Box::new(PngMagicValidator));
+        writer.write_tile_xyz(0, 0, 0, &png).unwrap();
+        assert_eq!(
+            writer.write_tile_xyz(1, 0, 0, b"GIF89a"),
+            Err(WriteError::ValidationFailed(
+                "tile 1 does not start with the PNG signature".into()
+            ))
+        );
+        assert!(writer.write_tile_xyz(1, 0, 1, &png[..7]).is_err());
+        assert!(writer.replace_tile(0, b"", None).is_err());
+        // refused tiles are not written
+        assert_eq!(writer.addressed_tiles, 1);
+        assert_eq!(writer.offset, png.len() as u64);
+
+        let validator = PbfMagicValidator;
+        // field 3 (layers) as a length delimited message
+        assert_eq!(validator.validate(0, &[0x1a, 0x02, 0x08, 0x01]), Ok(()));
+        assert_eq!(validator.validate(0, &[]), Ok(()));
+        // wire types 6 and 7 do not exist, field 0 is reserved
+        assert!(validator.validate(0, &[0x1e]).is_err());
+        assert!(validator.validate(0, &[0x0f]).is_err());
+        assert!(validator.validate(0, &[0x02, 0x00]).is_err());
+        // gzipped
+        assert!(validator.validate(0, &[0x1f, 0x8b]).is_err());
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_validator(Box::new(|tile_id: u64, data: &[u8]| {
+                if data.len() > 4 {
+                    Err(format!("tile {tile_id} is too large"))
+                } else {
+                    Ok(())
+                }
+            }));
+        writer.write_tile(0, b"tiny", None).unwrap();
+        assert_eq!(
+            writer.write_tile(1, b"large", None),
+            Err(WriteError::ValidationFailed("tile 1 is too large".into()))
+        );
+        writer.commit(&Metadata::default()).unwrap();
+    }
+
+    #[test]
+    fn test_split_by_face() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        let handles: Vec<_> = writer
+            .split_by_face()
+            .into_iter()
+            .map(|face_writer| {
+                std::thread::spawn(move || {
+                    let face = u8::from(face_writer.face());
+                    for zoom in 0..3 {
+                        for x in 0..(1 << zoom) {
+                            for y in 0..(1 << zoom) {
+                                let data = [face, zoom, x as u8, y as u8];
+                                face_writer.write_tile_xyz(zoom, x, y, &data).unwrap();
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 6 * 21);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert!(reader.get_header().is_s2);
+        for face in [
+            Face::Face0,
+            Face::Face1,
+            Face::Face2,
+            Face::Face3,
+            Face::Face4,
+            Face::Face5,
+        ] {
+            for zoom in 0..3 {
+                for x in 0..(1 << zoom) {
+                    for y in 0..(1 << zoom) {
+                        assert_eq!(
+                            reader.get_tile_s2(face, zoom, x, y).unwrap(),
+                            [u8::from(face), zoom, x as u8, y as u8]
+                        );
+                    }
+                }
+            }
+        }
+
+        // a refused tile stops the commit, receive_face_tiles says why
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_clustering_enforcement(ClusteringMode::Enforce);
+        let [face_0, ..] = writer.split_by_face();
+        face_0.write_tile_xyz(1, 0, 0, b"a").unwrap();
+        face_0.write_tile_xyz(0, 0, 0, b"b").unwrap();
+        assert_eq!(
+            writer.commit(&Metadata::default()),
+            Err(vec![WriterWarning::FaceTileRefused])
+        );
+        assert_eq!(writer.receive_face_tiles(), Ok(()));
+        writer.abort().unwrap();
+        assert_eq!(
+            face_0.write_tile_xyz(2, 0, 0, b"c"),
+            Err(WriteError::Aborted)
+        );
+    }
+
+    #[test]
+    fn test_writer_shared() {
+        let shared = PMTilesWriterShared::new(PMTilesWriter::new(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+        ));
+        let handles: Vec<_> = (0..8u64)
+            .map(|y| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for x in 0..8 {
+                        shared.write_tile_xyz(3, x, y, &[x as u8, y as u8]).unwrap();
+                    }
+                    shared
+                        .write_tile_s2(Face::Face1, 3, 0, y, &[y as u8])
+                        .unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        shared.lock().set_tile_type(TileType::Png);
+        // only the WM tiles are committed, but every tile written is counted
+        let result = shared.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 72);
+
+        let mut writer = shared.into_inner().unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.get_header().tile_type, TileType::Png);
+        for x in 0..8 {
+            for y in 0..8 {
+                assert_eq!(reader.get_tile_zxy(3, x, y).unwrap(), [x as u8, y as u8]);
+            }
+        }
+
+        let shared = PMTilesWriterShared::new(PMTilesWriter::new(
+            Compression::None,
+            Box::new(LocalWriter::new()),
+        ));
+        let other = shared.clone();
+        assert!(shared.into_inner().is_none());
+        assert!(other.into_inner().is_some());
+    }
+
+    #[test]
+    fn test_write_tile_run() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_run(0, 0, b"nothing", None).unwrap();
+        writer.write_tile_run(0, 1_000_000, b"ocean", None).unwrap();
+        // extends the previous run
+        writer.write_tile_run(1_000_000, 5, b"ocean", None).unwrap();
+        writer.write_tile_run(1_000_005, 1, b"land", None).unwrap();
+        writer
+            .write_tile_run(1_000_006, 10, b"ocean", None)
+            .unwrap();
+        assert_eq!(
+            writer.tile_entries.entries,
+            vec![
+                Entry::new(0, 0, 5, 1_000_005),
+                Entry::new(1_000_005, 5, 4, 1),
+                Entry::new(1_000_006, 0, 5, 10),
+            ]
+        );
+        assert_eq!(writer.clustering_score(), 1.0);
+        // a run going back is out of order, like a single tile
+        assert!(writer.write_tile_run(5, 2, b"land", None).is_ok());
+        assert!(!writer.clustered);
+
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_clustering_enforcement(ClusteringMode::Enforce);
+        writer.write_tile_run(10, 3, b"a", None).unwrap();
+        assert_eq!(
+            writer.write_tile_run(0, 3, b"a", None),
+            Err(WriteError::OutOfOrder {
+                expected: 12,
+                got: 0
+            })
+        );
+        writer.set_tile_type(TileType::Pbf);
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 3);
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let tile = Tile::from_id(12);
+        assert_eq!(
+            reader.get_tile_zxy(tile.zoom, tile.x, tile.y),
+            Some(b"a".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_write_tile_raw() {
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.set_tile_type(TileType::Png);
+        // sorted layout, written in reverse: tile 0 at 0, tile 1 at 1 and tile 2 at 3
+        writer
+            .write_tile_raw(Entry::new(2, 3, 3, 1), Some(b"ccc"), None)
+            .unwrap();
+        writer
+            .write_tile_raw(Entry::new(1, 1, 2, 1), Some(b"bb"), None)
+            .unwrap();
+        writer
+            .write_tile_raw(Entry::new(0, 0, 1, 1), Some(b"a"), None)
+            .unwrap();
+        // tiles 3 and 4 reuse tile 1's data
+        writer
+            .write_tile_raw(Entry::new(3, 1, 2, 2), None, None)
+            .unwrap();
+        assert!(!writer.clustered);
+
+        let invalid = |tile_id| Err(WriteError::InvalidRawEntry { tile_id });
+        assert_eq!(
+            writer.write_tile_raw(Entry::new(5, 6, 1, 0), Some(b"d"), None),
+            invalid(5)
+        );
+        assert_eq!(
+            writer.write_tile_raw(Entry::new(5, 6, 2, 1), Some(b"d"), None),
+            invalid(5)
+        );
+        assert_eq!(
+            writer.write_tile_raw(Entry::new(5, 5, 2, 1), None, None),
+            invalid(5)
+        );
+        assert_eq!(writer.offset, 6);
+
+        // the sorted entries follow the data, so the archive is clustered after all
+        writer.sort_and_recluster();
+        let result = writer.commit(&Metadata::default()).unwrap();
+        assert_eq!(result.tiles_written, 5);
+        assert_eq!(result.unique_contents, 3);
+        assert_eq!(result.data_size_bytes, 6);
+
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        let header = reader.get_header();
+        assert!(header.clustered);
+        assert_eq!(header.n_addressed_tiles, 5);
+        assert_eq!(
+            reader.get_entries(None).unwrap(),
+            vec![
+                Entry::new(0, 0, 1, 1),
+                Entry::new(1, 1, 2, 1),
+                Entry::new(2, 3, 3, 1),
+                Entry::new(3, 1, 2, 2),
+            ]
+        );
+        for (id, data) in [
+            (0, &b"a"[..]),
+            (1, b"bb"),
+            (2, b"ccc"),
+            (3, b"bb"),
+            (4, b"bb"),
+        ] {
+            let tile = Tile::from_id(id);
+            assert_eq!(
+                reader.get_tile_zxy(tile.zoom, tile.x, tile.y).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_tile_type_detection() {
+        let webp = include_bytes!("../test/fixtures/tile.webp");
+        let png = include_bytes!("../test/fixtures/tile.png");
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_tile_type_detection();
+        writer.write_tile_xyz(0, 0, 0, webp).unwrap();
+        // only the first tile is looked at
+        writer.write_tile_xyz(1, 0, 0, png).unwrap();
+        writer.commit(&Metadata::default()).unwrap();
+        let mut reader = PMTilesReader::new(LocalManager::new(writer.take()), None);
+        assert_eq!(reader.get_header().tile_type, TileType::Webp);
+
+        // an explicit tile type wins
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()))
+            .with_tile_type_detection();
+        writer.set_tile_type(TileType::Png);
+        writer.write_tile_xyz(0, 0, 0, &[0x1a, 0x00]).unwrap();
+        assert_eq!(writer.tile_type, TileType::Png);
+
+        // gzipped vector tiles are not recognized, and nothing is guessed by default
+        let mut writer = PMTilesWriter::new(Compression::Gzip, Box::new(LocalWriter::new()))
+            .with_tile_type_detection();
+        writer.write_tile_xyz(0, 0, 0, &[0x1f, 0x8b, 0x08]).unwrap();
+        assert_eq!(writer.tile_type, TileType::Unknown);
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, png).unwrap();
+        assert_eq!(writer.tile_type, TileType::Unknown);
+    }
+
+    #[test]
+    fn test_abort() {
+        let temp_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let file_path = temp_file.path().to_string_lossy().into_owned();
+        let file_writer = FileWriter::create(&file_path).unwrap();
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(file_writer))
+            .with_clustering_enforcement(ClusteringMode::Enforce);
+        writer.write_tile_xyz(1, 0, 0, b"hello").unwrap();
+        // simulate a failure partway through
+        assert!(writer.write_tile_xyz(0, 0, 0, b"world").is_err());
+        assert!(std::fs::metadata(&file_path).unwrap().len() > 0);
+
+        writer.abort().unwrap();
+        assert_eq!(std::fs::metadata(&file_path).unwrap().len(), 0);
+        assert_eq!(
+            writer.write_tile_xyz(2, 0, 0, b"again"),
+            Err(WriteError::Aborted)
+        );
+        assert_eq!(
+            writer.commit(&Metadata::default()),
+            Err(vec![WriterWarning::Aborted])
+        );
+        assert_eq!(std::fs::metadata(&file_path).unwrap().len(), 0);
+
+        // local
+        let mut writer = PMTilesWriter::new(Compression::None, Box::new(LocalWriter::new()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        writer.abort().unwrap();
+        assert!(writer.data_writer.take().is_empty());
+        assert_eq!(
+            writer.write_tile_xyz(1, 0, 0, b"hi"),
+            Err(WriteError::Aborted)
+        );
+
+        // a writer that can not truncate still aborts, but says so
+        let mut writer =
+            PMTilesWriter::new(Compression::None, Box::new(AppendOnlyWriter::default()));
+        writer.write_tile_xyz(0, 0, 0, b"hello").unwrap();
+        assert_eq!(
+            writer.abort().unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+        assert_eq!(
+            writer.write_tile_xyz(1, 0, 0, b"hi"),
+            Err(WriteError::Aborted)
+        );
+    }
+
+    /// A writer that only implements the required `DataWriter` methods
+    #[derive(Debug, Default)]
+    struct AppendOnlyWriter(LocalWriter);
+    impl DataWriter for AppendOnlyWriter {
+        fn write_data(&mut self, data: &[u8], offset: u64) {
+            self.0.write_data(data, offset);
+        }
+
+        fn append_data(&mut self, data: &[u8]) {
+            self.0.append_data(data);
+        }
+
+        fn take(&self) -> Vec<u8> {
+            self.0.take()
+        }
+
+        fn current_size(&self) -> u64 {
+            self.0.current_size()
+        }
+    }
 }